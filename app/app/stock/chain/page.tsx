@@ -154,6 +154,7 @@ export default function ChainPage() {
             const premiumAmount = Math.floor(params.premium * 1_000_000); // Premium in USDC with 6 decimals
             const expiryTimestamp = Math.floor(params.expiry.getTime() / 1000);
             const amount = new BN(params.contracts * 100 * 1_000_000); // 100 shares per contract with 6 decimals
+            const royaltyBps = 0; // No writer royalty on resales
 
             const [coveredCallPda] = PublicKey.findProgramAddressSync(
                 [Buffer.from("covered_call"), wallet.publicKey.toBuffer(), stock.mint.toBuffer(), uid.toArrayLike(Buffer, "le", 8)],
@@ -175,7 +176,7 @@ export default function ChainPage() {
             const sellerXstockAccount = getATA(stock.mint, wallet.publicKey);
 
             const ix = await program.methods
-                .createCoveredCall(uid, new BN(strikePrice), new BN(premiumAmount), new BN(expiryTimestamp), amount)
+                .createCoveredCall(uid, new BN(strikePrice), new BN(premiumAmount), new BN(expiryTimestamp), amount, royaltyBps)
                 .accounts({
                     seller: wallet.publicKey,
                     coveredCall: coveredCallPda,
@@ -252,6 +253,10 @@ export default function ChainPage() {
 
             const buyerQuoteAccount = getAta(data.quoteMint, wallet.publicKey);
             const sellerQuoteAccount = getAta(data.quoteMint, currentSeller);
+            // Resales pay the writer's royalty to the writer's quote account
+            const writerQuoteAccount = isResale && data.royaltyBps > 0
+                ? getAta(data.quoteMint, data.seller)
+                : null;
 
             const ix = await program.methods
                 .buyOption()
@@ -260,6 +265,7 @@ export default function ChainPage() {
                     coveredCall: coveredCallKey,
                     buyerQuoteAccount: buyerQuoteAccount,
                     paymentAccount: sellerQuoteAccount, // Payment goes to current owner (seller or previous buyer)
+                    sellerQuoteAccount: writerQuoteAccount,
                     tokenProgram: TOKEN_PROGRAM_ID,
                 })
                 .instruction();
@@ -115,6 +115,34 @@ export function deriveWithdrawalPda(vaultPda: PublicKey, userPubkey: PublicKey):
     );
 }
 
+export function deriveEpochRecordPda(vaultPda: PublicKey, epoch: number): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("epoch_record"), vaultPda.toBuffer(), new BN(epoch).toArrayLike(Buffer, "le", 8)],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveDepositReceiptPda(vaultPda: PublicKey, userPubkey: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), vaultPda.toBuffer(), userPubkey.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveAllowlistEntryPda(vaultPda: PublicKey, userPubkey: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("allowlist"), vaultPda.toBuffer(), userPubkey.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveWithdrawalSharesPda(vaultPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("withdrawal_shares"), vaultPda.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+}
+
 /**
  * Get the vault program instance
  */
@@ -204,7 +232,8 @@ export async function buildDepositTransaction(
     connection: Connection,
     wallet: Wallet,
     assetId: string,
-    amount: number // in base units (e.g., 1_000_000 for 1 USDC)
+    amount: number, // in base units (e.g., 1_000_000 for 1 USDC)
+    minSharesOut: number = 0
 ): Promise<Transaction> {
     const provider = new AnchorProvider(connection, wallet, { commitment: "confirmed" });
     const program = getVaultProgram(provider);
@@ -215,6 +244,8 @@ export async function buildDepositTransaction(
     const [vaultPda] = deriveVaultPda(assetId);
     const [shareMintPda] = deriveShareMintPda(vaultPda);
     const [vaultTokenAccountPda] = deriveVaultTokenAccountPda(vaultPda);
+    const [depositReceiptPda] = deriveDepositReceiptPda(vaultPda, wallet.publicKey);
+    const vault = await program.account.vault.fetch(vaultPda);
 
     // Get user's token account for the underlying asset
     const userTokenAccount = await getAssociatedTokenAddress(
@@ -225,7 +256,9 @@ export async function buildDepositTransaction(
     // Get user's share token account (create if needed)
     const userShareAccount = await getAssociatedTokenAddress(
         shareMintPda,
-        wallet.publicKey
+        wallet.publicKey,
+        false,
+        vault.shareTokenProgram
     );
 
     const tx = new Transaction();
@@ -239,7 +272,8 @@ export async function buildDepositTransaction(
                 wallet.publicKey,
                 userShareAccount,
                 wallet.publicKey,
-                shareMintPda
+                shareMintPda,
+                vault.shareTokenProgram
             )
         );
     }
@@ -252,22 +286,32 @@ export async function buildDepositTransaction(
                 wallet.publicKey,
                 userShareAccount,
                 wallet.publicKey,
-                shareMintPda
+                shareMintPda,
+                vault.shareTokenProgram
             )
         );
     }
 
+    // Guarded vaults require the wallet's allowlist entry
+    const allowlistEntry = vault.allowlistEnabled
+        ? deriveAllowlistEntryPda(vaultPda, wallet.publicKey)[0]
+        : null;
+
     // Build deposit instruction
     const depositIx = await program.methods
-        .deposit(new BN(amount))
+        .deposit(new BN(amount), new BN(minSharesOut))
         .accounts({
             vault: vaultPda,
             shareMint: shareMintPda,
             vaultTokenAccount: vaultTokenAccountPda,
             userTokenAccount: userTokenAccount,
             userShareAccount: userShareAccount,
+            depositReceipt: depositReceiptPda,
             user: wallet.publicKey,
             tokenProgram: TOKEN_PROGRAM_ID,
+            shareTokenProgram: vault.shareTokenProgram,
+            systemProgram: SystemProgram.programId,
+            allowlistEntry,
         })
         .instruction();
 
@@ -276,6 +320,62 @@ export async function buildDepositTransaction(
     return tx;
 }
 
+/**
+ * Build a claim shares transaction (for a deposit queued in an earlier epoch)
+ */
+export async function buildClaimSharesTransaction(
+    connection: Connection,
+    wallet: Wallet,
+    assetId: string
+): Promise<Transaction> {
+    const provider = new AnchorProvider(connection, wallet, { commitment: "confirmed" });
+    const program = getVaultProgram(provider);
+
+    const [vaultPda] = deriveVaultPda(assetId);
+    const [shareMintPda] = deriveShareMintPda(vaultPda);
+    const [vaultTokenAccountPda] = deriveVaultTokenAccountPda(vaultPda);
+    const [depositReceiptPda] = deriveDepositReceiptPda(vaultPda, wallet.publicKey);
+
+    // Queued deposits convert at the closing price of the epoch they were made in
+    const receipt = await program.account.depositReceipt.fetch(depositReceiptPda);
+    const [epochRecordPda] = deriveEpochRecordPda(vaultPda, Number(receipt.epoch));
+    const vault = await program.account.vault.fetch(vaultPda);
+
+    const userShareAccount = await getAssociatedTokenAddress(
+        shareMintPda,
+        wallet.publicKey,
+        false,
+        vault.shareTokenProgram
+    );
+    // Refunded here if the roll priced the deposit below its minimum shares
+    const userTokenAccount = await getAssociatedTokenAddress(
+        vault.underlyingMint,
+        wallet.publicKey
+    );
+
+    const tx = new Transaction();
+
+    const claimSharesIx = await program.methods
+        .claimShares()
+        .accounts({
+            vault: vaultPda,
+            depositReceipt: depositReceiptPda,
+            epochRecord: epochRecordPda,
+            shareMint: shareMintPda,
+            userShareAccount: userShareAccount,
+            vaultTokenAccount: vaultTokenAccountPda,
+            userTokenAccount: userTokenAccount,
+            user: wallet.publicKey,
+            shareTokenProgram: vault.shareTokenProgram,
+            tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .instruction();
+
+    tx.add(claimSharesIx);
+
+    return tx;
+}
+
 /**
  * Build a request withdrawal transaction
  */
@@ -291,10 +391,14 @@ export async function buildRequestWithdrawalTransaction(
     const [vaultPda] = deriveVaultPda(assetId);
     const [shareMintPda] = deriveShareMintPda(vaultPda);
     const [withdrawalPda] = deriveWithdrawalPda(vaultPda, wallet.publicKey);
+    const [withdrawalSharesPda] = deriveWithdrawalSharesPda(vaultPda);
+    const vault = await program.account.vault.fetch(vaultPda);
 
     const userShareAccount = await getAssociatedTokenAddress(
         shareMintPda,
-        wallet.publicKey
+        wallet.publicKey,
+        false,
+        vault.shareTokenProgram
     );
 
     const tx = new Transaction();
@@ -304,9 +408,12 @@ export async function buildRequestWithdrawalTransaction(
         .accounts({
             vault: vaultPda,
             withdrawalRequest: withdrawalPda,
+            shareMint: shareMintPda,
+            withdrawalShares: withdrawalSharesPda,
             userShareAccount: userShareAccount,
             user: wallet.publicKey,
             systemProgram: SystemProgram.programId,
+            shareTokenProgram: vault.shareTokenProgram,
         })
         .instruction();
 
@@ -333,17 +440,18 @@ export async function buildProcessWithdrawalTransaction(
     const [shareMintPda] = deriveShareMintPda(vaultPda);
     const [vaultTokenAccountPda] = deriveVaultTokenAccountPda(vaultPda);
     const [withdrawalPda] = deriveWithdrawalPda(vaultPda, wallet.publicKey);
+    const [withdrawalSharesPda] = deriveWithdrawalSharesPda(vaultPda);
+
+    // Withdrawals redeem at the closing price of the epoch they were requested in
+    const withdrawal = await program.account.withdrawalRequest.fetch(withdrawalPda);
+    const [epochRecordPda] = deriveEpochRecordPda(vaultPda, Number(withdrawal.requestEpoch));
+    const vault = await program.account.vault.fetch(vaultPda);
 
     const userTokenAccount = await getAssociatedTokenAddress(
         config.underlyingMint,
         wallet.publicKey
     );
 
-    const userShareAccount = await getAssociatedTokenAddress(
-        shareMintPda,
-        wallet.publicKey
-    );
-
     const tx = new Transaction();
 
     const processWithdrawalIx = await program.methods
@@ -351,12 +459,14 @@ export async function buildProcessWithdrawalTransaction(
         .accounts({
             vault: vaultPda,
             withdrawalRequest: withdrawalPda,
+            epochRecord: epochRecordPda,
             shareMint: shareMintPda,
+            withdrawalShares: withdrawalSharesPda,
             vaultTokenAccount: vaultTokenAccountPda,
             userTokenAccount: userTokenAccount,
-            userShareAccount: userShareAccount,
             user: wallet.publicKey,
             tokenProgram: TOKEN_PROGRAM_ID,
+            shareTokenProgram: vault.shareTokenProgram,
         })
         .instruction();
 
@@ -377,9 +487,13 @@ export async function getUserShareBalance(
         const [vaultPda] = deriveVaultPda(assetId);
         const [shareMintPda] = deriveShareMintPda(vaultPda);
 
+        // Share mints are owned by SPL Token or Token-2022
+        const shareMintInfo = await connection.getAccountInfo(shareMintPda);
         const userShareAccount = await getAssociatedTokenAddress(
             shareMintPda,
-            userPubkey
+            userPubkey,
+            false,
+            shareMintInfo?.owner ?? TOKEN_PROGRAM_ID
         );
 
         const accountInfo = await connection.getTokenAccountBalance(userShareAccount);
@@ -440,28 +554,180 @@ export async function getUserWithdrawalRequest(
     }
 }
 
+/**
+ * Shares a deposit would be issued, simulated on-chain so rounding matches the program
+ */
+export async function previewDeposit(
+    connection: Connection,
+    wallet: Wallet,
+    assetId: string,
+    amount: number
+): Promise<number> {
+    const provider = new AnchorProvider(connection, wallet, { commitment: "confirmed" });
+    const program = getVaultProgram(provider);
+    const [vaultPda] = deriveVaultPda(assetId);
+
+    const shares = await program.methods
+        .previewDeposit(new BN(amount))
+        .accounts({ vault: vaultPda })
+        .view();
+    return Number(shares);
+}
+
+/**
+ * Underlying and quote paid for an instant redemption, net of the fee, simulated on-chain
+ */
+export async function previewRedeem(
+    connection: Connection,
+    wallet: Wallet,
+    assetId: string,
+    shares: number
+): Promise<{ assets: number; quoteAssets: number; fee: number }> {
+    const provider = new AnchorProvider(connection, wallet, { commitment: "confirmed" });
+    const program = getVaultProgram(provider);
+    const [vaultPda] = deriveVaultPda(assetId);
+
+    const preview = await program.methods
+        .previewRedeem(new BN(shares))
+        .accounts({ vault: vaultPda })
+        .view();
+    return {
+        assets: Number(preview.assets),
+        quoteAssets: Number(preview.quoteAssets),
+        fee: Number(preview.fee),
+    };
+}
+
+export interface EpochHistory {
+    epoch: number;
+    startTimestamp: number;
+    endTimestamp: number;
+    startPricePerShare: number;   // Underlying per share
+    endPricePerShare: number;
+    notionalExposed: string;
+    premiumEarned: string;
+    quotePremiumEarned: string;
+    premiumPerTokenBps: number;
+    minStrike: string;
+    maxStrike: string;
+    settlementPrice: string;
+    payout: string;
+    realizedPnl: string;
+    apy: number;                  // Epoch return annualized, in percent
+}
+
+const SHARE_PRICE_SCALE = 1e9;
+const SECONDS_PER_YEAR = 365 * 24 * 60 * 60;
+
+/**
+ * Fetch the most recent closed epochs from their on-chain epoch records
+ */
+export async function fetchEpochHistory(
+    connection: Connection,
+    wallet: Wallet,
+    assetId: string,
+    limit = 12
+): Promise<EpochHistory[]> {
+    const provider = new AnchorProvider(connection, wallet, { commitment: "confirmed" });
+    const program = getVaultProgram(provider);
+
+    const [vaultPda] = deriveVaultPda(assetId);
+    const vault = await program.account.vault.fetch(vaultPda);
+    const currentEpoch = Number(vault.epoch);
+
+    const epochs: number[] = [];
+    for (let epoch = currentEpoch - 1; epoch >= 0 && epochs.length < limit; epoch--) {
+        epochs.push(epoch);
+    }
+    const records = await program.account.epochRecord.fetchMultiple(
+        epochs.map(epoch => deriveEpochRecordPda(vaultPda, epoch)[0])
+    );
+
+    return records
+        .filter((record: any) => record !== null)
+        .map((record: any) => {
+            const startPricePerShare = Number(record.startPricePerShare) / SHARE_PRICE_SCALE;
+            const endPricePerShare = Number(record.pricePerShare) / SHARE_PRICE_SCALE;
+            const duration = Number(record.endTimestamp) - Number(record.startTimestamp);
+            const epochReturn = startPricePerShare > 0 ? endPricePerShare / startPricePerShare - 1 : 0;
+
+            return {
+                epoch: Number(record.epoch),
+                startTimestamp: Number(record.startTimestamp),
+                endTimestamp: Number(record.endTimestamp),
+                startPricePerShare,
+                endPricePerShare,
+                notionalExposed: record.notionalExposed.toString(),
+                premiumEarned: record.premiumEarned.toString(),
+                quotePremiumEarned: record.quotePremiumEarned.toString(),
+                premiumPerTokenBps: Number(record.premiumPerTokenBps),
+                minStrike: record.minStrike.toString(),
+                maxStrike: record.maxStrike.toString(),
+                settlementPrice: record.settlementPrice.toString(),
+                payout: record.payout.toString(),
+                realizedPnl: record.realizedPnl.toString(),
+                apy: duration > 0 ? epochReturn * (SECONDS_PER_YEAR / duration) * 100 : 0,
+            };
+        });
+}
+
+/**
+ * Receipts of the deposits queued this epoch with a minimum; advance_epoch takes
+ * every one of them to refund those its price falls short of
+ */
+export async function fetchGuardedDepositReceipts(
+    program: any,
+    vaultPda: PublicKey,
+    epoch: BN
+): Promise<PublicKey[]> {
+    // DepositReceipt: discriminator, user, then vault
+    const receipts = await program.account.depositReceipt.all([
+        { memcmp: { offset: 40, bytes: vaultPda.toBase58() } },
+    ]);
+    return receipts
+        .filter((entry: any) => entry.account.epoch.eq(epoch) && !entry.account.minSharesOut.isZero())
+        .map((entry: any) => entry.publicKey as PublicKey);
+}
+
 /**
  * Build a transaction to advance the epoch (Keeper only)
  */
 export async function buildAdvanceEpochTransaction(
     connection: Connection,
     wallet: Wallet,
-    assetId: string,
-    premiumEarned: number = 0
+    assetId: string
 ): Promise<Transaction> {
     const provider = new AnchorProvider(connection, wallet, { commitment: "confirmed" });
     const program = getVaultProgram(provider);
 
     const [vaultPda] = deriveVaultPda(assetId);
+    const [vaultTokenAccountPda] = deriveVaultTokenAccountPda(vaultPda);
+
+    const vault = await program.account.vault.fetch(vaultPda);
+    const [epochRecordPda] = deriveEpochRecordPda(vaultPda, Number(vault.epoch));
+
+    // Fees are minted as shares to the fee recipient's share account
+    const chargesFees = vault.managementFeeBps > 0 || vault.performanceFeeBps > 0;
+    const feeRecipientShareAccount = chargesFees
+        ? await getAssociatedTokenAddress(vault.shareMint, vault.feeRecipient, true, vault.shareTokenProgram)
+        : null;
+    const guardedReceipts = await fetchGuardedDepositReceipts(program, vaultPda, vault.epoch);
 
     const tx = new Transaction();
 
     const advanceEpochIx = await program.methods
-        .advanceEpoch(new BN(premiumEarned))
+        .advanceEpoch()
         .accounts({
             vault: vaultPda,
+            epochRecord: epochRecordPda,
             authority: wallet.publicKey,
+            vaultTokenAccount: vaultTokenAccountPda,
+            systemProgram: SystemProgram.programId,
+            shareMint: chargesFees ? vault.shareMint : null,
+            feeRecipientShareAccount,
+            shareTokenProgram: chargesFees ? vault.shareTokenProgram : null,
         })
+        .remainingAccounts(guardedReceipts.map(pubkey => ({ pubkey, isSigner: false, isWritable: true })))
         .instruction();
 
     tx.add(advanceEpochIx);
@@ -2,14 +2,13 @@
  * xStock Keeper Service
  * 
  * Automates vault epoch transitions:
- * 1. Check if epoch is ready to roll
- * 2. Fetch oracle price
- * 3. Compute strike (spot + delta OTM)
- * 4. Issue RFQ via router
- * 5. Wait for quotes
- * 6. Submit best quote fill
- * 7. Record notional exposure
- * 8. Advance epoch
+ * 1. Record or close the current epoch's auctions
+ * 2. Settle each recorded RFQ after expiry, then settle the epoch
+ * 3. Advance epoch
+ * 4. Fetch oracle price and compute strike (spot + delta OTM)
+ * 5. Start the vault's auction on-chain and announce it via the router
+ * 6. Wait for a maker to fill the RFQ on-chain
+ * 7. Record notional exposure, or close the unfilled auction
  */
 
 import * as anchor from "@coral-xyz/anchor";
@@ -47,7 +46,8 @@ const config = {
     cronSchedule: process.env.CRON_SCHEDULE || "0 */6 * * *", // every 6 hours
     quoteWaitMs: parseInt(process.env.QUOTE_WAIT_MS || "30000"), // 30 seconds
     strikeDeltaBps: parseInt(process.env.STRIKE_DELTA_BPS || "500"), // 5% OTM
-    utilizationTargetBps: parseInt(process.env.UTILIZATION_TARGET_BPS || "5000"), // 50%
+    optionType: (process.env.OPTION_TYPE || "CALL") as "CALL" | "PUT", // PUT for cash-secured put vaults
+    premiumFloorBps: parseInt(process.env.PREMIUM_FLOOR_BPS || "30"), // 0.3% per token
 
     logLevel: process.env.LOG_LEVEL || "info",
     healthPort: parseInt(process.env.HEALTH_PORT || "3010"),
@@ -153,8 +153,11 @@ interface FillResponse {
 }
 
 interface OnChainTxResult {
-    recordExposureTx: string | null;
+    settleRfqTxs: string[];
+    settleEpochTx: string | null;
     advanceEpochTx: string | null;
+    startAuctionTx: string | null;
+    recordExposureTx: string | null;
 }
 
 // ============================================================================
@@ -332,53 +335,109 @@ function computeStrike(spotPrice: number, optionType: "CALL" | "PUT"): number {
 }
 
 /**
- * Calculate notional to expose based on TVL and utilization target
- */
-function calculateNotional(totalAssets: number): number {
-    return Math.floor((totalAssets * config.utilizationTargetBps) / 10000);
-}
-
-/**
- * Create RFQ via router
+ * Announce the vault's on-chain RFQ to makers via the router
  */
-async function createRfq(params: {
+async function announceRfq(params: {
+    rfqAddress: PublicKey;
     underlying: string;
     optionType: "CALL" | "PUT";
+    expiryTs: number;
     strike: number;
     size: number;
+    validUntilTs: number;
     oraclePrice: number;
 }): Promise<RfqResponse | null> {
     try {
-        const now = Math.floor(Date.now() / 1000);
-        const expiryTs = now + config.epochDurationSeconds;
-        // Add 5 second buffer so RFQ doesn't expire while we're waiting for quotes
-        const validUntilTs = now + Math.floor(config.quoteWaitMs / 1000) + 5;
-
         const response = await axios.post<RfqResponse>(
             `${config.rfqRouterUrl}/rfq`,
             {
+                rfqAddress: params.rfqAddress.toBase58(),
                 underlying: params.underlying,
                 optionType: params.optionType,
-                expiryTs,
+                expiryTs: params.expiryTs,
                 strike: params.strike,
                 size: params.size,
-                premiumFloor: Math.floor(params.size * 0.003), // 0.3% minimum premium
-                validUntilTs,
-                settlement: "CASH",
+                premiumFloor: Math.floor((params.size * config.premiumFloorBps) / 10000),
+                validUntilTs: params.validUntilTs,
+                settlement: params.optionType === "CALL" ? "CASH" : "PHYSICAL",
                 oraclePrice: params.oraclePrice,
-                oracleTs: now,
+                oracleTs: Math.floor(Date.now() / 1000),
             },
             { timeout: 10000 }
         );
 
-        logger.info("RFQ created", { rfqId: response.data.rfqId });
+        logger.info("RFQ announced", { rfqId: response.data.rfqId, rfqAddress: params.rfqAddress.toBase58() });
         return response.data;
     } catch (error) {
-        logger.error("Failed to create RFQ", { error });
+        logger.error("Failed to announce RFQ", { error });
         return null;
     }
 }
 
+/**
+ * Record filled auctions and close unfilled ones so the epoch can settle and roll.
+ * Fills that expired before being recorded, and put fills whose maker never approved
+ * delivery, are closed with the premium refunded.
+ * Returns false while an auction can still be filled.
+ */
+async function resolveOpenAuctions(client: OnChainClient): Promise<boolean> {
+    const now = Math.floor(Date.now() / 1000);
+    for (const rfqPda of await client.openAuctions(config.assetId)) {
+        const rfq = await client.fetchRfq(rfqPda);
+        if ("filled" in rfq.status && !(await client.makerApprovesDelivery(config.assetId, rfqPda))) {
+            const tx = await client.rejectFill(config.assetId, rfqPda);
+            logger.info("Rejected put fill without delivery approval", { rfq: rfqPda.toBase58(), tx });
+        } else if ("filled" in rfq.status && now < rfq.expiryTs.toNumber()) {
+            const tx = await client.recordNotionalExposure(config.assetId, rfqPda);
+            logger.info("Recorded notional exposure", { rfq: rfqPda.toBase58(), tx });
+        } else if ("filled" in rfq.status) {
+            const tx = await client.closeAuction(config.assetId, rfqPda);
+            logger.info("Closed expired fill and refunded its premium", { rfq: rfqPda.toBase58(), tx });
+        } else if ("open" in rfq.status && now < rfq.validUntilTs.toNumber()) {
+            logger.info("Auction still open", { rfq: rfqPda.toBase58() });
+            return false;
+        } else {
+            const tx = await client.closeAuction(config.assetId, rfqPda);
+            logger.info("Closed unfilled auction", { rfq: rfqPda.toBase58(), tx });
+        }
+    }
+    return true;
+}
+
+/**
+ * Settle every RFQ recorded this epoch, then the epoch itself.
+ * Returns false while an option has not expired or has no settlement price yet.
+ */
+async function settleCurrentEpoch(client: OnChainClient, txs: OnChainTxResult): Promise<boolean> {
+    const vault = await client.fetchVault(config.assetId);
+    if (vault.epochNotionalExposed.isZero() || vault.epochSettled) {
+        return true;
+    }
+
+    const now = Math.floor(Date.now() / 1000);
+    for (const rfqPda of await client.unsettledRfqs(config.assetId)) {
+        const rfq = await client.fetchRfq(rfqPda);
+        if (now < rfq.expiryTs.toNumber()) {
+            logger.info("Option not expired yet", { rfq: rfqPda.toBase58(), expiryTs: rfq.expiryTs.toNumber() });
+            return false;
+        }
+        try {
+            const tx = await client.settleRfq(config.assetId, rfqPda);
+            txs.settleRfqTxs.push(tx);
+            logger.info("Settled RFQ", { rfq: rfqPda.toBase58(), tx });
+        } catch (error) {
+            // The expiry slot isn't recorded yet: retried next run, or attested by the
+            // authority after the settlement grace period
+            logger.warn("Could not settle RFQ", { rfq: rfqPda.toBase58(), error });
+            return false;
+        }
+    }
+
+    txs.settleEpochTx = await client.settleEpoch(config.assetId);
+    logger.info("Settled epoch", { tx: txs.settleEpochTx });
+    return true;
+}
+
 /**
  * Wait for quotes and fill best one
  */
@@ -433,20 +492,45 @@ async function runEpochRoll(): Promise<boolean> {
     logger.info("========================================");
 
     try {
-        // Step 1: Fetch oracle price
-        logger.info("Step 1: Fetching oracle price...");
+        const client = state.onchainClient;
+        if (!client) {
+            throw new Error("No on-chain client available - the keeper needs a wallet to run auctions");
+        }
+
+        const onchainTxs: OnChainTxResult = {
+            settleRfqTxs: [],
+            settleEpochTx: null,
+            advanceEpochTx: null,
+            startAuctionTx: null,
+            recordExposureTx: null,
+        };
+
+        // Step 1: Record or close this epoch's auctions
+        logger.info("Step 1: Resolving open auctions...");
+        if (!(await resolveOpenAuctions(client))) {
+            logger.info("Waiting for open auctions, epoch roll deferred");
+            return false;
+        }
+
+        // Step 2: Settle each RFQ on its own expiry price, then the epoch
+        logger.info("Step 2: Settling expired options...");
+        if (!(await settleCurrentEpoch(client, onchainTxs))) {
+            logger.info("Options not settled yet, epoch roll deferred");
+            return false;
+        }
+
+        // Step 3: Advance epoch; premium is read from the vault balance on-chain
+        logger.info("Step 3: Advancing epoch...");
+        onchainTxs.advanceEpochTx = await client.advanceEpoch(config.assetId, config.pythPriceAccount);
+        logger.info("Advanced epoch", { tx: onchainTxs.advanceEpochTx });
+
+        // Step 4: Fetch oracle price and compute strike
+        logger.info("Step 4: Fetching oracle price and computing strike...");
         const oraclePrice = await fetchOraclePrice();
         if (!oraclePrice) {
             throw new Error("Could not fetch oracle price");
         }
-        logger.info("Oracle price", {
-            price: oraclePrice.price / 1_000_000,
-            conf: oraclePrice.conf,
-        });
-
-        // Step 2: Compute strike
-        logger.info("Step 2: Computing strike...");
-        const optionType = "CALL" as const;
+        const optionType = config.optionType;
         const strike = computeStrike(oraclePrice.price, optionType);
         logger.info("Strike computed", {
             optionType,
@@ -455,82 +539,57 @@ async function runEpochRoll(): Promise<boolean> {
             deltaBps: config.strikeDeltaBps,
         });
 
-        // Step 3: Calculate notional (placeholder TVL for now)
-        logger.info("Step 3: Calculating notional...");
-        const mockTvl = 100_000_000_000; // 100,000 tokens (6 decimals)
-        const notional = calculateNotional(mockTvl);
-        logger.info("Notional calculated", {
-            tvl: mockTvl / 1_000_000,
-            utilizationBps: config.utilizationTargetBps,
-            notional: notional / 1_000_000,
+        // Step 5: Start the vault's auction; the program sizes the notional
+        logger.info("Step 5: Starting auction...");
+        const now = Math.floor(Date.now() / 1000);
+        const expiryTs = now + config.epochDurationSeconds;
+        // Add 5 second buffer so RFQ doesn't expire while we're waiting for quotes
+        const validUntilTs = now + Math.floor(config.quoteWaitMs / 1000) + 5;
+        const { rfqPda, signature } = await client.startAuction(config.assetId, {
+            strike: BigInt(strike),
+            expiryTs,
+            premiumFloorPerTokenBps: config.premiumFloorBps,
+            validUntilTs,
+            pythPriceAccount: config.pythPriceAccount,
         });
+        onchainTxs.startAuctionTx = signature;
+        const rfq = await client.fetchRfq(rfqPda);
+        logger.info("Auction started", { rfq: rfqPda.toBase58(), tx: signature });
 
-        // Step 4: Create RFQ
-        logger.info("Step 4: Creating RFQ...");
-        const rfqResponse = await createRfq({
+        const rfqResponse = await announceRfq({
+            rfqAddress: rfqPda,
             underlying: config.assetId,
             optionType,
+            expiryTs,
             strike,
-            size: notional,
+            size: rfq.notionalTokens.toNumber(),
+            validUntilTs,
             oraclePrice: oraclePrice.price,
         });
 
-        if (!rfqResponse?.success) {
-            throw new Error("Failed to create RFQ");
-        }
-
-        // Step 5: Wait for quotes and fill
-        logger.info("Step 5: Waiting for quotes and filling...");
-        const fillResponse = await waitAndFill(rfqResponse.rfqId);
-
+        // Step 6: Wait for quotes; the winning maker fills the RFQ on-chain
+        logger.info("Step 6: Waiting for quotes and filling...");
+        const fillResponse = rfqResponse?.success ? await waitAndFill(rfqResponse.rfqId) : null;
         if (!fillResponse?.success) {
-            logger.warn("No valid quotes received, epoch roll incomplete");
-            // In production, might still advance epoch with 0 premium
-            return false;
+            logger.warn("No valid quotes received");
         }
 
-        // Step 6: Record exposure and advance epoch (on-chain)
-        logger.info("Step 6: Recording exposure and advancing epoch...");
-
-        let onchainTxs: OnChainTxResult = {
-            recordExposureTx: null,
-            advanceEpochTx: null,
-        };
-
-        if (state.onchainClient) {
-            try {
-                // Record notional exposure on vault
-                const recordTx = await state.onchainClient.recordNotionalExposure(
-                    config.assetId,
-                    BigInt(notional),
-                    BigInt(fillResponse.filled?.premium || 0)
-                );
-                onchainTxs.recordExposureTx = recordTx;
-                logger.info("Recorded notional exposure", { tx: recordTx });
-
-                // Advance epoch with premium
-                const advanceTx = await state.onchainClient.advanceEpoch(
-                    config.assetId,
-                    BigInt(fillResponse.filled?.premium || 0)
-                );
-                onchainTxs.advanceEpochTx = advanceTx;
-                logger.info("Advanced epoch", { tx: advanceTx });
-
-            } catch (onchainError) {
-                logger.error("On-chain transaction failed", { error: onchainError });
-                // Don't fail the whole epoch roll - log and continue
-            }
+        // Step 7: Record the fill, or close the auction once its window has passed
+        logger.info("Step 7: Recording exposure...");
+        const filledRfq = await client.fetchRfq(rfqPda);
+        if ("filled" in filledRfq.status) {
+            onchainTxs.recordExposureTx = await client.recordNotionalExposure(config.assetId, rfqPda);
+            logger.info("Recorded notional exposure", { tx: onchainTxs.recordExposureTx });
         } else {
-            logger.warn("No on-chain client available - skipping on-chain transactions");
+            logger.warn("RFQ not filled on-chain, the auction is closed on the next run", { rfq: rfqPda.toBase58() });
         }
 
         logger.info("========================================");
         logger.info("Epoch roll completed successfully", {
-            rfqId: rfqResponse.rfqId,
-            premium: fillResponse.filled?.premium,
-            maker: fillResponse.filled?.maker,
-            recordExposureTx: onchainTxs.recordExposureTx,
-            advanceEpochTx: onchainTxs.advanceEpochTx,
+            rfq: rfqPda.toBase58(),
+            premium: filledRfq.filledPremium?.toString(),
+            maker: "filled" in filledRfq.status ? filledRfq.filledBy.toBase58() : null,
+            ...onchainTxs,
         });
         logger.info("========================================");
 
@@ -1,14 +1,16 @@
 /**
  * On-Chain Client for xStock Options
  * 
- * Provides transaction building and submission for:
- * - RFQ program interactions
- * - Vault program interactions (record_notional_exposure, advance_epoch)
+ * Provides transaction building and submission for the vault's epoch cycle:
+ * - start_auction / close_auction / reject_fill (the vault creates its RFQs)
+ * - record_notional_exposure
+ * - settle_rfq / settle_epoch
+ * - advance_epoch
  */
 
 import * as anchor from "@coral-xyz/anchor";
 import { Connection, Keypair, PublicKey, Transaction, TransactionInstruction } from "@solana/web3.js";
-import { TOKEN_PROGRAM_ID, getAssociatedTokenAddress } from "@solana/spl-token";
+import { TOKEN_PROGRAM_ID, getAccount, getAssociatedTokenAddress, getAssociatedTokenAddressSync } from "@solana/spl-token";
 import * as fs from "fs";
 import * as path from "path";
 
@@ -19,6 +21,9 @@ const VAULT_PROGRAM_ID = new PublicKey(
 const RFQ_PROGRAM_ID = new PublicKey(
     process.env.RFQ_PROGRAM_ID || "3M2K6htNbWyZHtvvUyUME19f5GUS6x8AtGmitFENDT5Z"
 );
+const ORACLE_PROGRAM_ID = new PublicKey(
+    process.env.ORACLE_PROGRAM_ID || "5MnuN6ahpRSp5F3R2uXvy9pSN4TQmhSydywQSoxszuZk"
+);
 
 // ============================================================================
 // IDL Types (minimal - just what we need)
@@ -41,6 +46,66 @@ export function deriveVaultPda(assetId: string): [PublicKey, number] {
     );
 }
 
+export function deriveVaultTokenAccountPda(vaultPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("vault_tokens"), vaultPda.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveEpochRecordPda(vaultPda: PublicKey, epoch: bigint): [PublicKey, number] {
+    const epochBuffer = Buffer.alloc(8);
+    epochBuffer.writeBigUInt64LE(epoch);
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("epoch_record"), vaultPda.toBuffer(), epochBuffer],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveLockedCollateralPda(vaultPda: PublicKey, epoch: bigint): [PublicKey, number] {
+    const epochBuffer = Buffer.alloc(8);
+    epochBuffer.writeBigUInt64LE(epoch);
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("locked_collateral"), vaultPda.toBuffer(), epochBuffer],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveRecordedRfqPda(vaultPda: PublicKey, rfqPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("recorded_rfq"), vaultPda.toBuffer(), rfqPda.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveAuctionPda(vaultPda: PublicKey, rfqPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("auction"), vaultPda.toBuffer(), rfqPda.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveStrategyPda(vaultPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("strategy"), vaultPda.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+}
+
+export function deriveOracleAssetPda(assetId: string): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(assetId)],
+        ORACLE_PROGRAM_ID
+    );
+}
+
+export function derivePriceHistoryPda(assetConfigPda: PublicKey): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from("history"), assetConfigPda.toBuffer()],
+        ORACLE_PROGRAM_ID
+    );
+}
+
 export function deriveRfqConfigPda(): [PublicKey, number] {
     return PublicKey.findProgramAddressSync(
         [Buffer.from("config")],
@@ -109,34 +174,70 @@ export class OnChainClient {
     }
 
     /**
-     * Record notional exposure on the vault after an RFQ is filled
+     * Record a filled on-chain RFQ's exposure on the vault
+     * Notional, premium and strike are read from the RFQ account by the program,
+     * which moves the collateral into the epoch's locked collateral account
      */
-    async recordNotionalExposure(
-        assetId: string,
-        notionalTokens: bigint,
-        premium: bigint
-    ): Promise<string> {
+    async recordNotionalExposure(assetId: string, rfqPda: PublicKey): Promise<string> {
         const [vaultPda] = deriveVaultPda(assetId);
-        console.log("Recording notional exposure:", { assetId, notionalTokens: notionalTokens.toString(), premium: premium.toString(), vault: vaultPda.toString() });
+        const [vaultTokenAccountPda] = deriveVaultTokenAccountPda(vaultPda);
+        const [recordedRfqPda] = deriveRecordedRfqPda(vaultPda, rfqPda);
+        const [auctionPda] = deriveAuctionPda(vaultPda, rfqPda);
+        console.log("Recording notional exposure:", { assetId, rfq: rfqPda.toString(), vault: vaultPda.toString() });
+
+        // The auction account's rent goes back to whoever started the auction
+        // (discriminator, vault, rfq, epoch, then payer)
+        const auctionAccount = await this.connection.getAccountInfo(auctionPda);
+        if (!auctionAccount) {
+            throw new Error(`No open auction for RFQ ${rfqPda.toString()}`);
+        }
+        const auctionPayer = new PublicKey(auctionAccount.data.subarray(80, 112));
 
         if (this.vaultProgram) {
             // Use Anchor with snake_case method name (Anchor 0.30+)
             const program = this.vaultProgram as any;
+            const vault = await program.account.vault.fetch(vaultPda);
+            const [lockedCollateralPda] = deriveLockedCollateralPda(vaultPda, BigInt(vault.epoch.toString()));
+            const isPut = "cashSecuredPut" in vault.strategy;
+            const rfq = isPut ? await this.fetchRfq(rfqPda) : null;
             const tx = await program.methods
-                .recordNotionalExposure(
-                    new anchor.BN(notionalTokens.toString()),
-                    new anchor.BN(premium.toString())
-                )
+                .recordNotionalExposure()
                 .accounts({
                     vault: vaultPda,
+                    rfq: rfqPda,
+                    auction: auctionPda,
+                    auctionPayer,
+                    recordedRfq: recordedRfqPda,
+                    underlyingMint: vault.underlyingMint,
+                    vaultTokenAccount: vaultTokenAccountPda,
+                    lockedCollateralAccount: lockedCollateralPda,
+                    // Put makers must have approved the vault to take their xStock
+                    makerOptionAccount: rfq ? getAssociatedTokenAddressSync(vault.optionMint, rfq.filledBy, true) : null,
                     authority: this.wallet.publicKey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    systemProgram: anchor.web3.SystemProgram.programId,
                 })
                 .rpc();
             return tx;
         }
 
+        // Read underlying_mint and epoch from the raw vault account:
+        // discriminator, authority, asset_id string, then underlying_mint, share_mint,
+        // vault_token_account, total_assets, total_shares, epoch
+        const vaultAccount = await this.connection.getAccountInfo(vaultPda);
+        if (!vaultAccount) {
+            throw new Error("Vault not initialized");
+        }
+        const assetIdLen = vaultAccount.data.readUInt32LE(40);
+        const underlyingMintOffset = 44 + assetIdLen;
+        const underlyingMint = new PublicKey(
+            vaultAccount.data.subarray(underlyingMintOffset, underlyingMintOffset + 32)
+        );
+        const epoch = vaultAccount.data.readBigUInt64LE(underlyingMintOffset + 96 + 16);
+        const [lockedCollateralPda] = deriveLockedCollateralPda(vaultPda, epoch);
+
         // Manual instruction building (without IDL)
-        const data = Buffer.alloc(8 + 8 + 8);
+        const data = Buffer.alloc(8);
 
         // Calculate discriminator: first 8 bytes of sha256("global:record_notional_exposure")
         // For now use placeholder - in production, compute properly
@@ -144,13 +245,23 @@ export class OnChainClient {
             0x93, 0x68, 0x3a, 0x5d, 0x2c, 0x9e, 0x4f, 0x7b
         ]);
         discriminator.copy(data, 0);
-        data.writeBigUInt64LE(notionalTokens, 8);
-        data.writeBigUInt64LE(premium, 16);
 
         const instruction = new TransactionInstruction({
             keys: [
                 { pubkey: vaultPda, isSigner: false, isWritable: true },
-                { pubkey: this.wallet.publicKey, isSigner: true, isWritable: false },
+                { pubkey: rfqPda, isSigner: false, isWritable: false },
+                { pubkey: auctionPda, isSigner: false, isWritable: true },
+                { pubkey: auctionPayer, isSigner: false, isWritable: true },
+                { pubkey: recordedRfqPda, isSigner: false, isWritable: true },
+                { pubkey: underlyingMint, isSigner: false, isWritable: false },
+                { pubkey: vaultTokenAccountPda, isSigner: false, isWritable: true },
+                { pubkey: lockedCollateralPda, isSigner: false, isWritable: true },
+                // No maker option account (the program ID stands for None), so put
+                // vaults need the IDL path
+                { pubkey: VAULT_PROGRAM_ID, isSigner: false, isWritable: false },
+                { pubkey: this.wallet.publicKey, isSigner: true, isWritable: true },
+                { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+                { pubkey: anchor.web3.SystemProgram.programId, isSigner: false, isWritable: false },
             ],
             programId: VAULT_PROGRAM_ID,
             data,
@@ -165,36 +276,78 @@ export class OnChainClient {
 
     /**
      * Advance the vault epoch (called after settlement)
+     * Premium is measured on-chain from the vault token balance
      */
-    async advanceEpoch(
-        assetId: string,
-        premiumEarned: bigint
-    ): Promise<string> {
+    async advanceEpoch(assetId: string, pythPriceAccount: PublicKey): Promise<string> {
         const [vaultPda] = deriveVaultPda(assetId);
+        const [vaultTokenAccountPda] = deriveVaultTokenAccountPda(vaultPda);
 
         if (this.vaultProgram) {
+            // Quote premium is measured too once the vault has a quote account
+            const vault = await (this.vaultProgram as any).account.vault.fetch(vaultPda);
+            const quoteTokenAccount = vault.quoteTokenAccount.equals(PublicKey.default)
+                ? null
+                : vault.quoteTokenAccount;
+            const [epochRecordPda] = deriveEpochRecordPda(vaultPda, BigInt(vault.epoch.toString()));
+            // Fees are minted as shares to the fee recipient's share account
+            const chargesFees = vault.managementFeeBps > 0 || vault.performanceFeeBps > 0;
+            const feeRecipientShareAccount = chargesFees
+                ? await getAssociatedTokenAddress(vault.shareMint, vault.feeRecipient, true, vault.shareTokenProgram)
+                : null;
+            // NAV values the quote balance at the oracle price
+            const holdsQuote = quoteTokenAccount !== null;
+            // Every deposit queued this epoch with a minimum is checked at the roll
+            const receipts = await (this.vaultProgram as any).account.depositReceipt.all([
+                { memcmp: { offset: 40, bytes: vaultPda.toBase58() } },
+            ]);
+            const guardedReceipts = receipts
+                .filter((entry: any) => entry.account.epoch.eq(vault.epoch) && !entry.account.minSharesOut.isZero())
+                .map((entry: any) => ({ pubkey: entry.publicKey, isSigner: false, isWritable: true }));
+
             const tx = await this.vaultProgram.methods
-                .advanceEpoch(new anchor.BN(premiumEarned.toString()))
+                .advanceEpoch()
                 .accounts({
                     vault: vaultPda,
+                    epochRecord: epochRecordPda,
                     authority: this.wallet.publicKey,
+                    vaultTokenAccount: vaultTokenAccountPda,
+                    quoteTokenAccount,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    shareMint: chargesFees ? vault.shareMint : null,
+                    feeRecipientShareAccount,
+                    shareTokenProgram: chargesFees ? vault.shareTokenProgram : null,
+                    assetConfig: holdsQuote ? this.assetConfigFor(assetId, vault) : null,
+                    pythPriceAccount: holdsQuote ? pythPriceAccount : null,
+                    oracleProgram: holdsQuote ? ORACLE_PROGRAM_ID : null,
                 })
+                .remainingAccounts(guardedReceipts)
                 .rpc();
             return tx;
         }
 
         // Manual instruction building
-        const data = Buffer.alloc(8 + 8);
+        // Read the current epoch: discriminator, authority, asset_id string, 3 pubkeys, total_assets, total_shares
+        const vaultInfo = await this.connection.getAccountInfo(vaultPda);
+        if (!vaultInfo) throw new Error(`Vault not found: ${assetId}`);
+        const assetIdLen = vaultInfo.data.readUInt32LE(8 + 32);
+        const epoch = vaultInfo.data.readBigUInt64LE(8 + 32 + 4 + assetIdLen + 32 * 3 + 8 + 8);
+        const [epochRecordPda] = deriveEpochRecordPda(vaultPda, epoch);
+
+        const data = Buffer.alloc(8);
         const discriminator = Buffer.from([
             0xa2, 0x5b, 0x3c, 0x6d, 0x4e, 0x7f, 0x80, 0x91
         ]);
         discriminator.copy(data, 0);
-        data.writeBigUInt64LE(premiumEarned, 8);
 
         const instruction = new TransactionInstruction({
             keys: [
                 { pubkey: vaultPda, isSigner: false, isWritable: true },
-                { pubkey: this.wallet.publicKey, isSigner: true, isWritable: false },
+                { pubkey: epochRecordPda, isSigner: false, isWritable: true },
+                { pubkey: this.wallet.publicKey, isSigner: true, isWritable: true },
+                { pubkey: vaultTokenAccountPda, isSigner: false, isWritable: false },
+                // quote_token_account: None
+                { pubkey: VAULT_PROGRAM_ID, isSigner: false, isWritable: false },
+                { pubkey: anchor.web3.SystemProgram.programId, isSigner: false, isWritable: false },
             ],
             programId: VAULT_PROGRAM_ID,
             data,
@@ -207,21 +360,28 @@ export class OnChainClient {
         return signature;
     }
 
+    private requireVaultProgram(): any {
+        if (!this.vaultProgram) {
+            throw new Error("Vault IDL is required for auctions and settlement");
+        }
+        return this.vaultProgram as any;
+    }
+
     /**
-     * Create an RFQ on-chain
+     * Start this epoch's auction: the vault creates the RFQ on-chain as its creator,
+     * sizes the notional under its utilization cap and checks the terms against its
+     * StrategyParams. Makers fill it directly with rfq.fill_rfq.
      */
-    async createRfq(params: {
-        underlying: PublicKey;
-        optionType: "CALL" | "PUT";
-        expiryTs: number;
+    async startAuction(assetId: string, params: {
         strike: bigint;
-        notionalTokens: bigint;
+        expiryTs: number;
         premiumFloorPerTokenBps: number;
         validUntilTs: number;
-        settlement: "CASH" | "PHYSICAL";
-        oraclePrice: bigint;
-        oracleTs: number;
-    }): Promise<{ rfqId: number; signature: string }> {
+        pythPriceAccount: PublicKey;
+    }): Promise<{ rfqPda: PublicKey; signature: string }> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const vault = await program.account.vault.fetch(vaultPda);
         const [configPda] = deriveRfqConfigPda();
 
         // Fetch current rfq count to get next ID
@@ -231,36 +391,217 @@ export class OnChainClient {
         }
 
         // Parse rfq_count from config (offset: 8 discriminator + 32 authority = 40, then u64)
-        const rfqCount = configAccount.data.readBigUInt64LE(40);
-        const rfqId = Number(rfqCount);
-
+        const rfqId = Number(configAccount.data.readBigUInt64LE(40));
         const [rfqPda] = deriveRfqPda(rfqId);
 
-        if (this.rfqProgram) {
-            const tx = await this.rfqProgram.methods
-                .createRfq(
-                    params.underlying,
-                    params.optionType === "CALL" ? { call: {} } : { put: {} },
-                    new anchor.BN(params.expiryTs),
-                    new anchor.BN(params.strike.toString()),
-                    new anchor.BN(params.notionalTokens.toString()),
-                    params.premiumFloorPerTokenBps,
-                    new anchor.BN(params.validUntilTs),
-                    params.settlement === "CASH" ? { cash: {} } : { physical: {} },
-                    new anchor.BN(params.oraclePrice.toString()),
-                    new anchor.BN(params.oracleTs)
-                )
-                .accounts({
-                    config: configPda,
-                    rfq: rfqPda,
-                    creator: this.wallet.publicKey,
-                    systemProgram: anchor.web3.SystemProgram.programId,
-                })
-                .rpc();
-            return { rfqId, signature: tx };
+        // Premium goes to the quote account when the vault has one, otherwise to its token account
+        const premiumInQuote = "coveredCall" in vault.strategy && !vault.quoteTokenAccount.equals(PublicKey.default);
+        const signature = await program.methods
+            .startAuction(
+                new anchor.BN(params.strike.toString()),
+                new anchor.BN(params.expiryTs),
+                params.premiumFloorPerTokenBps,
+                new anchor.BN(params.validUntilTs)
+            )
+            .accounts({
+                vault: vaultPda,
+                strategy: deriveStrategyPda(vaultPda)[0],
+                authority: this.wallet.publicKey,
+                assetConfig: this.assetConfigFor(assetId, vault),
+                pythPriceAccount: params.pythPriceAccount,
+                premiumAccount: premiumInQuote ? vault.quoteTokenAccount : vault.vaultTokenAccount,
+                rfqConfig: configPda,
+                rfq: rfqPda,
+                auction: deriveAuctionPda(vaultPda, rfqPda)[0],
+                rfqProgram: RFQ_PROGRAM_ID,
+                oracleProgram: ORACLE_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+            })
+            .rpc();
+        return { rfqPda, signature };
+    }
+
+    /**
+     * Close an auction whose RFQ can no longer be filled or recorded, so the epoch can roll.
+     * A fill that expired unrecorded has its premium refunded to the maker.
+     */
+    async closeAuction(assetId: string, rfqPda: PublicKey): Promise<string> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const [auctionPda] = deriveAuctionPda(vaultPda, rfqPda);
+        const auction = await program.account.auction.fetch(auctionPda);
+        const rfq = await this.fetchRfq(rfqPda);
+        const filled = "filled" in rfq.status;
+        return program.methods
+            .closeAuction()
+            .accounts({
+                vault: vaultPda,
+                rfq: rfqPda,
+                auction: auctionPda,
+                auctionPayer: auction.payer,
+                premiumAccount: filled ? rfq.premiumAccount : null,
+                makerPremiumAccount: filled
+                    ? getAssociatedTokenAddressSync(rfq.premiumMint, rfq.filledBy, true)
+                    : null,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+    }
+
+    /**
+     * Whether a put RFQ's maker has approved the vault to take the notional in xStock.
+     * Always true for call vaults, which don't need the maker's delegation.
+     */
+    async makerApprovesDelivery(assetId: string, rfqPda: PublicKey): Promise<boolean> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const vault = await program.account.vault.fetch(vaultPda);
+        if (!("cashSecuredPut" in vault.strategy)) {
+            return true;
+        }
+        const rfq = await this.fetchRfq(rfqPda);
+        const makerOptionAccount = getAssociatedTokenAddressSync(vault.optionMint, rfq.filledBy, true);
+        try {
+            const account = await getAccount(this.connection, makerOptionAccount);
+            const notional = BigInt(rfq.notionalTokens.toString());
+            return account.delegate !== null
+                && account.delegate.equals(vaultPda)
+                && account.delegatedAmount >= notional
+                && account.amount >= notional;
+        } catch {
+            return false;
         }
+    }
+
+    /**
+     * Close a put auction whose maker never approved delivery, refunding the premium
+     */
+    async rejectFill(assetId: string, rfqPda: PublicKey): Promise<string> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const [auctionPda] = deriveAuctionPda(vaultPda, rfqPda);
+        const vault = await program.account.vault.fetch(vaultPda);
+        const auction = await program.account.auction.fetch(auctionPda);
+        const rfq = await this.fetchRfq(rfqPda);
+        return program.methods
+            .rejectFill()
+            .accounts({
+                vault: vaultPda,
+                rfq: rfqPda,
+                auction: auctionPda,
+                auctionPayer: auction.payer,
+                makerOptionAccount: getAssociatedTokenAddressSync(vault.optionMint, rfq.filledBy, true),
+                premiumAccount: rfq.premiumAccount,
+                makerPremiumAccount: getAssociatedTokenAddressSync(rfq.premiumMint, rfq.filledBy, true),
+                authority: this.wallet.publicKey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+    }
+
+    /**
+     * RFQs of the vault's auctions that are still open (not recorded or closed)
+     */
+    async openAuctions(assetId: string): Promise<PublicKey[]> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const auctions = await program.account.auction.all([
+            { memcmp: { offset: 8, bytes: vaultPda.toBase58() } },
+        ]);
+        return auctions.map((auction: any) => auction.account.rfq as PublicKey);
+    }
+
+    /**
+     * Fetch an on-chain RFQ (status, expiry, notional)
+     */
+    async fetchRfq(rfqPda: PublicKey): Promise<any> {
+        if (!this.rfqProgram) {
+            throw new Error("RFQ IDL is required to read RFQs");
+        }
+        return (this.rfqProgram as any).account.rfq.fetch(rfqPda);
+    }
+
+    /**
+     * RFQs recorded in the vault's current epoch that are not settled yet
+     */
+    async unsettledRfqs(assetId: string): Promise<PublicKey[]> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const vault = await program.account.vault.fetch(vaultPda);
+        const recorded = await program.account.recordedRfq.all([
+            { memcmp: { offset: 8, bytes: vaultPda.toBase58() } },
+        ]);
+        return recorded
+            .filter((entry: any) => entry.account.epoch.eq(vault.epoch) && !entry.account.settled)
+            .map((entry: any) => entry.account.rfq as PublicKey);
+    }
+
+    /**
+     * Settle one recorded RFQ on the oracle observation recorded for its expiry slot
+     */
+    async settleRfq(assetId: string, rfqPda: PublicKey): Promise<string> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const vault = await program.account.vault.fetch(vaultPda);
+        const rfq = await this.fetchRfq(rfqPda);
+        const [lockedCollateralPda] = deriveLockedCollateralPda(vaultPda, BigInt(vault.epoch.toString()));
+        const isPut = "cashSecuredPut" in vault.strategy;
+        const [recordedRfqPda] = deriveRecordedRfqPda(vaultPda, rfqPda);
+        const recordedRfq = await program.account.recordedRfq.fetch(recordedRfqPda);
+        const assetConfig = this.assetConfigFor(assetId, vault);
+
+        return program.methods
+            .settleRfq()
+            .accounts({
+                vault: vaultPda,
+                rfq: rfqPda,
+                recordedRfq: recordedRfqPda,
+                lockedCollateralAccount: lockedCollateralPda,
+                makerTokenAccount: getAssociatedTokenAddressSync(vault.underlyingMint, rfq.filledBy, true),
+                // Assigned puts take the maker's xStock from the account approved at record time
+                makerOptionAccount: isPut ? recordedRfq.makerOptionAccount : null,
+                quoteTokenAccount: isPut ? vault.quoteTokenAccount : null,
+                assetConfig,
+                priceHistory: derivePriceHistoryPda(assetConfig)[0],
+                oracleProgram: ORACLE_PROGRAM_ID,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+    }
+
+    /**
+     * Release the epoch's remaining locked collateral once every RFQ is settled
+     */
+    async settleEpoch(assetId: string): Promise<string> {
+        const program = this.requireVaultProgram();
+        const [vaultPda] = deriveVaultPda(assetId);
+        const vault = await program.account.vault.fetch(vaultPda);
+        const [lockedCollateralPda] = deriveLockedCollateralPda(vaultPda, BigInt(vault.epoch.toString()));
+        return program.methods
+            .settleEpoch()
+            .accounts({
+                vault: vaultPda,
+                authority: this.wallet.publicKey,
+                lockedCollateralPayer: vault.lockedCollateralPayer,
+                vaultTokenAccount: vault.vaultTokenAccount,
+                lockedCollateralAccount: lockedCollateralPda,
+                tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+    }
+
+    /**
+     * Fetch the decoded vault account
+     */
+    async fetchVault(assetId: string): Promise<any> {
+        const program = this.requireVaultProgram();
+        return program.account.vault.fetch(deriveVaultPda(assetId)[0]);
+    }
 
-        throw new Error("RFQ creation requires IDL - not implemented for manual building");
+    private assetConfigFor(assetId: string, vault: any): PublicKey {
+        return vault.oracleAssetConfig.equals(PublicKey.default)
+            ? deriveOracleAssetPda(assetId)[0]
+            : vault.oracleAssetConfig;
     }
 
     /**
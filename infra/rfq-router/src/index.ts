@@ -23,6 +23,7 @@ const QUOTE_TIMEOUT_MS = parseInt(process.env.QUOTE_TIMEOUT_MS || "30000");
 // Types
 interface RfqRequest {
     id: string;
+    rfqAddress?: string;    // On-chain Rfq account the winning maker fills
     underlying: string;
     optionType: "CALL" | "PUT";
     expiryTs: number;
@@ -94,6 +95,7 @@ function selectBestQuote(quotes: Quote[]): Quote | null {
 app.post("/rfq", async (req: Request, res: Response) => {
     try {
         const {
+            rfqAddress,
             underlying,
             optionType,
             expiryTs,
@@ -109,6 +111,7 @@ app.post("/rfq", async (req: Request, res: Response) => {
         const rfqId = generateRfqId();
         const request: RfqRequest = {
             id: rfqId,
+            rfqAddress,
             underlying,
             optionType,
             expiryTs,
@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
 
 declare_id!("5MnuN6ahpRSp5F3R2uXvy9pSN4TQmhSydywQSoxszuZk");
 
@@ -12,6 +13,13 @@ pub const MAX_PRICE_AGE_SECS: i64 = 300;
 pub const CONFIDENCE_OK_THRESHOLD_BPS: u64 = 100;
 pub const CONFIDENCE_DEGRADED_THRESHOLD_BPS: u64 = 500;
 
+// Price history ring buffer: one observation per fixed 15 minute slot covers 24 hours
+pub const MAX_OBSERVATIONS: usize = 96;
+pub const OBSERVATION_SLOT_SECS: i64 = 900;
+// An observation must be published this soon after its slot starts, so callers
+// cannot pick the moment within the slot that is recorded
+pub const OBSERVATION_SLOT_TOLERANCE_SECS: i64 = 300;
+
 /// Pyth price structure (matching on-chain format)
 /// Reference: https://docs.pyth.network/price-feeds/pythnet-price-feeds/on-chain-data
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
@@ -56,6 +64,57 @@ fn parse_pyth_price(data: &[u8]) -> Option<PythPrice> {
     })
 }
 
+/// Read and validate the configured Pyth price for an asset
+fn read_price(asset_config: &AssetConfig, pyth_price_info: &AccountInfo) -> Result<PriceResult> {
+    let clock = Clock::get()?;
+
+    // Verify the Pyth account matches what's configured
+    require_keys_eq!(
+        pyth_price_info.key(),
+        asset_config.pyth_price_account,
+        OracleError::InvalidPriceAccount
+    );
+
+    // Parse the Pyth price data
+    let pyth_data = pyth_price_info.try_borrow_data()?;
+    let price = parse_pyth_price(&pyth_data)
+        .ok_or(OracleError::InvalidPriceData)?;
+
+    // Check staleness - set disputed if stale but don't fail
+    let age = clock.unix_timestamp.saturating_sub(price.publish_time);
+    let is_stale = age > MAX_PRICE_AGE_SECS;
+
+    // Calculate confidence percentage in basis points: (conf / price) * 10000
+    #[allow(clippy::manual_saturating_arithmetic)]
+    let confidence_bps = if price.price > 0 {
+        ((price.conf as u128)
+            .checked_mul(10000)
+            .unwrap_or(u128::MAX)
+            .checked_div(price.price.unsigned_abs() as u128)
+            .unwrap_or(u64::MAX as u128)) as u64
+    } else {
+        u64::MAX // Invalid price means max uncertainty
+    };
+
+    // Determine oracle status
+    let status = if is_stale || confidence_bps > CONFIDENCE_DEGRADED_THRESHOLD_BPS {
+        OracleStatus::Disputed
+    } else if confidence_bps > CONFIDENCE_OK_THRESHOLD_BPS {
+        OracleStatus::Degraded
+    } else {
+        OracleStatus::Ok
+    };
+
+    Ok(PriceResult {
+        price: price.price,
+        conf: price.conf,
+        exponent: price.expo,
+        publish_time: price.publish_time,
+        status,
+        confidence_bps: confidence_bps.min(u16::MAX as u64) as u16,
+    })
+}
+
 #[program]
 pub mod oracle {
     use super::*;
@@ -77,53 +136,7 @@ pub mod oracle {
     /// Get price from Pyth - reads the Pyth price account and validates freshness
     pub fn get_price(ctx: Context<GetPrice>) -> Result<PriceResult> {
         let asset_config = &ctx.accounts.asset_config;
-        let pyth_price_info = &ctx.accounts.pyth_price_account;
-        let clock = Clock::get()?;
-
-        // Verify the Pyth account matches what's configured
-        require_keys_eq!(
-            pyth_price_info.key(),
-            asset_config.pyth_price_account,
-            OracleError::InvalidPriceAccount
-        );
-
-        // Parse the Pyth price data
-        let pyth_data = pyth_price_info.try_borrow_data()?;
-        let price = parse_pyth_price(&pyth_data)
-            .ok_or(OracleError::InvalidPriceData)?;
-
-        // Check staleness - set disputed if stale but don't fail
-        let age = clock.unix_timestamp.saturating_sub(price.publish_time);
-        let is_stale = age > MAX_PRICE_AGE_SECS;
-
-        // Calculate confidence percentage in basis points: (conf / price) * 10000
-        let confidence_bps = if price.price > 0 {
-            ((price.conf as u128)
-                .checked_mul(10000)
-                .unwrap_or(u128::MAX)
-                .checked_div(price.price.unsigned_abs() as u128)
-                .unwrap_or(u64::MAX as u128)) as u64
-        } else {
-            u64::MAX // Invalid price means max uncertainty
-        };
-
-        // Determine oracle status
-        let status = if is_stale || confidence_bps > CONFIDENCE_DEGRADED_THRESHOLD_BPS {
-            OracleStatus::Disputed
-        } else if confidence_bps > CONFIDENCE_OK_THRESHOLD_BPS {
-            OracleStatus::Degraded
-        } else {
-            OracleStatus::Ok
-        };
-
-        let result = PriceResult {
-            price: price.price,
-            conf: price.conf,
-            exponent: price.expo,
-            publish_time: price.publish_time,
-            status,
-            confidence_bps: confidence_bps.min(u16::MAX as u64) as u16,
-        };
+        let result = read_price(asset_config, &ctx.accounts.pyth_price_account)?;
 
         emit!(PriceRead {
             asset_id: asset_config.asset_id.clone(),
@@ -138,6 +151,138 @@ pub mod oracle {
         Ok(result)
     }
 
+    /// Create the observation history for an asset (admin only)
+    pub fn initialize_price_history(ctx: Context<InitializePriceHistory>) -> Result<()> {
+        let history = &mut ctx.accounts.price_history;
+        history.asset_config = ctx.accounts.asset_config.key();
+        history.exponent = 0;
+        history.head = 0;
+        history.count = 0;
+        history.observations = [PriceObservation::default(); MAX_OBSERVATIONS];
+        history.bump = ctx.bumps.price_history;
+        Ok(())
+    }
+
+    /// Record the current Pyth price into the asset's history (anyone can call)
+    ///
+    /// Time is split into fixed `OBSERVATION_SLOT_SECS` slots. Only `Ok` readings
+    /// published within `OBSERVATION_SLOT_TOLERANCE_SECS` of a slot start are stored,
+    /// once per slot and timestamped with the slot start.
+    pub fn record_observation(ctx: Context<RecordObservation>) -> Result<()> {
+        let price = read_price(&ctx.accounts.asset_config, &ctx.accounts.pyth_price_account)?;
+        require!(price.status == OracleStatus::Ok, OracleError::PriceNotOk);
+
+        let slot_start = price.publish_time - price.publish_time.rem_euclid(OBSERVATION_SLOT_SECS);
+        require!(
+            price.publish_time - slot_start <= OBSERVATION_SLOT_TOLERANCE_SECS,
+            OracleError::OutsideObservationSlot
+        );
+
+        let history = &mut ctx.accounts.price_history;
+        if let Some(last) = history.latest() {
+            require!(slot_start > last.publish_time, OracleError::ObservationTooSoon);
+            require!(price.exponent == history.exponent, OracleError::InvalidPriceData);
+        } else {
+            history.exponent = price.exponent;
+        }
+
+        let head = history.head as usize;
+        history.observations[head] = PriceObservation {
+            price: price.price,
+            publish_time: slot_start,
+        };
+        history.head = ((head + 1) % MAX_OBSERVATIONS) as u16;
+        history.count = history.count.saturating_add(1).min(MAX_OBSERVATIONS as u16);
+
+        emit!(ObservationRecorded {
+            asset_id: ctx.accounts.asset_config.asset_id.clone(),
+            price: price.price,
+            exponent: price.exponent,
+            publish_time: slot_start,
+        });
+
+        Ok(())
+    }
+
+    /// Time-weighted average price over [start_ts, end_ts] from recorded observations
+    ///
+    /// Each observation holds until the next one. Fails unless the window is fully
+    /// covered, i.e. an observation exists at or before `start_ts`. The returned
+    /// `observations` counts those recorded inside [start_ts, end_ts), so counts of
+    /// adjacent windows add up.
+    pub fn get_average_price(
+        ctx: Context<GetAveragePrice>,
+        start_ts: i64,
+        end_ts: i64,
+    ) -> Result<AveragePriceResult> {
+        require!(end_ts > start_ts, OracleError::InvalidWindow);
+
+        let history = &ctx.accounts.price_history;
+        let observations = history.chronological();
+
+        let mut weighted_sum: i128 = 0;
+        let mut covered_secs: i64 = 0;
+        let mut recorded: u16 = 0;
+        for (i, observation) in observations.iter().enumerate() {
+            let next_ts = observations
+                .get(i + 1)
+                .map_or(end_ts, |next| next.publish_time);
+            let from = observation.publish_time.max(start_ts);
+            let to = next_ts.min(end_ts);
+            if to > from {
+                let secs = to - from;
+                weighted_sum = weighted_sum
+                    .checked_add((observation.price as i128) * (secs as i128))
+                    .ok_or(OracleError::MathOverflow)?;
+                covered_secs += secs;
+            }
+            if observation.publish_time >= start_ts && observation.publish_time < end_ts {
+                recorded += 1;
+            }
+        }
+        require!(covered_secs == end_ts - start_ts, OracleError::InsufficientObservations);
+
+        Ok(AveragePriceResult {
+            price: (weighted_sum / covered_secs as i128) as i64,
+            exponent: history.exponent,
+            start_ts,
+            end_ts,
+            observations: recorded,
+        })
+    }
+
+    /// Price recorded for the first observation slot starting at or after `ts`
+    ///
+    /// Options expiring at `ts` settle on it, so the settlement price is the one
+    /// `record_observation` stored for that slot rather than a reading picked by
+    /// whoever settles. Fails until the slot is recorded, and again once it has
+    /// rolled out of the 24 hour history.
+    pub fn get_settlement_price(ctx: Context<GetSettlementPrice>, ts: i64) -> Result<ObservationResult> {
+        let slot_start = settlement_slot(ts);
+        let history = &ctx.accounts.price_history;
+        let observation = history
+            .observation_at(slot_start)
+            .ok_or(OracleError::ObservationMissing)?;
+
+        Ok(ObservationResult {
+            price: observation.price,
+            exponent: history.exponent,
+            publish_time: observation.publish_time,
+        })
+    }
+
+    /// Record the token mint an asset config prices (admin only)
+    ///
+    /// Programs that take an asset config for a token, such as the options program,
+    /// check it against this record so the oracle asset can't be swapped for another.
+    pub fn register_asset_mint(ctx: Context<RegisterAssetMint>) -> Result<()> {
+        let asset_mint = &mut ctx.accounts.asset_mint;
+        asset_mint.asset_config = ctx.accounts.asset_config.key();
+        asset_mint.mint = ctx.accounts.mint.key();
+        asset_mint.bump = ctx.bumps.asset_mint;
+        Ok(())
+    }
+
     /// Update the Pyth price account for an asset (admin only)
     pub fn update_pyth_account(
         ctx: Context<UpdatePythAccount>,
@@ -166,6 +311,75 @@ impl AssetConfig {
     pub const LEN: usize = 8 + 32 + 4 + Self::MAX_ASSET_ID_LEN + 32 + 1;
 }
 
+#[account]
+pub struct AssetMint {
+    pub asset_config: Pubkey,
+    pub mint: Pubkey,
+    pub bump: u8,
+}
+
+impl AssetMint {
+    pub const LEN: usize = 8 + 32 + 32 + 1;
+}
+
+#[account]
+pub struct PriceHistory {
+    pub asset_config: Pubkey,
+    pub exponent: i32,  // Shared by all observations (Pyth exponents are fixed per feed)
+    pub head: u16,      // Next slot to write
+    pub count: u16,     // Number of valid observations
+    pub observations: [PriceObservation; MAX_OBSERVATIONS],
+    pub bump: u8,
+}
+
+impl PriceHistory {
+    pub const LEN: usize = 8 + 32 + 4 + 2 + 2 + MAX_OBSERVATIONS * PriceObservation::LEN + 1;
+
+    /// Most recently recorded observation
+    pub fn latest(&self) -> Option<&PriceObservation> {
+        if self.count == 0 {
+            return None;
+        }
+        let index = (self.head as usize + MAX_OBSERVATIONS - 1) % MAX_OBSERVATIONS;
+        Some(&self.observations[index])
+    }
+
+    /// Valid observations ordered from oldest to newest
+    pub fn chronological(&self) -> Vec<PriceObservation> {
+        let count = self.count as usize;
+        let oldest = (self.head as usize + MAX_OBSERVATIONS - count) % MAX_OBSERVATIONS;
+        (0..count)
+            .map(|i| self.observations[(oldest + i) % MAX_OBSERVATIONS])
+            .collect()
+    }
+
+    /// Observation recorded for the slot starting at `slot_start`, if still held
+    pub fn observation_at(&self, slot_start: i64) -> Option<PriceObservation> {
+        self.chronological()
+            .into_iter()
+            .find(|observation| observation.publish_time == slot_start)
+    }
+}
+
+/// Start of the first observation slot at or after `ts`, whose observation settles
+/// anything expiring at `ts`
+pub fn settlement_slot(ts: i64) -> i64 {
+    match ts.rem_euclid(OBSERVATION_SLOT_SECS) {
+        0 => ts,
+        offset => ts + (OBSERVATION_SLOT_SECS - offset),
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceObservation {
+    pub price: i64,
+    pub publish_time: i64,
+}
+
+impl PriceObservation {
+    pub const LEN: usize = 8 + 8;
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default)]
 pub enum OracleStatus {
     #[default]
@@ -184,6 +398,64 @@ pub struct PriceResult {
     pub confidence_bps: u16,  // Confidence as basis points (conf/price * 10000)
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct AveragePriceResult {
+    pub price: i64,
+    pub exponent: i32,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub observations: u16,  // Observations recorded inside the window
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct ObservationResult {
+    pub price: i64,
+    pub exponent: i32,
+    pub publish_time: i64,  // Start of the slot it was recorded for
+}
+
+impl ObservationResult {
+    /// Price as a fixed-point integer with `decimals` digits
+    pub fn scaled_price(&self, decimals: u32) -> Option<u64> {
+        PriceResult {
+            price: self.price,
+            exponent: self.exponent,
+            ..Default::default()
+        }
+        .scaled_price(decimals)
+    }
+}
+
+impl AveragePriceResult {
+    /// Average price as a fixed-point integer with `decimals` digits
+    pub fn scaled_price(&self, decimals: u32) -> Option<u64> {
+        PriceResult {
+            price: self.price,
+            exponent: self.exponent,
+            ..Default::default()
+        }
+        .scaled_price(decimals)
+    }
+}
+
+impl PriceResult {
+    /// Price as a fixed-point integer with `decimals` digits (e.g. 6 for USDC quotes)
+    /// Returns None for non-positive prices or on overflow
+    pub fn scaled_price(&self, decimals: u32) -> Option<u64> {
+        if self.price <= 0 {
+            return None;
+        }
+        let price = self.price as u128;
+        let shift = self.exponent.checked_add(decimals as i32)?;
+        let scaled = if shift >= 0 {
+            price.checked_mul(10u128.checked_pow(shift as u32)?)?
+        } else {
+            price.checked_div(10u128.checked_pow(shift.unsigned_abs())?)?
+        };
+        u64::try_from(scaled).ok()
+    }
+}
+
 // ============================================================================
 // Contexts
 // ============================================================================
@@ -214,6 +486,85 @@ pub struct GetPrice<'info> {
     pub pyth_price_account: AccountInfo<'info>,
 }
 
+#[derive(Accounts)]
+pub struct InitializePriceHistory<'info> {
+    #[account(has_one = authority)]
+    pub asset_config: Account<'info, AssetConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = PriceHistory::LEN,
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordObservation<'info> {
+    pub asset_config: Account<'info, AssetConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    /// CHECK: This is a Pyth price account - we validate the pubkey matches config
+    pub pyth_price_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetAveragePrice<'info> {
+    pub asset_config: Account<'info, AssetConfig>,
+
+    #[account(
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+}
+
+#[derive(Accounts)]
+pub struct GetSettlementPrice<'info> {
+    pub asset_config: Account<'info, AssetConfig>,
+
+    #[account(
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterAssetMint<'info> {
+    #[account(has_one = authority)]
+    pub asset_config: Account<'info, AssetConfig>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AssetMint::LEN,
+        seeds = [b"asset_mint", asset_config.key().as_ref()],
+        bump
+    )]
+    pub asset_mint: Account<'info, AssetMint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct UpdatePythAccount<'info> {
     #[account(
@@ -240,6 +591,14 @@ pub struct PriceRead {
     pub confidence_bps: u16,
 }
 
+#[event]
+pub struct ObservationRecorded {
+    pub asset_id: String,
+    pub price: i64,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
 // ============================================================================
 // Errors
 // ============================================================================
@@ -252,4 +611,18 @@ pub enum OracleError {
     InvalidPriceData,
     #[msg("Price is stale")]
     StalePrice,
+    #[msg("Oracle status is not OK")]
+    PriceNotOk,
+    #[msg("An observation was already recorded for this slot")]
+    ObservationTooSoon,
+    #[msg("Invalid averaging window")]
+    InvalidWindow,
+    #[msg("Not enough observations to cover the averaging window")]
+    InsufficientObservations,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Price was not published close enough to the start of an observation slot")]
+    OutsideObservationSlot,
+    #[msg("No observation is held for the settlement slot")]
+    ObservationMissing,
 }
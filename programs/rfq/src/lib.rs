@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
 use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
 declare_id!("3M2K6htNbWyZHtvvUyUME19f5GUS6x8AtGmitFENDT5Z");
@@ -30,57 +31,29 @@ pub mod rfq {
     }
 
     /// Create an RFQ (called by vault/keeper)
-    pub fn create_rfq(
-        ctx: Context<CreateRfq>,
-        underlying: Pubkey,
-        option_type: OptionType,
-        expiry_ts: i64,
-        strike: u64,
-        notional_tokens: u64,          // Token exposure (fractional options)
-        premium_floor_per_token_bps: u32, // Min premium rate in basis points
-        valid_until_ts: i64,
-        settlement: SettlementType,
-        oracle_price: u64,
-        oracle_ts: i64,
-    ) -> Result<()> {
-        let config = &mut ctx.accounts.config;
-        let rfq = &mut ctx.accounts.rfq;
-        let clock = Clock::get()?;
-
-        require!(valid_until_ts > clock.unix_timestamp, RfqError::InvalidExpiry);
-        require!(expiry_ts > clock.unix_timestamp, RfqError::InvalidExpiry);
-
-        rfq.id = config.rfq_count;
-        rfq.creator = ctx.accounts.creator.key();
-        rfq.underlying = underlying;
-        rfq.option_type = option_type;
-        rfq.expiry_ts = expiry_ts;
-        rfq.strike = strike;
-        rfq.notional_tokens = notional_tokens;
-        rfq.premium_floor_per_token_bps = premium_floor_per_token_bps;
-        rfq.valid_until_ts = valid_until_ts;
-        rfq.settlement = settlement;
-        rfq.oracle_price = oracle_price;
-        rfq.oracle_ts = oracle_ts;
-        rfq.status = RfqStatus::Open;
-        rfq.filled_by = Pubkey::default();
-        rfq.filled_premium = 0;
-        rfq.filled_premium_per_token_bps = 0;
-        rfq.created_at = clock.unix_timestamp;
-        rfq.bump = ctx.bumps.rfq;
-
-        config.rfq_count = config.rfq_count.checked_add(1).unwrap();
-
-        emit!(RfqCreatedEvent {
-            rfq_id: rfq.id,
-            underlying,
-            strike,
-            notional_tokens,
-            premium_floor_per_token_bps,
-            valid_until_ts,
-        });
+    /// The premium can only be paid into `premium_account`, which the creator must own.
+    pub fn create_rfq(ctx: Context<CreateRfq>, terms: RfqTerms) -> Result<()> {
+        open_rfq(
+            &mut ctx.accounts.config,
+            &mut ctx.accounts.rfq,
+            ctx.accounts.creator.key(),
+            &ctx.accounts.premium_account,
+            ctx.bumps.rfq,
+            terms,
+        )
+    }
 
-        Ok(())
+    /// Create an RFQ with its rent paid by a separate payer
+    /// For creators that can't pay rent themselves, like a program PDA signing via CPI.
+    pub fn create_rfq_with_payer(ctx: Context<CreateRfqWithPayer>, terms: RfqTerms) -> Result<()> {
+        open_rfq(
+            &mut ctx.accounts.config,
+            &mut ctx.accounts.rfq,
+            ctx.accounts.creator.key(),
+            &ctx.accounts.premium_account,
+            ctx.bumps.rfq,
+            terms,
+        )
     }
 
     /// Fill an RFQ (called by maker)
@@ -156,6 +129,45 @@ pub mod rfq {
         Ok(())
     }
 
+    /// Grow an RFQ created before premium accounts were pinned to the current
+    /// layout and pin its premium account (creator only, pays the extra rent)
+    ///
+    /// The RFQ is taken unchecked because the old layout is too short to
+    /// deserialize before the resize.
+    pub fn migrate_rfq(ctx: Context<MigrateRfq>) -> Result<()> {
+        let rfq_info = ctx.accounts.rfq.to_account_info();
+        require!(rfq_info.data_len() < Rfq::LEN, RfqError::RfqAlreadyMigrated);
+
+        let required = Rent::get()?.minimum_balance(Rfq::LEN);
+        let top_up = required.saturating_sub(rfq_info.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.creator.to_account_info(),
+                        to: rfq_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        rfq_info.resize(Rfq::LEN)?;
+
+        // Checks the discriminator
+        let mut rfq = Rfq::try_deserialize(&mut &rfq_info.try_borrow_data()?[..])?;
+        require!(rfq.creator == ctx.accounts.creator.key(), RfqError::NotCreator);
+
+        let premium_account = &ctx.accounts.premium_account;
+        require!(premium_account.owner == rfq.creator, RfqError::InvalidCreatorAccount);
+        rfq.premium_mint = premium_account.mint;
+        rfq.premium_account = premium_account.key();
+        rfq.version = CURRENT_RFQ_VERSION;
+        rfq.try_serialize(&mut &mut rfq_info.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+
     /// Expire an RFQ (anyone can call if past valid_until_ts)
     pub fn expire_rfq(ctx: Context<ExpireRfq>) -> Result<()> {
         let rfq = &mut ctx.accounts.rfq;
@@ -172,6 +184,59 @@ pub mod rfq {
     }
 }
 
+/// Layout version written by create_rfq, bumped whenever Rfq grows
+pub const CURRENT_RFQ_VERSION: u8 = 2;
+
+/// Validate the terms, fill in a freshly created RFQ and take the next id
+fn open_rfq(
+    config: &mut Config,
+    rfq: &mut Rfq,
+    creator: Pubkey,
+    premium_account: &Account<TokenAccount>,
+    bump: u8,
+    terms: RfqTerms,
+) -> Result<()> {
+    let clock = Clock::get()?;
+
+    require!(terms.valid_until_ts > clock.unix_timestamp, RfqError::InvalidExpiry);
+    require!(terms.expiry_ts > clock.unix_timestamp, RfqError::InvalidExpiry);
+
+    rfq.id = config.rfq_count;
+    rfq.creator = creator;
+    rfq.underlying = terms.underlying;
+    rfq.option_type = terms.option_type;
+    rfq.expiry_ts = terms.expiry_ts;
+    rfq.strike = terms.strike;
+    rfq.notional_tokens = terms.notional_tokens;
+    rfq.premium_floor_per_token_bps = terms.premium_floor_per_token_bps;
+    rfq.valid_until_ts = terms.valid_until_ts;
+    rfq.settlement = terms.settlement;
+    rfq.oracle_price = terms.oracle_price;
+    rfq.oracle_ts = terms.oracle_ts;
+    rfq.status = RfqStatus::Open;
+    rfq.filled_by = Pubkey::default();
+    rfq.filled_premium = 0;
+    rfq.filled_premium_per_token_bps = 0;
+    rfq.created_at = clock.unix_timestamp;
+    rfq.bump = bump;
+    rfq.premium_mint = premium_account.mint;
+    rfq.premium_account = premium_account.key();
+    rfq.version = CURRENT_RFQ_VERSION;
+
+    config.rfq_count = config.rfq_count.checked_add(1).unwrap();
+
+    emit!(RfqCreatedEvent {
+        rfq_id: rfq.id,
+        underlying: terms.underlying,
+        strike: terms.strike,
+        notional_tokens: terms.notional_tokens,
+        premium_floor_per_token_bps: terms.premium_floor_per_token_bps,
+        valid_until_ts: terms.valid_until_ts,
+    });
+
+    Ok(())
+}
+
 // ============================================================================
 // Account Structures
 // ============================================================================
@@ -193,7 +258,9 @@ pub struct MakerAccount {
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Rfq {
+    // Version 1
     pub id: u64,
     pub creator: Pubkey,
     pub underlying: Pubkey,
@@ -212,25 +279,48 @@ pub struct Rfq {
     pub filled_premium_per_token_bps: u32, // Actual premium rate per token
     pub created_at: i64,
     pub bump: u8,
+    // Version 2
+    pub premium_mint: Pubkey,           // Mint the premium is paid in
+    pub premium_account: Pubkey,        // Creator's account that receives the premium
+    pub version: u8,
+}
+
+impl Rfq {
+    pub const LEN: usize = 8 + Rfq::INIT_SPACE;
+}
+
+/// Terms of a new RFQ
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RfqTerms {
+    pub underlying: Pubkey,
+    pub option_type: OptionType,
+    pub expiry_ts: i64,
+    pub strike: u64,
+    pub notional_tokens: u64,           // Token exposure (fractional options)
+    pub premium_floor_per_token_bps: u32, // Min premium rate in basis points
+    pub valid_until_ts: i64,
+    pub settlement: SettlementType,
+    pub oracle_price: u64,
+    pub oracle_ts: i64,
 }
 
 // ============================================================================
 // Enums
 // ============================================================================
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 pub enum OptionType {
     Call,
     Put,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 pub enum SettlementType {
     Cash,
     Physical,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 pub enum RfqStatus {
     Open,
     Filled,
@@ -296,7 +386,7 @@ pub struct CreateRfq<'info> {
     #[account(
         init,
         payer = creator,
-        space = 8 + 8 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 1 + 32 + 8 + 8 + 1,
+        space = Rfq::LEN,
         seeds = [b"rfq", config.rfq_count.to_le_bytes().as_ref()],
         bump
     )]
@@ -305,6 +395,44 @@ pub struct CreateRfq<'info> {
     #[account(mut)]
     pub creator: Signer<'info>,
 
+    /// Token account the premium is paid into
+    #[account(
+        constraint = premium_account.owner == creator.key() @ RfqError::InvalidCreatorAccount
+    )]
+    pub premium_account: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRfqWithPayer<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Rfq::LEN,
+        seeds = [b"rfq", config.rfq_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub rfq: Account<'info, Rfq>,
+
+    pub creator: Signer<'info>,
+
+    /// Token account the premium is paid into
+    #[account(
+        constraint = premium_account.owner == creator.key() @ RfqError::InvalidCreatorAccount
+    )]
+    pub premium_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -330,12 +458,19 @@ pub struct FillRfq<'info> {
     )]
     pub maker_account: Account<'info, MakerAccount>,
 
-    /// The token account to receive premium (vault's account)
-    #[account(mut)]
+    /// The premium account chosen by the creator (the vault's account)
+    #[account(
+        mut,
+        address = rfq.premium_account @ RfqError::InvalidCreatorAccount,
+        token::mint = rfq.premium_mint
+    )]
     pub creator_token_account: Account<'info, TokenAccount>,
 
     /// The maker's token account to pay premium from
-    #[account(mut)]
+    #[account(
+        mut,
+        token::mint = rfq.premium_mint
+    )]
     pub maker_token_account: Account<'info, TokenAccount>,
 
     #[account(mut)]
@@ -363,6 +498,21 @@ pub struct CancelRfq<'info> {
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateRfq<'info> {
+    /// CHECK: Deserialized in the handler after the resize, which checks the discriminator
+    #[account(mut, owner = crate::ID)]
+    pub rfq: UncheckedAccount<'info>,
+
+    /// Token account the premium is paid into, checked against the creator in the handler
+    pub premium_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct ExpireRfq<'info> {
     #[account(
@@ -428,4 +578,10 @@ pub enum RfqError {
     PremiumBelowFloor,
     #[msg("RFQ has not expired yet")]
     RfqNotExpired,
+    #[msg("Premium account is not the RFQ creator's premium account")]
+    InvalidCreatorAccount,
+    #[msg("RFQ is already at the current layout")]
+    RfqAlreadyMigrated,
+    #[msg("Signer is not the RFQ creator")]
+    NotCreator,
 }
@@ -1,63 +1,391 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer, MintTo, Burn};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer, CloseAccount};
+use anchor_spl::token_2022::{self, Token2022};
+use anchor_spl::token_interface::spl_pod::optional_keys::OptionalNonZeroPubkey;
+use anchor_spl::token_interface::spl_token_metadata_interface::state::{Field, TokenMetadata};
+use anchor_spl::token_interface::{
+    self, Burn, MintTo, TokenInterface, TokenMetadataInitialize, TokenMetadataUpdateField, TransferChecked,
+};
+use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use anchor_spl::token_2022::spl_token_2022::state::Mint as MintState;
+use oracle::program::Oracle;
+use oracle::{AssetConfig, OracleStatus, PriceHistory, PriceResult};
+use rfq::program::Rfq;
 
 declare_id!("8gJHdrseXDiqUuBUBtMuNgn6G6GoLZ8oLiPHwru7NuPY");
 
+// Strikes and oracle prices passed to RFQs use 6 decimals (USDC)
+pub const PRICE_DECIMALS: u32 = 6;
+
+// If no oracle observation was recorded for an option's expiry, this long after
+// expiry the authority can attest the settlement price
+pub const SETTLEMENT_GRACE_SECS: i64 = 24 * 60 * 60;
+
+// EpochRecord.price_per_share is NAV in underlying base units per share, scaled by 1e9
+pub const SHARE_PRICE_SCALE: u64 = 1_000_000_000;
+
+// Minimum seed deposit locked in every new vault, in underlying base units
+pub const MIN_SEED_DEPOSIT: u64 = 1_000;
+
+// Deposits queued with a nonzero min_shares_out each pass their receipt to
+// advance_epoch, so an epoch takes at most this many to fit the roll's transaction
+pub const MAX_GUARDED_DEPOSITS: u32 = 20;
+
+// Instant withdrawal fee cap (10%)
+pub const MAX_INSTANT_WITHDRAW_FEE_BPS: u16 = 1_000;
+
+// Fee caps: 5% annual management fee, 50% performance fee
+pub const MAX_MANAGEMENT_FEE_BPS: u16 = 500;
+pub const MAX_PERFORMANCE_FEE_BPS: u16 = 5_000;
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+// Layout version written to new accounts. Bump it when fields are appended and
+// teach `migrate_vault` their defaults.
+pub const CURRENT_VAULT_VERSION: u8 = 6;
+
 // Metaplex Token Metadata Program ID: metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s
 pub static TOKEN_METADATA_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
     11, 112, 101, 177, 227, 209, 124, 69, 56, 157, 82, 127, 107, 4, 195, 205,
     88, 184, 108, 115, 26, 160, 253, 181, 73, 182, 209, 188, 3, 248, 41, 70,
 ]);
 
+/// Read the oracle price via CPI, rejecting anything but an Ok status
+fn read_oracle_price<'info>(
+    oracle_program: AccountInfo<'info>,
+    asset_config: AccountInfo<'info>,
+    pyth_price_account: AccountInfo<'info>,
+) -> Result<PriceResult> {
+    let price = oracle::cpi::get_price(CpiContext::new(
+        oracle_program,
+        oracle::cpi::accounts::GetPrice {
+            asset_config,
+            pyth_price_account,
+        },
+    ))?
+    .get();
+    require!(price.status == OracleStatus::Ok, VaultError::OracleNotOk);
+    Ok(price)
+}
+
+/// Oracle price the vault's quote balance is valued at, `None` while it holds no
+/// quote assets. The oracle accounts are only required once it does.
+fn quote_price<'info>(
+    vault: &Vault,
+    asset_config: &Option<Account<'info, AssetConfig>>,
+    pyth_price_account: &Option<UncheckedAccount<'info>>,
+    oracle_program: &Option<Program<'info, Oracle>>,
+) -> Result<Option<u64>> {
+    if vault.total_quote_assets == 0 {
+        return Ok(None);
+    }
+    let (Some(asset_config), Some(pyth_price_account), Some(oracle_program)) =
+        (asset_config, pyth_price_account, oracle_program)
+    else {
+        return err!(VaultError::MissingOracleAccounts);
+    };
+    require!(
+        vault.uses_asset_config(asset_config),
+        VaultError::InvalidOracleAsset
+    );
+    let price = read_oracle_price(
+        oracle_program.to_account_info(),
+        asset_config.to_account_info(),
+        pyth_price_account.to_account_info(),
+    )?
+    .scaled_price(PRICE_DECIMALS)
+    .ok_or(VaultError::InvalidOraclePrice)?;
+    Ok(Some(price))
+}
+
+/// NAV for the conversion views, see `Vault::nav`
+fn view_nav(accounts: &VaultView) -> Result<Nav> {
+    let vault = &accounts.vault;
+    let price = quote_price(vault, &accounts.asset_config, &accounts.pyth_price_account, &accounts.oracle_program)?;
+    Ok(vault.nav(price, Clock::get()?.unix_timestamp).ok_or(VaultError::Overflow)?)
+}
+
+/// Top `account` up to the rent-exempt minimum for `len` bytes, paid by `payer`
+fn fund_rent_exemption<'info>(
+    account: &AccountInfo<'info>,
+    len: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(len);
+    let top_up = required.saturating_sub(account.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether a put maker's xStock account lets the vault take delivery of `notional`
+fn approves_delivery(maker_option_account: &TokenAccount, vault: &Pubkey, notional: u64) -> bool {
+    maker_option_account.delegate == Some(*vault).into()
+        && maker_option_account.delegated_amount >= notional
+        && maker_option_account.amount >= notional
+}
+
+/// Pay a fill's premium back to the maker from the vault's premium account, for
+/// auctions closed without the vault writing the option
+fn refund_premium<'info>(
+    vault: &Account<'info, Vault>,
+    rfq: &rfq::Rfq,
+    premium_account: Option<&Account<'info, TokenAccount>>,
+    maker_premium_account: Option<&Account<'info, TokenAccount>>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let (Some(premium_account), Some(maker_premium_account)) = (premium_account, maker_premium_account) else {
+        return err!(VaultError::MissingRefundAccounts);
+    };
+
+    let asset_id = vault.asset_id.as_bytes();
+    let seeds = &[
+        b"vault",
+        asset_id,
+        &[vault.bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: premium_account.to_account_info(),
+                to: maker_premium_account.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        rfq.filled_premium,
+    )
+}
+
+/// Close a program-owned account that isn't loaded as an `Account`, refunding its rent to `destination`
+fn close_program_account<'info>(account: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    let refund = destination
+        .lamports()
+        .checked_add(account.lamports())
+        .ok_or(VaultError::Overflow)?;
+    **destination.try_borrow_mut_lamports()? = refund;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.assign(&system_program::ID);
+    account.resize(0)?;
+    Ok(())
+}
+
+/// Metaplex `DataV2` for the share token: no royalties, creators, collection or uses
+fn share_metadata_data_v2(name: &str, symbol: &str, uri: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    // name, symbol, uri (strings with length prefix)
+    for field in [name, symbol, uri] {
+        data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        data.extend_from_slice(field.as_bytes());
+    }
+
+    // sellerFeeBasisPoints (u16) = 0
+    data.extend_from_slice(&0u16.to_le_bytes());
+
+    // creators, collection, uses: None = 0
+    data.extend_from_slice(&[0, 0, 0]);
+
+    data
+}
+
 #[program]
 pub mod vault {
     use super::*;
 
     /// Initialize a new vault for a specific xStock asset
+    ///
+    /// The authority seeds the vault with at least `MIN_SEED_DEPOSIT` underlying. The
+    /// matching shares are counted in `total_shares` but never minted, so they stay
+    /// locked forever and the share price can't be inflated against an empty vault.
     pub fn initialize_vault(
         ctx: Context<InitializeVault>,
         asset_id: String,
         utilization_cap_bps: u16,
+        seed_amount: u64,
     ) -> Result<()> {
-        let vault = &mut ctx.accounts.vault;
-        vault.authority = ctx.accounts.authority.key();
-        vault.asset_id = asset_id;
-        vault.underlying_mint = ctx.accounts.underlying_mint.key();
-        vault.share_mint = ctx.accounts.share_mint.key();
-        vault.vault_token_account = ctx.accounts.vault_token_account.key();
-        vault.total_assets = 0;
-        vault.total_shares = 0;
-        vault.epoch = 0;
-        vault.utilization_cap_bps = utilization_cap_bps;
-        vault.last_roll_timestamp = 0;
-        vault.pending_withdrawals = 0;
-        // Initialize notional exposure tracking
-        vault.epoch_notional_exposed = 0;
-        vault.epoch_premium_earned = 0;
-        vault.epoch_premium_per_token_bps = 0;
-        vault.bump = ctx.bumps.vault;
-        Ok(())
+        require!(seed_amount >= MIN_SEED_DEPOSIT, VaultError::SeedTooSmall);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.authority_token_account.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            seed_amount,
+        )?;
+
+        ctx.accounts.vault.init(VaultInit {
+            authority: ctx.accounts.authority.key(),
+            asset_id,
+            underlying_mint: ctx.accounts.underlying_mint.key(),
+            underlying_decimals: ctx.accounts.underlying_mint.decimals,
+            share_mint: ctx.accounts.share_mint.key(),
+            share_token_program: token::ID,
+            vault_token_account: ctx.accounts.vault_token_account.key(),
+            utilization_cap_bps,
+            seed_amount,
+            bump: ctx.bumps.vault,
+        })
     }
 
-    /// Deposit underlying tokens and receive vault shares
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        require!(amount > 0, VaultError::ZeroAmount);
+    /// Initialize a vault whose share mint is a Token-2022 mint carrying its own metadata
+    ///
+    /// Same as `initialize_vault`, but the share mint has the metadata pointer and
+    /// token metadata extensions instead of a Metaplex metadata account. The vault
+    /// PDA is the metadata update authority, so `update_share_metadata` keeps working
+    /// after an authority transfer.
+    pub fn initialize_vault_token_2022(
+        ctx: Context<InitializeVaultToken2022>,
+        asset_id: String,
+        utilization_cap_bps: u16,
+        seed_amount: u64,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        require!(seed_amount >= MIN_SEED_DEPOSIT, VaultError::SeedTooSmall);
 
-        let vault = &mut ctx.accounts.vault;
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.authority_token_account.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            seed_amount,
+        )?;
 
-        // Calculate shares to mint (1:1 if first deposit, otherwise pro-rata)
-        let shares_to_mint = if vault.total_shares == 0 {
-            amount
-        } else {
-            // shares = amount * total_shares / total_assets
-            (amount as u128)
-                .checked_mul(vault.total_shares as u128)
-                .unwrap()
-                .checked_div(vault.total_assets as u128)
-                .unwrap() as u64
+        let bump = ctx.bumps.vault;
+        let seeds = &[
+            b"vault",
+            asset_id.as_bytes(),
+            &[bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Initializing the metadata grows the mint, which must already hold the rent
+        let share_mint = ctx.accounts.share_mint.to_account_info();
+        let metadata = TokenMetadata {
+            update_authority: OptionalNonZeroPubkey(ctx.accounts.vault.key()),
+            mint: share_mint.key(),
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+            additional_metadata: vec![],
         };
+        fund_rent_exemption(
+            &share_mint,
+            share_mint.data_len() + metadata.tlv_size_of()?,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        token_interface::token_metadata_initialize(
+            CpiContext::new_with_signer(
+                ctx.accounts.share_token_program.to_account_info(),
+                TokenMetadataInitialize {
+                    program_id: ctx.accounts.share_token_program.to_account_info(),
+                    metadata: share_mint.clone(),
+                    update_authority: ctx.accounts.vault.to_account_info(),
+                    mint_authority: ctx.accounts.vault.to_account_info(),
+                    mint: share_mint.clone(),
+                },
+                signer_seeds,
+            ),
+            name,
+            symbol,
+            uri,
+        )?;
+
+        ctx.accounts.vault.init(VaultInit {
+            authority: ctx.accounts.authority.key(),
+            asset_id,
+            underlying_mint: ctx.accounts.underlying_mint.key(),
+            underlying_decimals: ctx.accounts.underlying_mint.decimals,
+            share_mint: share_mint.key(),
+            share_token_program: ctx.accounts.share_token_program.key(),
+            vault_token_account: ctx.accounts.vault_token_account.key(),
+            utilization_cap_bps,
+            seed_amount,
+            bump,
+        })
+    }
+
+    /// Deposit underlying tokens
+    ///
+    /// Deposits are queued and converted at the next epoch's opening price when the
+    /// epoch rolls, so they can't capture premium earned before they arrived. The
+    /// shares are then collected with `claim_shares`. A vault with no shares mints
+    /// 1:1 immediately.
+    ///
+    /// A nonzero `min_shares_out` is checked against the shares the deposit would get
+    /// at the current NAV after accrued fees, the same price `preview_deposit` reports
+    /// (or the minted shares for an empty vault). Oracle accounts are then needed to
+    /// value the quote balance once the vault holds one. A queued deposit is checked
+    /// again at the roll's price and refunded if it falls short; at most
+    /// `MAX_GUARDED_DEPOSITS` queued deposits per epoch can carry a minimum.
+    pub fn deposit(ctx: Context<Deposit>, amount: u64, min_shares_out: u64) -> Result<()> {
+        require!(amount > 0, VaultError::ZeroAmount);
+
+        let vault = &ctx.accounts.vault;
+        if min_shares_out > 0 {
+            let quote_price = quote_price(
+                vault,
+                &ctx.accounts.asset_config,
+                &ctx.accounts.pyth_price_account,
+                &ctx.accounts.oracle_program,
+            )?;
+            let expected_shares = vault
+                .nav(quote_price, Clock::get()?.unix_timestamp)
+                .and_then(|nav| nav.convert_to_shares(amount))
+                .ok_or(VaultError::Overflow)?;
+            require!(expected_shares >= min_shares_out, VaultError::SlippageExceeded);
+        }
+
+        // Guarded launch: allowlist, vault TVL cap and per-wallet cap
+        if vault.allowlist_enabled {
+            require!(ctx.accounts.allowlist_entry.is_some(), VaultError::NotAllowlisted);
+        }
+        if vault.deposit_cap > 0 {
+            let tvl = vault.total_assets
+                .checked_add(vault.pending_deposits)
+                .and_then(|tvl| tvl.checked_add(amount))
+                .ok_or(VaultError::Overflow)?;
+            require!(tvl <= vault.deposit_cap, VaultError::DepositCapExceeded);
+        }
 
-        require!(shares_to_mint > 0, VaultError::ZeroShares);
+        // The per-wallet cap is on principal deposited, so moving shares to another
+        // account doesn't free up room
+        let depositor = &mut ctx.accounts.depositor;
+        depositor.vault = vault.key();
+        depositor.user = ctx.accounts.user.key();
+        depositor.principal = depositor.principal.checked_add(amount).ok_or(VaultError::Overflow)?;
+        depositor.bump = ctx.bumps.depositor;
+        depositor.version = CURRENT_VAULT_VERSION;
+        if vault.user_deposit_cap > 0 {
+            require!(depositor.principal <= vault.user_deposit_cap, VaultError::UserDepositCapExceeded);
+        }
 
         // Transfer underlying tokens from user to vault
         token::transfer(
@@ -72,7 +400,88 @@ pub mod vault {
             amount,
         )?;
 
-        // Mint vault shares to user
+        let vault = &mut ctx.accounts.vault;
+
+        if vault.total_shares == 0 {
+            // Mint vault shares to user
+            let asset_id = vault.asset_id.as_bytes();
+            let seeds = &[
+                b"vault",
+                asset_id,
+                &[vault.bump],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            token_interface::mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.share_token_program.to_account_info(),
+                    MintTo {
+                        mint: ctx.accounts.share_mint.to_account_info(),
+                        to: ctx.accounts.user_share_account.to_account_info(),
+                        authority: vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount,
+            )?;
+
+            // Update vault state
+            vault.total_assets = vault.total_assets.checked_add(amount).unwrap();
+            vault.total_shares = vault.total_shares.checked_add(amount).unwrap();
+
+            emit!(DepositEvent {
+                vault: vault.key(),
+                user: ctx.accounts.user.key(),
+                amount,
+                shares_minted: amount,
+                epoch: vault.epoch,
+            });
+
+            return Ok(());
+        }
+
+        // Queue the deposit until the epoch rolls
+        let receipt = &mut ctx.accounts.deposit_receipt;
+        if receipt.amount > 0 {
+            require!(receipt.epoch == vault.epoch, VaultError::UnclaimedDeposit);
+        }
+        // The roll checks every receipt holding a minimum, so count the new ones
+        if min_shares_out > 0 && receipt.min_shares_out == 0 {
+            require!(vault.guarded_deposits < MAX_GUARDED_DEPOSITS, VaultError::TooManyGuardedDeposits);
+            vault.guarded_deposits += 1;
+        }
+        receipt.user = ctx.accounts.user.key();
+        receipt.vault = vault.key();
+        receipt.epoch = vault.epoch;
+        receipt.amount = receipt.amount.checked_add(amount).unwrap();
+        receipt.min_shares_out = receipt.min_shares_out.checked_add(min_shares_out).unwrap();
+        receipt.bump = ctx.bumps.deposit_receipt;
+        receipt.version = CURRENT_VAULT_VERSION;
+
+        vault.pending_deposits = vault.pending_deposits.checked_add(amount).unwrap();
+
+        emit!(DepositQueuedEvent {
+            vault: vault.key(),
+            user: ctx.accounts.user.key(),
+            amount,
+            total_queued: receipt.amount,
+            epoch: vault.epoch,
+        });
+
+        Ok(())
+    }
+
+    /// Mint the shares for a deposit queued in an epoch that has since rolled
+    ///
+    /// If the roll priced the deposit below the `min_shares_out` given at deposit time,
+    /// it set the deposit aside instead, and it is returned here.
+    pub fn claim_shares(ctx: Context<ClaimShares>) -> Result<()> {
+        let receipt = &ctx.accounts.deposit_receipt;
+        let vault = &ctx.accounts.vault;
+
+        require!(receipt.amount > 0, VaultError::ZeroAmount);
+        require!(vault.epoch > receipt.epoch, VaultError::EpochNotSettled);
+
         let asset_id = vault.asset_id.as_bytes();
         let seeds = &[
             b"vault",
@@ -81,9 +490,45 @@ pub mod vault {
         ];
         let signer_seeds = &[&seeds[..]];
 
-        token::mint_to(
+        if receipt.refunded {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.vault_token_account.to_account_info(),
+                        to: ctx.accounts.user_token_account.to_account_info(),
+                        authority: vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                receipt.amount,
+            )?;
+
+            // The roll left the deposit out of total_assets and reserved it
+            let vault = &mut ctx.accounts.vault;
+            vault.reserved_assets = vault.reserved_assets.checked_sub(receipt.amount).ok_or(VaultError::Overflow)?;
+            if let Some(depositor) = ctx.accounts.depositor.as_mut() {
+                depositor.principal = depositor.principal.saturating_sub(receipt.amount);
+            }
+
+            emit!(RefundClaimedEvent {
+                vault: vault.key(),
+                user: ctx.accounts.user.key(),
+                amount: receipt.amount,
+                deposit_epoch: receipt.epoch,
+            });
+
+            return Ok(());
+        }
+
+        let shares = ctx.accounts.epoch_record
+            .claimable_shares(receipt.amount)
+            .ok_or(VaultError::Overflow)?;
+        require!(shares > 0, VaultError::ZeroShares);
+
+        token_interface::mint_to(
             CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.share_token_program.to_account_info(),
                 MintTo {
                     mint: ctx.accounts.share_mint.to_account_info(),
                     to: ctx.accounts.user_share_account.to_account_info(),
@@ -91,37 +536,50 @@ pub mod vault {
                 },
                 signer_seeds,
             ),
-            shares_to_mint,
+            shares,
         )?;
 
-        // Update vault state
-        vault.total_assets = vault.total_assets.checked_add(amount).unwrap();
-        vault.total_shares = vault.total_shares.checked_add(shares_to_mint).unwrap();
-
-        emit!(DepositEvent {
+        emit!(SharesClaimedEvent {
             vault: vault.key(),
             user: ctx.accounts.user.key(),
-            amount,
-            shares_minted: shares_to_mint,
-            epoch: vault.epoch,
+            amount: receipt.amount,
+            shares,
+            deposit_epoch: receipt.epoch,
         });
 
         Ok(())
     }
 
     /// Request withdrawal (queued until epoch end)
+    ///
+    /// The shares are escrowed in the vault until the request is processed or cancelled.
     pub fn request_withdrawal(ctx: Context<RequestWithdrawal>, shares: u64) -> Result<()> {
         require!(shares > 0, VaultError::ZeroAmount);
 
-        let withdrawal = &mut ctx.accounts.withdrawal_request;
-        let vault = &mut ctx.accounts.vault;
-
         // Check user has enough shares
         require!(
             ctx.accounts.user_share_account.amount >= shares,
             VaultError::InsufficientShares
         );
 
+        // Escrow the shares so they can't be moved before processing
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_share_account.to_account_info(),
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    to: ctx.accounts.withdrawal_shares.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            shares,
+            ctx.accounts.share_mint.decimals,
+        )?;
+
+        let withdrawal = &mut ctx.accounts.withdrawal_request;
+        let vault = &mut ctx.accounts.vault;
+
         // Create or update withdrawal request
         withdrawal.user = ctx.accounts.user.key();
         withdrawal.vault = vault.key();
@@ -129,6 +587,7 @@ pub mod vault {
         withdrawal.request_epoch = vault.epoch;
         withdrawal.processed = false;
         withdrawal.bump = ctx.bumps.withdrawal_request;
+        withdrawal.version = CURRENT_VAULT_VERSION;
 
         // Track pending withdrawals in vault
         vault.pending_withdrawals = vault.pending_withdrawals.checked_add(shares).unwrap();
@@ -143,10 +602,107 @@ pub mod vault {
         Ok(())
     }
 
+    /// Add shares to a withdrawal request during the epoch it was filed
+    pub fn increase_withdrawal(ctx: Context<IncreaseWithdrawal>, shares: u64) -> Result<()> {
+        require!(shares > 0, VaultError::ZeroAmount);
+        require!(
+            ctx.accounts.withdrawal_request.request_epoch == ctx.accounts.vault.epoch,
+            VaultError::WithdrawalEpochPassed
+        );
+        require!(
+            ctx.accounts.user_share_account.amount >= shares,
+            VaultError::InsufficientShares
+        );
+
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_share_account.to_account_info(),
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    to: ctx.accounts.withdrawal_shares.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            shares,
+            ctx.accounts.share_mint.decimals,
+        )?;
+
+        let withdrawal = &mut ctx.accounts.withdrawal_request;
+        let vault = &mut ctx.accounts.vault;
+
+        withdrawal.shares = withdrawal.shares.checked_add(shares).unwrap();
+        vault.pending_withdrawals = vault.pending_withdrawals.checked_add(shares).unwrap();
+
+        emit!(WithdrawalIncreasedEvent {
+            vault: vault.key(),
+            user: ctx.accounts.user.key(),
+            shares,
+            total_shares: withdrawal.shares,
+            request_epoch: withdrawal.request_epoch,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a withdrawal request during the epoch it was filed, returning the escrowed shares
+    ///
+    /// The request account is closed so a new one can be filed later.
+    pub fn cancel_withdrawal(ctx: Context<CancelWithdrawal>) -> Result<()> {
+        let withdrawal = &ctx.accounts.withdrawal_request;
+        let vault = &mut ctx.accounts.vault;
+
+        require!(
+            withdrawal.request_epoch == vault.epoch,
+            VaultError::WithdrawalEpochPassed
+        );
+
+        let shares = withdrawal.shares;
+
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.share_token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.withdrawal_shares.to_account_info(),
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    to: ctx.accounts.user_share_account.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            shares,
+            ctx.accounts.share_mint.decimals,
+        )?;
+
+        vault.pending_withdrawals = vault.pending_withdrawals.checked_sub(shares).unwrap();
+
+        emit!(WithdrawalCancelledEvent {
+            vault: vault.key(),
+            user: ctx.accounts.user.key(),
+            shares,
+            request_epoch: withdrawal.request_epoch,
+        });
+
+        Ok(())
+    }
+
     /// Process withdrawal after epoch settles
+    ///
+    /// Redeems at the closing price of the epoch the request was filed in, paid from
+    /// the amount set aside when that epoch rolled. Burns the escrowed shares and
+    /// closes the request so the user can file another.
     pub fn process_withdrawal(ctx: Context<ProcessWithdrawal>) -> Result<()> {
-        let withdrawal = &mut ctx.accounts.withdrawal_request;
+        let withdrawal = &ctx.accounts.withdrawal_request;
         let vault = &mut ctx.accounts.vault;
+        let record = &ctx.accounts.epoch_record;
 
         require!(!withdrawal.processed, VaultError::AlreadyProcessed);
         require!(
@@ -156,14 +712,13 @@ pub mod vault {
 
         let shares = withdrawal.shares;
 
-        // Calculate underlying amount to return
-        let amount = (shares as u128)
-            .checked_mul(vault.total_assets as u128)
-            .unwrap()
-            .checked_div(vault.total_shares as u128)
-            .unwrap() as u64;
+        // Calculate underlying amount to return at the epoch's closing price
+        let amount = record.redeem_assets(shares).ok_or(VaultError::Overflow)?;
 
-        // Burn user's shares
+        // Quote premium is paid out pro-rata alongside the underlying
+        let quote_amount = record.redeem_quote_assets(shares).ok_or(VaultError::Overflow)?;
+
+        // Burn the escrowed shares
         let asset_id = vault.asset_id.as_bytes();
         let seeds = &[
             b"vault",
@@ -172,14 +727,15 @@ pub mod vault {
         ];
         let signer_seeds = &[&seeds[..]];
 
-        token::burn(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
+        token_interface::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.share_token_program.to_account_info(),
                 Burn {
                     mint: ctx.accounts.share_mint.to_account_info(),
-                    from: ctx.accounts.user_share_account.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
+                    from: ctx.accounts.withdrawal_shares.to_account_info(),
+                    authority: vault.to_account_info(),
                 },
+                signer_seeds,
             ),
             shares,
         )?;
@@ -198,87 +754,832 @@ pub mod vault {
             amount,
         )?;
 
-        // Update vault state
-        vault.total_assets = vault.total_assets.checked_sub(amount).unwrap();
-        vault.total_shares = vault.total_shares.checked_sub(shares).unwrap();
-        vault.pending_withdrawals = vault.pending_withdrawals.checked_sub(shares).unwrap();
+        if quote_amount > 0 {
+            let (Some(vault_quote_account), Some(user_quote_account)) = (
+                &ctx.accounts.vault_quote_account,
+                &ctx.accounts.user_quote_account,
+            ) else {
+                return err!(VaultError::MissingQuoteAccounts);
+            };
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: vault_quote_account.to_account_info(),
+                        to: user_quote_account.to_account_info(),
+                        authority: vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                quote_amount,
+            )?;
+        }
 
-        // Mark withdrawal as processed
-        withdrawal.processed = true;
+        // Release the amount set aside at the epoch roll
+        vault.reserved_assets = vault.reserved_assets.checked_sub(amount).unwrap();
+        vault.reserved_quote_assets = vault.reserved_quote_assets.checked_sub(quote_amount).unwrap();
 
         emit!(WithdrawalProcessedEvent {
             vault: vault.key(),
             user: ctx.accounts.user.key(),
             shares,
             amount,
+            quote_amount,
             epoch: vault.epoch,
         });
 
         Ok(())
     }
 
-    /// Advance epoch (called by keeper after settlement)
-    pub fn advance_epoch(ctx: Context<AdvanceEpoch>, premium_earned: u64) -> Result<()> {
-        let vault = &mut ctx.accounts.vault;
-        let clock = Clock::get()?;
+    /// Redeem shares immediately against idle underlying, for a fee left to remaining depositors
+    ///
+    /// Only assets not backing this epoch's live options can be withdrawn. Shares are
+    /// priced at the current NAV after the fees accrued since the last roll, as the
+    /// conversion views price them, and any quote balance is paid pro-rata, both net of
+    /// `instant_withdraw_fee_bps`. The withheld fees stay in the vault. Once an option
+    /// has expired the NAV no longer reflects what the epoch owes, so withdrawals are
+    /// closed until the epoch is settled.
+    pub fn instant_withdraw(ctx: Context<InstantWithdraw>, shares: u64) -> Result<()> {
+        require!(shares > 0, VaultError::ZeroAmount);
+        require!(
+            ctx.accounts.user_share_account.amount >= shares,
+            VaultError::InsufficientShares
+        );
 
-        // Store epoch stats before resetting
-        let notional_exposed = vault.epoch_notional_exposed;
-        let avg_premium_bps = vault.epoch_premium_per_token_bps;
+        let now = Clock::get()?.unix_timestamp;
+        require!(!ctx.accounts.vault.awaiting_settlement(now), VaultError::AwaitingSettlement);
 
-        // Add premium to total assets (increases share value)
-        vault.total_assets = vault.total_assets.checked_add(premium_earned).unwrap();
-        vault.epoch = vault.epoch.checked_add(1).unwrap();
-        vault.last_roll_timestamp = clock.unix_timestamp;
+        let price = quote_price(
+            &ctx.accounts.vault,
+            &ctx.accounts.asset_config,
+            &ctx.accounts.pyth_price_account,
+            &ctx.accounts.oracle_program,
+        )?;
+        let vault = &mut ctx.accounts.vault;
+        let nav = vault.nav(price, now).ok_or(VaultError::Overflow)?;
+        let RedeemPreview { assets: amount, quote_assets: quote_amount, fee, quote_fee } =
+            vault.preview_redeem(shares, &nav).ok_or(VaultError::Overflow)?;
 
-        // Reset epoch tracking for new epoch
-        vault.epoch_notional_exposed = 0;
-        vault.epoch_premium_earned = 0;
-        vault.epoch_premium_per_token_bps = 0;
+        require!(amount > 0, VaultError::ZeroAmount);
+        require!(amount <= vault.idle_assets(), VaultError::InsufficientIdleAssets);
 
-        emit!(EpochAdvancedEvent {
-            vault: vault.key(),
-            new_epoch: vault.epoch,
-            premium_earned,
-            notional_exposed,
-            avg_premium_bps,
-            total_assets: vault.total_assets,
-            total_shares: vault.total_shares,
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.user_share_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        if quote_amount > 0 {
+            let (Some(vault_quote_account), Some(user_quote_account)) = (
+                &ctx.accounts.vault_quote_account,
+                &ctx.accounts.user_quote_account,
+            ) else {
+                return err!(VaultError::MissingQuoteAccounts);
+            };
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: vault_quote_account.to_account_info(),
+                        to: user_quote_account.to_account_info(),
+                        authority: vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                quote_amount,
+            )?;
+        }
+
+        // The fee stays in the vault, raising the price of the remaining shares
+        vault.total_assets = vault.total_assets.checked_sub(amount).unwrap();
+        vault.total_quote_assets = vault.total_quote_assets.checked_sub(quote_amount).unwrap();
+        vault.total_shares = vault.total_shares.checked_sub(shares).unwrap();
+
+        emit!(InstantWithdrawEvent {
+            vault: vault.key(),
+            user: ctx.accounts.user.key(),
+            shares,
+            amount,
+            quote_amount,
+            fee,
+            quote_fee,
+            epoch: vault.epoch,
+        });
+
+        Ok(())
+    }
+
+    /// Propose a new vault authority, who must call `accept_authority` to take over.
+    /// Proposing the default pubkey cancels a pending transfer.
+    pub fn propose_authority(ctx: Context<UpdateVaultConfig>, new_authority: Pubkey) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.pending_authority = new_authority;
+
+        emit!(AuthorityProposedEvent {
+            vault: vault.key(),
+            authority: vault.authority,
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Complete an authority transfer (pending authority only)
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let previous_authority = vault.authority;
+        vault.authority = ctx.accounts.pending_authority.key();
+        vault.pending_authority = Pubkey::default();
+
+        emit!(AuthorityTransferredEvent {
+            vault: vault.key(),
+            previous_authority,
+            new_authority: vault.authority,
+        });
+
+        Ok(())
+    }
+
+    /// Set the keeper allowed to run epoch operations alongside the authority
+    pub fn set_keeper(ctx: Context<UpdateVaultConfig>, keeper: Pubkey) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.keeper = keeper;
+
+        emit!(KeeperUpdatedEvent {
+            vault: vault.key(),
+            keeper,
+        });
+
+        Ok(())
+    }
+
+    /// Upgrade a vault created by an older program to the current layout
+    ///
+    /// Grows the account to `Vault::LEN`, with the authority paying the extra rent,
+    /// and fills the fields added since the vault's `version` with their defaults.
+    /// The vault is taken unchecked because an old layout may be too short to
+    /// deserialize before the resize.
+    pub fn migrate_vault(ctx: Context<MigrateVault>) -> Result<()> {
+        let vault_info = ctx.accounts.vault.to_account_info();
+
+        if vault_info.data_len() < Vault::LEN {
+            fund_rent_exemption(
+                &vault_info,
+                Vault::LEN,
+                &ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+            vault_info.resize(Vault::LEN)?;
+        }
+
+        let mut vault = Vault::try_deserialize(&mut &vault_info.try_borrow_data()?[..])?;
+        require_keys_eq!(vault.authority, ctx.accounts.authority.key(), VaultError::Unauthorized);
+        let expected = Pubkey::create_program_address(
+            &[b"vault", vault.asset_id.as_bytes(), &[vault.bump]],
+            ctx.program_id,
+        )
+        .map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+        require_keys_eq!(expected, vault_info.key(), ErrorCode::ConstraintSeeds);
+
+        let from_version = vault.version;
+        require!(from_version < CURRENT_VAULT_VERSION, VaultError::VaultAlreadyMigrated);
+
+        // Unversioned vaults predate roles, fees and strategies. The resize
+        // zero-fills, so only fields whose default isn't zero need setting.
+        if from_version < 1 {
+            let underlying_mint = &ctx.accounts.underlying_mint;
+            require_keys_eq!(underlying_mint.key(), vault.underlying_mint, VaultError::InvalidMint);
+            vault.underlying_decimals = underlying_mint.decimals;
+            // Options recorded before the upgrade were settled off-chain and have
+            // no locked collateral, so they must not hold up settle_epoch
+            vault.epoch_notional_exposed = 0;
+            // Old requests left their shares in the user's wallet. They are
+            // escrowed and counted again by migrate_withdrawal.
+            vault.pending_withdrawals = 0;
+            if vault.keeper == Pubkey::default() {
+                vault.keeper = vault.authority;
+            }
+            if vault.fee_recipient == Pubkey::default() {
+                vault.fee_recipient = vault.authority;
+            }
+            vault.option_mint = vault.underlying_mint;
+            vault.option_decimals = vault.underlying_decimals;
+            // No quote balance before version 1
+            let price_per_share = vault.nav_per_share(0);
+            if vault.high_water_mark == 0 {
+                vault.high_water_mark = price_per_share;
+            }
+            if vault.epoch_start_price_per_share == 0 {
+                vault.epoch_start_price_per_share = price_per_share;
+            }
+        }
+        // Version 2 records the share mint's token program, SPL Token before it
+        if from_version < 2 {
+            vault.share_token_program = token::ID;
+        }
+        // Version 3 tracks settlement per RFQ; an epoch with options recorded under
+        // an older version has no RecordedRfq settlement state to finish. Auctions
+        // started before the upgrade have no Auction account and can't be recorded.
+        if from_version < 3 {
+            require!(vault.epoch_notional_exposed == 0 || vault.epoch_settled, VaultError::RfqsNotSettled);
+        }
+        // Version 4 records the epoch's earliest expiry. It stays 0 for options recorded
+        // before the upgrade, so instant withdrawals wait for their settlement.
+        // Version 5 reserves collateral for open auctions; an auction started before
+        // the upgrade has no reservation to release. It also pins the put maker's xStock
+        // account on RecordedRfq and records who paid for the locked collateral account,
+        // so unsettled options recorded before it can't settle.
+        if from_version < 5 {
+            require!(vault.open_auctions == 0, VaultError::AuctionsOpen);
+            require!(vault.epoch_notional_exposed == 0 || vault.epoch_settled, VaultError::RfqsNotSettled);
+            vault.locked_collateral_payer = vault.authority;
+        }
+        // Version 6 counts the deposits queued with a minimum so the roll can check
+        // them. Deposits queued before the upgrade weren't counted, so migrate right
+        // after a roll, before new deposits queue.
+        if from_version < 6 {
+            require!(vault.pending_deposits == 0, VaultError::DepositsQueued);
+        }
+        vault.version = CURRENT_VAULT_VERSION;
+
+        vault.try_serialize(&mut &mut vault_info.try_borrow_mut_data()?[..])?;
+
+        emit!(VaultMigratedEvent {
+            vault: vault_info.key(),
+            from_version,
+            to_version: CURRENT_VAULT_VERSION,
+        });
+
+        Ok(())
+    }
+
+    /// Upgrade a withdrawal request filed before shares were escrowed (user only)
+    ///
+    /// Old requests left the shares in the user's wallet and have no EpochRecord to
+    /// redeem against. An unprocessed request escrows up to its size from the user's
+    /// share balance and is re-queued for the next roll. Processed or unbacked requests
+    /// are closed, refunding the rent, so a new request can be filed.
+    pub fn migrate_withdrawal(ctx: Context<MigrateWithdrawal>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.version == CURRENT_VAULT_VERSION, VaultError::VaultNotMigrated);
+
+        let request_info = ctx.accounts.withdrawal_request.to_account_info();
+        let user_info = ctx.accounts.user.to_account_info();
+
+        if request_info.data_len() < WithdrawalRequest::LEN {
+            fund_rent_exemption(
+                &request_info,
+                WithdrawalRequest::LEN,
+                &user_info,
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+            request_info.resize(WithdrawalRequest::LEN)?;
+        }
+
+        let mut withdrawal = WithdrawalRequest::try_deserialize(&mut &request_info.try_borrow_data()?[..])?;
+        require_keys_eq!(withdrawal.user, user_info.key(), VaultError::Unauthorized);
+        require_keys_eq!(withdrawal.vault, vault.key(), VaultError::Unauthorized);
+        require!(withdrawal.version == 0, VaultError::WithdrawalAlreadyMigrated);
+
+        let shares = if withdrawal.processed {
+            0
+        } else {
+            withdrawal.shares.min(ctx.accounts.user_share_account.amount)
+        };
+
+        if shares == 0 {
+            close_program_account(&request_info, &user_info)?;
+        } else {
+            token_interface::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.share_token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.user_share_account.to_account_info(),
+                        mint: ctx.accounts.share_mint.to_account_info(),
+                        to: ctx.accounts.withdrawal_shares.to_account_info(),
+                        authority: user_info.clone(),
+                    },
+                ),
+                shares,
+                ctx.accounts.share_mint.decimals,
+            )?;
+
+            withdrawal.shares = shares;
+            withdrawal.request_epoch = vault.epoch;
+            withdrawal.version = CURRENT_VAULT_VERSION;
+            withdrawal.try_serialize(&mut &mut request_info.try_borrow_mut_data()?[..])?;
+
+            vault.pending_withdrawals = vault.pending_withdrawals.checked_add(shares).unwrap();
+        }
+
+        emit!(WithdrawalMigratedEvent {
+            vault: vault.key(),
+            user: user_info.key(),
+            shares,
+            request_epoch: vault.epoch,
+        });
+
+        Ok(())
+    }
+
+    /// Set the instant withdrawal fee in basis points
+    pub fn set_instant_withdraw_fee(ctx: Context<UpdateVaultConfig>, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= MAX_INSTANT_WITHDRAW_FEE_BPS, VaultError::InvalidFee);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.instant_withdraw_fee_bps = fee_bps;
+
+        emit!(InstantWithdrawFeeUpdatedEvent {
+            vault: vault.key(),
+            fee_bps,
         });
 
         Ok(())
     }
 
-    /// Record notional exposure when an RFQ is filled (fractional options)
-    /// Premium is the total premium paid for the notional exposure
-    pub fn record_notional_exposure(
-        ctx: Context<RecordNotionalExposure>,
-        notional_tokens: u64,
-        premium: u64,
+    /// Set the annual management fee, performance fee and the account they're paid to
+    pub fn set_fees(
+        ctx: Context<UpdateVaultConfig>,
+        management_fee_bps: u16,
+        performance_fee_bps: u16,
+        fee_recipient: Pubkey,
     ) -> Result<()> {
+        require!(management_fee_bps <= MAX_MANAGEMENT_FEE_BPS, VaultError::InvalidFee);
+        require!(performance_fee_bps <= MAX_PERFORMANCE_FEE_BPS, VaultError::InvalidFee);
+
         let vault = &mut ctx.accounts.vault;
+        vault.management_fee_bps = management_fee_bps;
+        vault.performance_fee_bps = performance_fee_bps;
+        vault.fee_recipient = fee_recipient;
+
+        emit!(FeesUpdatedEvent {
+            vault: vault.key(),
+            management_fee_bps,
+            performance_fee_bps,
+            fee_recipient,
+        });
 
-        // Calculate max exposure: TVL * utilization_cap_bps / 10000
-        let max_exposure = (vault.total_assets as u128)
-            .checked_mul(vault.utilization_cap_bps as u128)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap() as u64;
+        Ok(())
+    }
 
-        let new_exposure = vault.epoch_notional_exposed
-            .checked_add(notional_tokens)
+    /// Set the vault TVL cap, the per-wallet cap (0 = unlimited) and whether deposits
+    /// require an allowlist entry
+    pub fn set_deposit_limits(
+        ctx: Context<UpdateVaultConfig>,
+        deposit_cap: u64,
+        user_deposit_cap: u64,
+        allowlist_enabled: bool,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        vault.deposit_cap = deposit_cap;
+        vault.user_deposit_cap = user_deposit_cap;
+        vault.allowlist_enabled = allowlist_enabled;
+
+        emit!(DepositLimitsUpdatedEvent {
+            vault: vault.key(),
+            deposit_cap,
+            user_deposit_cap,
+            allowlist_enabled,
+        });
+
+        Ok(())
+    }
+
+    /// Approve a wallet to deposit while the allowlist is enabled
+    pub fn add_to_allowlist(ctx: Context<AddToAllowlist>, user: Pubkey) -> Result<()> {
+        let entry = &mut ctx.accounts.allowlist_entry;
+        entry.vault = ctx.accounts.vault.key();
+        entry.user = user;
+        entry.bump = ctx.bumps.allowlist_entry;
+        entry.version = CURRENT_VAULT_VERSION;
+
+        emit!(AllowlistUpdatedEvent {
+            vault: entry.vault,
+            user,
+            allowed: true,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a wallet's allowlist entry. Existing shares and queued deposits are unaffected.
+    pub fn remove_from_allowlist(ctx: Context<RemoveFromAllowlist>) -> Result<()> {
+        emit!(AllowlistUpdatedEvent {
+            vault: ctx.accounts.vault.key(),
+            user: ctx.accounts.allowlist_entry.user,
+            allowed: false,
+        });
+
+        Ok(())
+    }
+
+    /// Advance epoch (called by keeper after settlement)
+    ///
+    /// Premium is measured as the vault token balances above `total_assets` and
+    /// `total_quote_assets`, so only tokens actually held by the vault are added to
+    /// NAV. The premium recorded from fills this epoch must have arrived before the
+    /// epoch can roll: in the quote token once the vault has a quote account,
+    /// otherwise in underlying.
+    ///
+    /// NAV is `total_assets` plus the quote balance valued at the oracle price, so
+    /// oracle accounts are needed whenever the vault holds quote assets.
+    ///
+    /// Management and performance fees are accrued on NAV before the epoch closes by
+    /// minting shares to the fee recipient. The performance fee only applies to the
+    /// NAV per share gain above the vault's high-water mark.
+    ///
+    /// Writes the closing `EpochRecord`, sets aside the assets owed to this epoch's
+    /// withdrawal requests at the closing price and converts queued deposits into
+    /// shares at the same price.
+    ///
+    /// The receipt of every deposit queued this epoch with a `min_shares_out` is
+    /// passed as a writable remaining account. A deposit the closing price gives
+    /// fewer shares than its minimum doesn't convert: it is reserved, outside
+    /// `total_assets`, and returned by `claim_shares`.
+    pub fn advance_epoch<'info>(ctx: Context<'_, '_, 'info, 'info, AdvanceEpoch<'info>>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        let clock = Clock::get()?;
+
+        // Options sold this epoch must be settled before rolling, and an auction still
+        // open could be filled after the roll with nowhere to record it
+        require!(
+            vault.epoch_notional_exposed == 0 || vault.epoch_settled,
+            VaultError::EpochNotSettled
+        );
+        require!(vault.open_auctions == 0, VaultError::AuctionsOpen);
+
+        // Store epoch stats before resetting
+        let notional_exposed = vault.epoch_notional_exposed;
+        let avg_premium_bps = vault.epoch_premium_per_token_bps;
+
+        // Premium is whatever the vault holds beyond its accounted, reserved and queued assets
+        let premium_earned = ctx.accounts.vault_token_account.amount
+            .checked_sub(vault.total_assets)
+            .and_then(|balance| balance.checked_sub(vault.reserved_assets))
+            .and_then(|balance| balance.checked_sub(vault.pending_deposits))
+            .ok_or(VaultError::UnbackedAssets)?;
+
+        let quote_premium_earned = if vault.has_quote_account() {
+            let quote_token_account = ctx.accounts.quote_token_account
+                .as_ref()
+                .ok_or(VaultError::MissingQuoteAccounts)?;
+            quote_token_account.amount
+                .checked_sub(vault.total_quote_assets)
+                .and_then(|balance| balance.checked_sub(vault.reserved_quote_assets))
+                .ok_or(VaultError::UnbackedAssets)?
+        } else {
+            0
+        };
+
+        let premium_received = if vault.premium_in_quote() {
+            quote_premium_earned
+        } else {
+            premium_earned
+        };
+        require!(
+            premium_received >= vault.epoch_premium_earned,
+            VaultError::UnbackedPremium
+        );
+
+        // Add premium to total assets (increases share value)
+        vault.total_assets = vault.total_assets.checked_add(premium_earned).unwrap();
+        vault.total_quote_assets = vault.total_quote_assets.checked_add(quote_premium_earned).unwrap();
+
+        // NAV in underlying, including the quote balance at the oracle price
+        let quote_price = quote_price(
+            vault,
+            &ctx.accounts.asset_config,
+            &ctx.accounts.pyth_price_account,
+            &ctx.accounts.oracle_program,
+        )?;
+        let quote_value = vault.quote_balance_value(quote_price).ok_or(VaultError::Overflow)?;
+        let total_value = vault.total_value(quote_value).ok_or(VaultError::Overflow)?;
+
+        // Accrue fees in underlying terms, then pay them as newly minted shares
+        let (management_fee, performance_fee) = vault
+            .accrued_fees(quote_value, clock.unix_timestamp)
+            .ok_or(VaultError::Overflow)?;
+        let fee_assets = management_fee.checked_add(performance_fee).ok_or(VaultError::Overflow)?;
+        let fee_shares = vault.fee_shares(total_value, fee_assets).ok_or(VaultError::Overflow)?;
+
+        if fee_shares > 0 {
+            let (Some(share_mint), Some(fee_recipient_share_account), Some(share_token_program)) = (
+                &ctx.accounts.share_mint,
+                &ctx.accounts.fee_recipient_share_account,
+                &ctx.accounts.share_token_program,
+            ) else {
+                return err!(VaultError::MissingFeeAccounts);
+            };
+
+            let asset_id = vault.asset_id.as_bytes();
+            let seeds = &[
+                b"vault",
+                asset_id,
+                &[vault.bump],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            token_interface::mint_to(
+                CpiContext::new_with_signer(
+                    share_token_program.to_account_info(),
+                    MintTo {
+                        mint: share_mint.to_account_info(),
+                        to: fee_recipient_share_account.to_account_info(),
+                        authority: vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                fee_shares,
+            )?;
+
+            vault.total_shares = vault.total_shares.checked_add(fee_shares).unwrap();
+        }
+
+        vault.high_water_mark = vault.high_water_mark.max(vault.nav_per_share(quote_value));
+
+        if fee_assets > 0 {
+            emit!(FeesAccruedEvent {
+                vault: vault.key(),
+                epoch: vault.epoch,
+                management_fee,
+                performance_fee,
+                fee_shares,
+                high_water_mark: vault.high_water_mark,
+            });
+        }
+
+        // Close the epoch at the current share price
+        let record = &mut ctx.accounts.epoch_record;
+        record.vault = vault.key();
+        record.epoch = vault.epoch;
+        record.closing_assets = vault.total_assets;
+        record.closing_quote_assets = vault.total_quote_assets;
+        record.closing_shares = vault.total_shares;
+        record.price_per_share = vault.nav_per_share(quote_value);
+        record.withdrawal_shares = vault.pending_withdrawals;
+        record.bump = ctx.bumps.epoch_record;
+        record.version = CURRENT_VAULT_VERSION;
+
+        // History for APY and audit
+        record.start_timestamp = vault.last_roll_timestamp;
+        record.end_timestamp = clock.unix_timestamp;
+        record.start_price_per_share = vault.epoch_start_price_per_share;
+        record.notional_exposed = notional_exposed;
+        record.premium_earned = premium_earned;
+        record.quote_premium_earned = quote_premium_earned;
+        record.premium_per_token_bps = avg_premium_bps;
+        record.min_strike = vault.epoch_min_strike;
+        record.max_strike = vault.epoch_max_strike;
+        record.settlement_price = vault.epoch_settlement_price;
+        record.payout = vault.epoch_payout;
+
+        // Realized PnL in underlying, with quote premium valued at the settlement price
+        let quote_premium_value = if vault.epoch_settlement_price > 0 {
+            vault
+                .quote_value(quote_premium_earned, vault.epoch_settlement_price)
+                .ok_or(VaultError::Overflow)?
+        } else {
+            0
+        };
+        record.realized_pnl = i64::try_from(premium_earned as u128 + quote_premium_value as u128)
+            .ok()
+            .and_then(|gain| gain.checked_sub(vault.epoch_payout as i64))
+            .ok_or(VaultError::Overflow)?;
+
+        // Queued deposits buy in at closing NAV
+        let total_shares = vault.total_shares;
+        let convert_to_shares = |assets: u64| -> Option<u64> {
+            if total_shares == 0 {
+                return Some(assets);
+            }
+            // shares = deposits * total_shares / (total_assets + quote value)
+            (assets as u128)
+                .checked_mul(total_shares as u128)
+                .and_then(|v| v.checked_div(total_value as u128))
+                .and_then(|v| u64::try_from(v).ok())
+        };
+
+        // Deposits priced below their minimum are refunded instead of converting
+        let mut refunded_deposits: u64 = 0;
+        require!(
+            ctx.remaining_accounts.len() == vault.guarded_deposits as usize,
+            VaultError::InvalidDepositReceipts
+        );
+        for (i, receipt_info) in ctx.remaining_accounts.iter().enumerate() {
+            require!(
+                !ctx.remaining_accounts[..i].iter().any(|other| other.key == receipt_info.key),
+                VaultError::InvalidDepositReceipts
+            );
+            let mut receipt: Account<'info, DepositReceipt> = Account::try_from(receipt_info)?;
+            require!(
+                receipt.vault == vault.key() && receipt.epoch == vault.epoch && receipt.min_shares_out > 0,
+                VaultError::InvalidDepositReceipts
+            );
+
+            let shares = convert_to_shares(receipt.amount).ok_or(VaultError::Overflow)?;
+            if shares < receipt.min_shares_out {
+                receipt.refunded = true;
+                receipt.exit(&crate::ID)?;
+                refunded_deposits = refunded_deposits.checked_add(receipt.amount).ok_or(VaultError::Overflow)?;
+
+                emit!(DepositRefundedEvent {
+                    vault: vault.key(),
+                    user: receipt.user,
+                    amount: receipt.amount,
+                    shares,
+                    min_shares_out: receipt.min_shares_out,
+                    deposit_epoch: receipt.epoch,
+                });
+            }
+        }
+        let converted_deposits = vault.pending_deposits
+            .checked_sub(refunded_deposits)
+            .ok_or(VaultError::Overflow)?;
+
+        let deposit_shares = convert_to_shares(converted_deposits).ok_or(VaultError::Overflow)?;
+        record.deposit_assets = converted_deposits;
+        record.deposit_shares = deposit_shares;
+
+        // Set aside what this epoch's withdrawals are owed; the escrowed shares leave the float
+        let withdrawal_assets = record.redeem_assets(vault.pending_withdrawals).ok_or(VaultError::Overflow)?;
+        let withdrawal_quote_assets = record
+            .redeem_quote_assets(vault.pending_withdrawals)
             .ok_or(VaultError::Overflow)?;
+        vault.total_assets = vault.total_assets.checked_sub(withdrawal_assets).unwrap();
+        vault.total_quote_assets = vault.total_quote_assets.checked_sub(withdrawal_quote_assets).unwrap();
+        vault.reserved_assets = vault.reserved_assets.checked_add(withdrawal_assets).unwrap();
+        vault.reserved_quote_assets = vault.reserved_quote_assets.checked_add(withdrawal_quote_assets).unwrap();
+        vault.total_shares = vault.total_shares.checked_sub(vault.pending_withdrawals).unwrap();
+        vault.pending_withdrawals = 0;
+
+        // Queued deposits join the working capital; their shares are minted on claim
+        vault.total_assets = vault.total_assets.checked_add(converted_deposits).unwrap();
+        vault.total_shares = vault.total_shares.checked_add(deposit_shares).unwrap();
+        vault.reserved_assets = vault.reserved_assets.checked_add(refunded_deposits).unwrap();
+        vault.pending_deposits = 0;
+        vault.guarded_deposits = 0;
+
+        vault.epoch = vault.epoch.checked_add(1).unwrap();
+        vault.last_roll_timestamp = clock.unix_timestamp;
+        // The next epoch opens at NAV after withdrawals leave, at the same oracle price
+        let opening_quote_value = vault.quote_balance_value(quote_price).ok_or(VaultError::Overflow)?;
+        vault.epoch_start_price_per_share = vault.nav_per_share(opening_quote_value);
+
+        // Reset epoch tracking for new epoch
+        vault.epoch_notional_exposed = 0;
+        vault.epoch_collateral = 0;
+        vault.epoch_premium_earned = 0;
+        vault.epoch_premium_per_token_bps = 0;
+        vault.epoch_settled = false;
+        vault.epoch_settlement_price = 0;
+        vault.epoch_settled_notional = 0;
+        vault.epoch_first_expiry = 0;
+        vault.epoch_payout = 0;
+        vault.epoch_min_strike = 0;
+        vault.epoch_max_strike = 0;
+
+        emit!(EpochAdvancedEvent {
+            vault: vault.key(),
+            new_epoch: vault.epoch,
+            premium_earned,
+            quote_premium_earned,
+            notional_exposed,
+            avg_premium_bps,
+            total_assets: vault.total_assets,
+            total_quote_assets: vault.total_quote_assets,
+            total_shares: vault.total_shares,
+        });
 
+        Ok(())
+    }
+
+    /// Record the exposure of an RFQ the vault filled this epoch (fractional options)
+    ///
+    /// Notional, premium and strike are read from the filled `rfq::Rfq` account, and
+    /// a `RecordedRfq` marker is created so the same fill can't be counted twice.
+    /// Premium is in the quote token once a covered call vault has a quote account.
+    /// The strike sizes the cash backing a put vault's options.
+    ///
+    /// The collateral reserved when the auction started moves from the vault token
+    /// account into the epoch's locked collateral account, so withdrawals can only
+    /// draw on idle assets until the epoch settles. The fill must be recorded before
+    /// the option expires.
+    pub fn record_notional_exposure(ctx: Context<RecordNotionalExposure>) -> Result<()> {
+        let rfq = &ctx.accounts.rfq;
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.epoch_settled, VaultError::EpochAlreadySettled);
+
+        let (option_type, settlement) = if vault.strategy == VaultStrategy::CashSecuredPut {
+            (rfq::OptionType::Put, rfq::SettlementType::Physical)
+        } else {
+            (rfq::OptionType::Call, rfq::SettlementType::Cash)
+        };
+        require_keys_eq!(rfq.creator, vault.key(), VaultError::InvalidRfq);
+        require_keys_eq!(rfq.underlying, vault.option_mint, VaultError::InvalidRfq);
+        require!(rfq.status == rfq::RfqStatus::Filled, VaultError::RfqNotFilled);
+        require!(rfq.option_type == option_type, VaultError::InvalidRfq);
+        require!(rfq.settlement == settlement, VaultError::InvalidRfq);
+        require_keys_eq!(rfq.premium_account, vault.premium_account(), VaultError::InvalidRfq);
+        // Once the option has expired its payoff is known, so the fill can only be
+        // closed with close_auction, which refunds the premium
         require!(
-            new_exposure <= max_exposure,
-            VaultError::ExceedsUtilizationCap
+            Clock::get()?.unix_timestamp < rfq.expiry_ts,
+            VaultError::OptionExpired
         );
 
-        // Update epoch tracking
-        vault.epoch_notional_exposed = new_exposure;
+        // A put can only be assigned if the maker lets the vault take their xStock,
+        // so the approval must be in place before the put counts. The approved account
+        // is pinned so settlement can't be pointed at another one.
+        let mut approved_account = Pubkey::default();
+        if vault.strategy == VaultStrategy::CashSecuredPut {
+            let Some(maker_option_account) = &ctx.accounts.maker_option_account else {
+                return err!(VaultError::MissingMakerAccount);
+            };
+            require!(
+                approves_delivery(maker_option_account, &vault.key(), rfq.notional_tokens),
+                VaultError::MakerNotApproved
+            );
+            approved_account = maker_option_account.key();
+        }
+
+        let notional_tokens = rfq.notional_tokens;
+        let premium = rfq.filled_premium;
+
+        // The collateral was reserved against the utilization cap by start_auction,
+        // so withdrawals since then can't have taken it
+        let collateral = vault.collateral_for(notional_tokens, rfq.strike).ok_or(VaultError::Overflow)?;
+        let new_collateral = vault.epoch_collateral
+            .checked_add(collateral)
+            .ok_or(VaultError::Overflow)?;
+
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    to: ctx.accounts.locked_collateral_account.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            collateral,
+        )?;
+
+        // Update epoch tracking. The epoch's first record creates the locked collateral
+        // account, so its signer gets the rent back when settle_epoch closes it.
+        if vault.epoch_notional_exposed == 0 {
+            vault.epoch_first_expiry = rfq.expiry_ts;
+            vault.locked_collateral_payer = ctx.accounts.authority.key();
+        } else {
+            vault.epoch_first_expiry = vault.epoch_first_expiry.min(rfq.expiry_ts);
+        }
+        vault.epoch_collateral = new_collateral;
+        vault.epoch_notional_exposed = vault.epoch_notional_exposed
+            .checked_add(notional_tokens)
+            .ok_or(VaultError::Overflow)?;
         vault.epoch_premium_earned = vault.epoch_premium_earned
             .checked_add(premium)
             .ok_or(VaultError::Overflow)?;
+        // The auction account is closed by the constraint
+        vault.open_auctions = vault.open_auctions.saturating_sub(1);
+        vault.auction_collateral = vault.auction_collateral.saturating_sub(ctx.accounts.auction.collateral);
 
         // Calculate running average premium rate in basis points
         if vault.epoch_notional_exposed > 0 {
@@ -289,194 +1590,2545 @@ pub mod vault {
                 .unwrap()) as u32;
         }
 
-        emit!(NotionalExposureEvent {
-            vault: vault.key(),
-            epoch: vault.epoch,
-            notional_tokens,
-            premium,
-            total_notional_this_epoch: vault.epoch_notional_exposed,
-            total_premium_this_epoch: vault.epoch_premium_earned,
-            avg_premium_bps: vault.epoch_premium_per_token_bps,
-        });
+        let recorded = &mut ctx.accounts.recorded_rfq;
+        recorded.vault = vault.key();
+        recorded.rfq = rfq.key();
+        recorded.epoch = vault.epoch;
+        recorded.bump = ctx.bumps.recorded_rfq;
+        recorded.version = CURRENT_VAULT_VERSION;
+        recorded.settled = false;
+        recorded.settlement_price = 0;
+        recorded.payout = 0;
+        recorded.maker_option_account = approved_account;
+
+        emit!(NotionalExposureEvent {
+            vault: vault.key(),
+            epoch: vault.epoch,
+            rfq: rfq.key(),
+            notional_tokens,
+            premium,
+            collateral,
+            total_notional_this_epoch: vault.epoch_notional_exposed,
+            total_premium_this_epoch: vault.epoch_premium_earned,
+            avg_premium_bps: vault.epoch_premium_per_token_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Create the vault's strategy parameters (authority only)
+    pub fn initialize_strategy(ctx: Context<InitializeStrategy>, config: StrategyConfig) -> Result<()> {
+        config.validate()?;
+
+        let strategy = &mut ctx.accounts.strategy;
+        strategy.vault = ctx.accounts.vault.key();
+        strategy.apply(&config);
+        strategy.bump = ctx.bumps.strategy;
+        strategy.version = CURRENT_VAULT_VERSION;
+
+        emit!(StrategyUpdatedEvent {
+            vault: strategy.vault,
+            config,
+        });
+
+        Ok(())
+    }
+
+    /// Update the vault's strategy parameters (authority only)
+    pub fn update_strategy(ctx: Context<UpdateStrategy>, config: StrategyConfig) -> Result<()> {
+        config.validate()?;
+
+        let strategy = &mut ctx.accounts.strategy;
+        strategy.apply(&config);
+
+        emit!(StrategyUpdatedEvent {
+            vault: strategy.vault,
+            config,
+        });
+
+        Ok(())
+    }
+
+    /// Start an RFQ auction for the vault's options: covered calls, or cash-secured
+    /// puts for a put vault
+    ///
+    /// CPIs into `rfq::create_rfq_with_payer` with the vault PDA as creator, so fills pay premium
+    /// straight into a vault-owned token account. The notional is the capacity left
+    /// under the utilization cap and the strategy's per-epoch limit, and the reference
+    /// price is read from the oracle. Strike, tenor, premium floor and RFQ window must
+    /// be within the vault's `StrategyParams`.
+    pub fn start_auction(
+        ctx: Context<StartAuction>,
+        strike: u64,
+        expiry_ts: i64,
+        premium_floor_per_token_bps: u32,
+        valid_until_ts: i64,
+    ) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let strategy = &ctx.accounts.strategy;
+        require!(!vault.epoch_settled, VaultError::EpochAlreadySettled);
+
+        let now = Clock::get()?.unix_timestamp;
+        let tenor = expiry_ts.saturating_sub(now);
+        require!(
+            tenor >= strategy.min_tenor_secs && tenor <= strategy.max_tenor_secs,
+            VaultError::InvalidTenor
+        );
+        let auction_window = valid_until_ts.saturating_sub(now);
+        require!(
+            auction_window > 0 && auction_window <= strategy.rfq_duration_secs,
+            VaultError::InvalidAuctionWindow
+        );
+        require!(
+            premium_floor_per_token_bps >= strategy.min_premium_bps,
+            VaultError::PremiumFloorTooLow
+        );
+
+        let price = read_oracle_price(
+            ctx.accounts.oracle_program.to_account_info(),
+            ctx.accounts.asset_config.to_account_info(),
+            ctx.accounts.pyth_price_account.to_account_info(),
+        )?;
+        let spot = price
+            .scaled_price(PRICE_DECIMALS)
+            .ok_or(VaultError::InvalidOraclePrice)?;
+
+        // Options are sold out of the money, within the strategy's OTM band:
+        // calls above spot, puts below
+        let (option_type, settlement, otm_distance) = match vault.strategy {
+            VaultStrategy::CoveredCall => {
+                require!(strike > spot, VaultError::InvalidStrike);
+                (rfq::OptionType::Call, rfq::SettlementType::Cash, strike - spot)
+            }
+            VaultStrategy::CashSecuredPut => {
+                require!(strike < spot && strike > 0, VaultError::InvalidStrike);
+                (rfq::OptionType::Put, rfq::SettlementType::Physical, spot - strike)
+            }
+        };
+        let otm_bps = (otm_distance as u128) * 10000 / spot as u128;
+        require!(
+            otm_bps >= strategy.min_otm_bps as u128 && otm_bps <= strategy.max_otm_bps as u128,
+            VaultError::InvalidStrike
+        );
+
+        // Size the notional to the collateral left under the utilization cap, after what
+        // is recorded and what other open auctions have reserved
+        let collateral_room = vault
+            .max_exposure()
+            .saturating_sub(vault.epoch_collateral)
+            .saturating_sub(vault.auction_collateral);
+        let mut notional_tokens = vault.notional_for(collateral_room, strike).ok_or(VaultError::Overflow)?;
+        if strategy.max_notional_per_epoch > 0 {
+            notional_tokens = notional_tokens.min(
+                strategy.max_notional_per_epoch.saturating_sub(vault.epoch_notional_exposed),
+            );
+        }
+        require!(notional_tokens > 0, VaultError::ExceedsUtilizationCap);
+        let collateral = vault.collateral_for(notional_tokens, strike).ok_or(VaultError::Overflow)?;
+
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        rfq::cpi::create_rfq_with_payer(
+            CpiContext::new_with_signer(
+                ctx.accounts.rfq_program.to_account_info(),
+                rfq::cpi::accounts::CreateRfqWithPayer {
+                    config: ctx.accounts.rfq_config.to_account_info(),
+                    rfq: ctx.accounts.rfq.to_account_info(),
+                    creator: vault.to_account_info(),
+                    premium_account: ctx.accounts.premium_account.to_account_info(),
+                    payer: ctx.accounts.authority.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            rfq::RfqTerms {
+                underlying: vault.option_mint,
+                option_type,
+                expiry_ts,
+                strike,
+                notional_tokens,
+                premium_floor_per_token_bps,
+                valid_until_ts,
+                settlement,
+                oracle_price: spot,
+                oracle_ts: price.publish_time,
+            },
+        )?;
+
+        let auction = &mut ctx.accounts.auction;
+        auction.vault = vault.key();
+        auction.rfq = ctx.accounts.rfq.key();
+        auction.epoch = vault.epoch;
+        auction.payer = ctx.accounts.authority.key();
+        auction.bump = ctx.bumps.auction;
+        auction.collateral = collateral;
+
+        emit!(AuctionStartedEvent {
+            vault: vault.key(),
+            epoch: vault.epoch,
+            rfq: ctx.accounts.rfq.key(),
+            strike,
+            notional_tokens,
+            premium_floor_per_token_bps,
+            oracle_price: spot,
+            expiry_ts,
+            valid_until_ts,
+        });
+
+        // Withdrawals can't take the collateral while the auction can still be filled
+        let vault = &mut ctx.accounts.vault;
+        vault.open_auctions = vault.open_auctions.checked_add(1).ok_or(VaultError::Overflow)?;
+        vault.auction_collateral = vault.auction_collateral.checked_add(collateral).ok_or(VaultError::Overflow)?;
+
+        Ok(())
+    }
+
+    /// Close an auction whose RFQ can no longer be filled or recorded (anyone can call)
+    ///
+    /// The RFQ must be cancelled, expired, or past its quote window. Filled auctions
+    /// are closed by `record_notional_exposure` instead, unless the option expired
+    /// before the fill was recorded: the vault never backed it, so the premium goes
+    /// back to the maker. Releases the collateral the auction reserved. The epoch
+    /// can't roll while any auction is open.
+    pub fn close_auction(ctx: Context<CloseAuction>) -> Result<()> {
+        let rfq = &ctx.accounts.rfq;
+        let now = Clock::get()?.unix_timestamp;
+        let fillable = rfq.status == rfq::RfqStatus::Open && now < rfq.valid_until_ts;
+        let filled = rfq.status == rfq::RfqStatus::Filled;
+        require!(
+            (!filled && !fillable) || (filled && now >= rfq.expiry_ts),
+            VaultError::AuctionStillOpen
+        );
+
+        if filled {
+            refund_premium(
+                &ctx.accounts.vault,
+                rfq,
+                ctx.accounts.premium_account.as_ref(),
+                ctx.accounts.maker_premium_account.as_ref(),
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        let vault = &mut ctx.accounts.vault;
+        vault.open_auctions = vault.open_auctions.saturating_sub(1);
+        vault.auction_collateral = vault.auction_collateral.saturating_sub(ctx.accounts.auction.collateral);
+
+        emit!(AuctionClosedEvent {
+            vault: vault.key(),
+            epoch: ctx.accounts.auction.epoch,
+            rfq: rfq.key(),
+            premium_refunded: if filled { rfq.filled_premium } else { 0 },
+        });
+
+        Ok(())
+    }
+
+    /// Close a put vault's filled auction whose maker never approved delivery
+    /// (authority or keeper)
+    ///
+    /// `record_notional_exposure` only records a put once the maker's associated xStock
+    /// account approves the vault as delegate for the notional. Without this a maker who
+    /// never approves would hold the epoch open until expiry. The rejection checks the
+    /// same account on-chain and fails while it approves, so a fill that could be
+    /// recorded can't be rejected. The premium goes back to the maker and the auction's
+    /// reserved collateral is released.
+    pub fn reject_fill(ctx: Context<RejectFill>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let rfq = &ctx.accounts.rfq;
+        require!(vault.strategy == VaultStrategy::CashSecuredPut, VaultError::InvalidStrategy);
+        require!(rfq.status == rfq::RfqStatus::Filled, VaultError::RfqNotFilled);
+        require!(
+            !approves_delivery(&ctx.accounts.maker_option_account, &vault.key(), rfq.notional_tokens),
+            VaultError::MakerApproved
+        );
+
+        refund_premium(
+            vault,
+            rfq,
+            Some(&ctx.accounts.premium_account),
+            Some(&ctx.accounts.maker_premium_account),
+            &ctx.accounts.token_program,
+        )?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.open_auctions = vault.open_auctions.saturating_sub(1);
+        vault.auction_collateral = vault.auction_collateral.saturating_sub(ctx.accounts.auction.collateral);
+
+        emit!(AuctionClosedEvent {
+            vault: vault.key(),
+            epoch: ctx.accounts.auction.epoch,
+            rfq: rfq.key(),
+            premium_refunded: rfq.filled_premium,
+        });
+
+        Ok(())
+    }
+
+    /// Create the vault's quote token account so premium paid in USDC is tracked in NAV
+    pub fn initialize_quote_account(ctx: Context<InitializeQuoteAccount>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.has_quote_account(), VaultError::QuoteAccountAlreadyInitialized);
+        require!(vault.strategy == VaultStrategy::CoveredCall, VaultError::InvalidStrategy);
+
+        vault.quote_mint = ctx.accounts.quote_mint.key();
+        vault.quote_token_account = ctx.accounts.quote_token_account.key();
+        vault.total_quote_assets = 0;
+
+        emit!(QuoteAccountInitializedEvent {
+            vault: vault.key(),
+            quote_mint: vault.quote_mint,
+            quote_token_account: vault.quote_token_account,
+        });
+
+        Ok(())
+    }
+
+    /// Turn a new vault into a cash-secured put vault (authority only)
+    ///
+    /// The vault takes USDC deposits (its `underlying_mint`) and sells puts on
+    /// `option_mint` through RFQ. Its quote account holds the xStock received when
+    /// puts are assigned, which is paid out pro-rata on withdrawal and valued at the
+    /// oracle price of `asset_config` when deposits convert. Must be called before
+    /// the first epoch rolls.
+    pub fn initialize_put_strategy(ctx: Context<InitializePutStrategy>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(vault.strategy == VaultStrategy::CoveredCall, VaultError::InvalidStrategy);
+        require!(!vault.has_quote_account(), VaultError::QuoteAccountAlreadyInitialized);
+        require!(vault.epoch == 0, VaultError::InvalidStrategy);
+        require!(
+            vault.underlying_decimals == PRICE_DECIMALS as u8,
+            VaultError::InvalidQuoteMint
+        );
+
+        vault.strategy = VaultStrategy::CashSecuredPut;
+        vault.option_mint = ctx.accounts.option_mint.key();
+        vault.option_decimals = ctx.accounts.option_mint.decimals;
+        vault.oracle_asset_config = ctx.accounts.asset_config.key();
+        vault.quote_mint = ctx.accounts.option_mint.key();
+        vault.quote_token_account = ctx.accounts.quote_token_account.key();
+        vault.total_quote_assets = 0;
+
+        emit!(PutStrategyInitializedEvent {
+            vault: vault.key(),
+            option_mint: vault.option_mint,
+            asset_config: vault.oracle_asset_config,
+            assigned_token_account: vault.quote_token_account,
+        });
+
+        Ok(())
+    }
+
+    /// Settle one RFQ recorded this epoch against its own expiry price (anyone can call)
+    ///
+    /// Uses the observation the oracle's price history holds for the option's expiry
+    /// slot (`oracle::get_settlement_price`), which anyone can record with
+    /// `record_observation` once the slot opens, so every settler sees the same price.
+    /// If none was recorded, the price attested by the authority with
+    /// `attest_settlement_price` after `SETTLEMENT_GRACE_SECS` is used instead.
+    ///
+    /// Calls that finished in the money are cash settled in underlying tokens,
+    /// `notional * (price - strike) / price`, paid from the epoch's locked collateral
+    /// and deducted from `total_assets`. A put that finished in the money is assigned
+    /// through the delegation `record_notional_exposure` required on the maker's
+    /// pinned `maker_option_account`: the vault takes the xStock into its quote account
+    /// and pays `notional * strike` USDC. If the maker has since revoked the approval or
+    /// moved the xStock, settlement fails until `SETTLEMENT_GRACE_SECS` after expiry,
+    /// after which the put expires unexercised so the epoch can still settle.
+    pub fn settle_rfq(ctx: Context<SettleRfq>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let rfq = &ctx.accounts.rfq;
+        require!(!ctx.accounts.recorded_rfq.settled, VaultError::RfqAlreadySettled);
+
+        let clock = Clock::get()?;
+        require!(clock.unix_timestamp >= rfq.expiry_ts, VaultError::OptionsNotExpired);
+
+        let settlement_price = if ctx.accounts.recorded_rfq.settlement_price > 0 {
+            ctx.accounts.recorded_rfq.settlement_price
+        } else {
+            let price = oracle::cpi::get_settlement_price(
+                CpiContext::new(
+                    ctx.accounts.oracle_program.to_account_info(),
+                    oracle::cpi::accounts::GetSettlementPrice {
+                        asset_config: ctx.accounts.asset_config.to_account_info(),
+                        price_history: ctx.accounts.price_history.to_account_info(),
+                    },
+                ),
+                rfq.expiry_ts,
+            )?
+            .get();
+            price
+                .scaled_price(PRICE_DECIMALS)
+                .ok_or(VaultError::InvalidOraclePrice)?
+        };
+
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let mut payout: u64 = 0;
+        let mut paid: u64 = 0;
+        let mut assigned: u64 = 0;
+
+        if vault.strategy == VaultStrategy::CashSecuredPut {
+            if settlement_price < rfq.strike {
+                let Some(maker_option_account) = &ctx.accounts.maker_option_account else {
+                    return err!(VaultError::MissingMakerAccount);
+                };
+
+                let approved = approves_delivery(maker_option_account, &vault.key(), rfq.notional_tokens);
+                require!(
+                    approved || clock.unix_timestamp >= rfq.expiry_ts.saturating_add(SETTLEMENT_GRACE_SECS),
+                    VaultError::MakerNotApproved
+                );
+
+                // Past the grace period a maker who revoked the approval lets the put lapse
+                if approved {
+                    let Some(quote_token_account) = &ctx.accounts.quote_token_account else {
+                        return err!(VaultError::MissingQuoteAccounts);
+                    };
+
+                    // Take delivery of the xStock and pay the strike in USDC
+                    token::transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: maker_option_account.to_account_info(),
+                                to: quote_token_account.to_account_info(),
+                                authority: vault.to_account_info(),
+                            },
+                            signer_seeds,
+                        ),
+                        rfq.notional_tokens,
+                    )?;
+
+                    paid = vault.collateral_for(rfq.notional_tokens, rfq.strike).ok_or(VaultError::Overflow)?;
+                    token::transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: ctx.accounts.locked_collateral_account.to_account_info(),
+                                to: ctx.accounts.maker_token_account.to_account_info(),
+                                authority: vault.to_account_info(),
+                            },
+                            signer_seeds,
+                        ),
+                        paid,
+                    )?;
+
+                    // The loss against the settlement price is the put's intrinsic value
+                    payout = vault
+                        .collateral_for(rfq.notional_tokens, rfq.strike - settlement_price)
+                        .ok_or(VaultError::Overflow)?;
+                    assigned = rfq.notional_tokens;
+                }
+            }
+        } else if settlement_price > rfq.strike {
+            // Cash settle the intrinsic value in underlying tokens
+            payout = ((rfq.notional_tokens as u128)
+                .checked_mul((settlement_price - rfq.strike) as u128)
+                .ok_or(VaultError::Overflow)?
+                / settlement_price as u128) as u64;
+            paid = payout;
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.locked_collateral_account.to_account_info(),
+                        to: ctx.accounts.maker_token_account.to_account_info(),
+                        authority: vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                payout,
+            )?;
+        }
+
+        let recorded = &mut ctx.accounts.recorded_rfq;
+        recorded.settled = true;
+        recorded.settlement_price = settlement_price;
+        recorded.payout = payout;
+
+        let vault = &mut ctx.accounts.vault;
+        if vault.epoch_settled_notional == 0 {
+            vault.epoch_min_strike = rfq.strike;
+        }
+        vault.epoch_min_strike = vault.epoch_min_strike.min(rfq.strike);
+        vault.epoch_max_strike = vault.epoch_max_strike.max(rfq.strike);
+        vault.epoch_settled_notional = vault.epoch_settled_notional
+            .checked_add(rfq.notional_tokens)
+            .ok_or(VaultError::Overflow)?;
+        vault.epoch_settlement_price = settlement_price;
+        vault.epoch_payout = vault.epoch_payout.checked_add(payout).ok_or(VaultError::Overflow)?;
+        vault.total_assets = vault.total_assets.checked_sub(paid).ok_or(VaultError::Overflow)?;
+        vault.total_quote_assets = vault.total_quote_assets.checked_add(assigned).ok_or(VaultError::Overflow)?;
+        // What was paid out has left the locked collateral account
+        vault.epoch_collateral = vault.epoch_collateral.checked_sub(paid).ok_or(VaultError::Overflow)?;
+
+        emit!(RfqSettledEvent {
+            vault: vault.key(),
+            epoch: vault.epoch,
+            rfq: rfq.key(),
+            settlement_price,
+            notional_tokens: rfq.notional_tokens,
+            payout,
+            assigned,
+        });
+
+        Ok(())
+    }
+
+    /// Set the settlement price of a recorded RFQ the oracle couldn't settle (authority only)
+    ///
+    /// Only allowed once `SETTLEMENT_GRACE_SECS` have passed since expiry without the
+    /// RFQ being settled, so an oracle outage can't hold the epoch open forever.
+    /// `settle_rfq` then settles against this price.
+    pub fn attest_settlement_price(ctx: Context<AttestSettlementPrice>, price: u64) -> Result<()> {
+        require!(price > 0, VaultError::InvalidOraclePrice);
+
+        let rfq = &ctx.accounts.rfq;
+        let recorded = &mut ctx.accounts.recorded_rfq;
+        require!(!recorded.settled, VaultError::RfqAlreadySettled);
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= rfq.expiry_ts.saturating_add(SETTLEMENT_GRACE_SECS),
+            VaultError::SettlementGracePeriod
+        );
+
+        recorded.settlement_price = price;
+
+        emit!(SettlementPriceAttestedEvent {
+            vault: ctx.accounts.vault.key(),
+            rfq: rfq.key(),
+            price,
+            authority: ctx.accounts.authority.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Close out this epoch's options once every recorded RFQ is settled
+    ///
+    /// Requires `settle_rfq` to have run for all of them, i.e. the settled notional
+    /// adds up to `epoch_notional_exposed`. Whatever collateral is left after paying
+    /// the makers is released to the vault token account and the locked account is closed,
+    /// refunding its rent to whoever paid it when the epoch's first RFQ was recorded.
+    pub fn settle_epoch(ctx: Context<SettleEpoch>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        require!(!vault.epoch_settled, VaultError::EpochAlreadySettled);
+        require!(vault.epoch_notional_exposed > 0, VaultError::NothingToSettle);
+        require!(
+            vault.epoch_settled_notional == vault.epoch_notional_exposed,
+            VaultError::RfqsNotSettled
+        );
+        // A fill landing after settlement could no longer be recorded
+        require!(vault.open_auctions == 0, VaultError::AuctionsOpen);
+
+        let (Some(locked_collateral_account), Some(locked_collateral_payer)) = (
+            &ctx.accounts.locked_collateral_account,
+            &ctx.accounts.locked_collateral_payer,
+        ) else {
+            return err!(VaultError::MissingCollateralAccount);
+        };
+
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Release the remaining collateral back to the idle account
+        let released = locked_collateral_account.amount;
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: locked_collateral_account.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            released,
+        )?;
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: locked_collateral_account.to_account_info(),
+                destination: locked_collateral_payer.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.epoch_settled = true;
+
+        emit!(EpochSettledEvent {
+            vault: vault.key(),
+            epoch: vault.epoch,
+            settlement_price: vault.epoch_settlement_price,
+            notional_settled: vault.epoch_settled_notional,
+            payout: vault.epoch_payout,
+            collateral_released: released,
+            total_assets: vault.total_assets,
+        });
+
+        Ok(())
+    }
+
+    /// Create metadata for the vault share token
+    /// This uses CPI to Metaplex Token Metadata program. The vault PDA is the update
+    /// authority, so metadata stays updatable after the vault authority changes.
+    pub fn create_share_metadata(
+        ctx: Context<CreateShareMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        
+        // Build signer seeds for vault PDA
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Create the metadata instruction data
+        // CreateMetadataAccountV3 instruction discriminator = 33
+        let mut data = vec![33u8];
+        data.extend(share_metadata_data_v2(&name, &symbol, &uri));
+
+        // isMutable = true
+        data.push(1);
+        
+        // collectionDetails: None = 0
+        data.push(0);
+
+        // Build account metas
+        let accounts = vec![
+            AccountMeta::new(ctx.accounts.metadata.key(), false),
+            AccountMeta::new_readonly(ctx.accounts.share_mint.key(), false),
+            AccountMeta::new_readonly(vault.key(), true), // mint authority (vault PDA)
+            AccountMeta::new(ctx.accounts.payer.key(), true),
+            AccountMeta::new_readonly(vault.key(), true), // update authority (vault PDA)
+            AccountMeta::new_readonly(ctx.accounts.system_program.key(), false),
+        ];
+
+        let instruction = anchor_lang::solana_program::instruction::Instruction {
+            program_id: ctx.accounts.token_metadata_program.key(),
+            accounts,
+            data,
+        };
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &instruction,
+            &[
+                ctx.accounts.metadata.to_account_info(),
+                ctx.accounts.share_mint.to_account_info(),
+                vault.to_account_info(),
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        msg!("Created metadata for share token: {}", symbol);
+        Ok(())
+    }
+
+    /// Update the share token's name, symbol and URI (authority only)
+    ///
+    /// Token-2022 share mints are updated in place, signed by the vault, with the
+    /// authority paying for any extra rent. Metaplex metadata is updated with
+    /// `UpdateMetadataAccountV2`, signed by the vault as its update authority.
+    /// Metadata created before the vault became its update authority has to be
+    /// handed over to the vault PDA with `hand_over_share_metadata` first.
+    pub fn update_share_metadata(
+        ctx: Context<UpdateShareMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+
+        emit!(ShareMetadataUpdatedEvent {
+            vault: vault.key(),
+            share_mint: vault.share_mint,
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+        });
+
+        if vault.share_token_program == token_2022::ID {
+            let share_mint = ctx.accounts.share_mint.to_account_info();
+            let current = {
+                let data = share_mint.try_borrow_data()?;
+                StateWithExtensions::<MintState>::unpack(&data)?
+                    .get_variable_len_extension::<TokenMetadata>()?
+            };
+            let mut updated = current.clone();
+            updated.update(Field::Name, name.clone());
+            updated.update(Field::Symbol, symbol.clone());
+            updated.update(Field::Uri, uri.clone());
+
+            // Token-2022 resizes the mint but doesn't fund it
+            fund_rent_exemption(
+                &share_mint,
+                share_mint.data_len() - current.tlv_size_of()? + updated.tlv_size_of()?,
+                &ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+
+            let asset_id = vault.asset_id.as_bytes();
+            let seeds = &[
+                b"vault",
+                asset_id,
+                &[vault.bump],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            for (field, value) in [(Field::Name, name), (Field::Symbol, symbol), (Field::Uri, uri)] {
+                token_interface::token_metadata_update_field(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.share_token_program.to_account_info(),
+                        TokenMetadataUpdateField {
+                            program_id: ctx.accounts.share_token_program.to_account_info(),
+                            metadata: share_mint.clone(),
+                            update_authority: vault.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    field,
+                    value,
+                )?;
+            }
+
+            return Ok(());
+        }
+
+        let (Some(metadata), Some(token_metadata_program)) = (
+            &ctx.accounts.metadata,
+            &ctx.accounts.token_metadata_program,
+        ) else {
+            return err!(VaultError::MissingMetadataAccounts);
+        };
+
+        // UpdateMetadataAccountV2 instruction discriminator = 15
+        let mut data = vec![15u8];
+
+        // data: Some(DataV2)
+        data.push(1);
+        data.extend(share_metadata_data_v2(&name, &symbol, &uri));
+
+        // updateAuthority, primarySaleHappened, isMutable: None = 0
+        data.extend_from_slice(&[0, 0, 0]);
+
+        let instruction = anchor_lang::solana_program::instruction::Instruction {
+            program_id: token_metadata_program.key(),
+            accounts: vec![
+                AccountMeta::new(metadata.key(), false),
+                AccountMeta::new_readonly(vault.key(), true), // update authority (vault PDA)
+            ],
+            data,
+        };
+
+        let asset_id = vault.asset_id.as_bytes();
+        let seeds = &[
+            b"vault",
+            asset_id,
+            &[vault.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        anchor_lang::solana_program::program::invoke_signed(
+            &instruction,
+            &[
+                metadata.to_account_info(),
+                vault.to_account_info(),
+            ],
+            signer_seeds,
+        )?;
+
+        Ok(())
+    }
+
+    /// Hand a share token's Metaplex update authority over to the vault PDA
+    ///
+    /// Metadata created before the vault PDA became its update authority is held by
+    /// whoever was the vault authority at the time. They sign `UpdateMetadataAccountV2`
+    /// here to make the vault the update authority, after which `update_share_metadata`
+    /// works for the current vault authority.
+    pub fn hand_over_share_metadata(ctx: Context<HandOverShareMetadata>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+
+        // UpdateMetadataAccountV2 instruction discriminator = 15
+        let mut data = vec![15u8];
+
+        // data: None
+        data.push(0);
+
+        // updateAuthority: Some(vault PDA)
+        data.push(1);
+        data.extend_from_slice(vault.key().as_ref());
+
+        // primarySaleHappened, isMutable: None = 0
+        data.extend_from_slice(&[0, 0]);
+
+        let instruction = anchor_lang::solana_program::instruction::Instruction {
+            program_id: ctx.accounts.token_metadata_program.key(),
+            accounts: vec![
+                AccountMeta::new(ctx.accounts.metadata.key(), false),
+                AccountMeta::new_readonly(ctx.accounts.update_authority.key(), true),
+            ],
+            data,
+        };
+
+        anchor_lang::solana_program::program::invoke(
+            &instruction,
+            &[
+                ctx.accounts.metadata.to_account_info(),
+                ctx.accounts.update_authority.to_account_info(),
+            ],
+        )?;
+
+        msg!("Share metadata update authority handed over to vault {}", vault.key());
+        Ok(())
+    }
+
+    // ========================================================================
+    // Read-only views, returned via return data
+    // ========================================================================
+
+    /// Shares a deposit of `assets` would be issued at the current NAV, the price
+    /// `min_shares_out` is checked against. Queued deposits convert at the roll,
+    /// after premium still to arrive this epoch is added.
+    ///
+    /// Like the other conversion views this values the quote balance at the oracle
+    /// price and mints fees accrued since the last roll first, as the roll does
+    /// (see `Vault::nav`). Oracle accounts are needed once the vault holds quote.
+    pub fn preview_deposit(ctx: Context<VaultView>, assets: u64) -> Result<u64> {
+        let nav = view_nav(ctx.accounts)?;
+        Ok(nav.convert_to_shares(assets).ok_or(VaultError::Overflow)?)
+    }
+
+    /// Underlying and quote paid for redeeming `shares` with `instant_withdraw`, at
+    /// NAV after accrued fees and net of the instant withdrawal fee. The two are
+    /// returned separately and the quote is not valued in underlying.
+    pub fn preview_redeem(ctx: Context<VaultView>, shares: u64) -> Result<RedeemPreview> {
+        let nav = view_nav(ctx.accounts)?;
+        Ok(ctx.accounts.vault.preview_redeem(shares, &nav).ok_or(VaultError::Overflow)?)
+    }
+
+    /// Shares worth `assets` of underlying at NAV after accrued fees
+    pub fn convert_to_shares(ctx: Context<VaultView>, assets: u64) -> Result<u64> {
+        let nav = view_nav(ctx.accounts)?;
+        Ok(nav.convert_to_shares(assets).ok_or(VaultError::Overflow)?)
+    }
+
+    /// Value of `shares` in underlying at NAV after accrued fees, including their
+    /// share of the quote balance
+    pub fn convert_to_assets(ctx: Context<VaultView>, shares: u64) -> Result<u64> {
+        let nav = view_nav(ctx.accounts)?;
+        Ok(nav.convert_to_assets(shares).ok_or(VaultError::Overflow)?)
+    }
+
+    /// Most `user` can deposit right now under the vault and per-wallet caps and the
+    /// allowlist. `u64::MAX` when uncapped.
+    pub fn max_deposit(ctx: Context<MaxDeposit>) -> Result<u64> {
+        let vault = &ctx.accounts.vault;
+        if vault.allowlist_enabled && ctx.accounts.allowlist_entry.is_none() {
+            return Ok(0);
+        }
+
+        let mut max = u64::MAX;
+        if vault.deposit_cap > 0 {
+            let tvl = vault.total_assets.saturating_add(vault.pending_deposits);
+            max = max.min(vault.deposit_cap.saturating_sub(tvl));
+        }
+        if vault.user_deposit_cap > 0 {
+            let principal = ctx.accounts.depositor.as_ref().map_or(0, |depositor| depositor.principal);
+            max = max.min(vault.user_deposit_cap.saturating_sub(principal));
+        }
+
+        Ok(max)
+    }
+
+    /// Most underlying the share account's owner can take out with `instant_withdraw`:
+    /// their shares at NAV after accrued fees and net of the instant withdrawal fee,
+    /// limited to idle assets, or 0 while the epoch awaits settlement. Shares escrowed
+    /// for a pending withdrawal are not counted.
+    pub fn max_withdraw(ctx: Context<MaxWithdraw>) -> Result<u64> {
+        let vault = &ctx.accounts.vault;
+        let now = Clock::get()?.unix_timestamp;
+        if vault.awaiting_settlement(now) {
+            return Ok(0);
+        }
+        let price = quote_price(
+            vault,
+            &ctx.accounts.asset_config,
+            &ctx.accounts.pyth_price_account,
+            &ctx.accounts.oracle_program,
+        )?;
+        let nav = vault.nav(price, now).ok_or(VaultError::Overflow)?;
+        let assets = vault
+            .preview_redeem(ctx.accounts.user_share_account.amount, &nav)
+            .ok_or(VaultError::Overflow)?
+            .assets;
+        Ok(assets.min(vault.idle_assets()))
+    }
+}
+
+// ============================================================================
+// Account Structures
+// ============================================================================
+
+#[account]
+#[derive(InitSpace)]
+pub struct Vault {
+    // Version 0, the original unversioned layout, must not move
+    pub authority: Pubkey,
+    #[max_len(60)]
+    pub asset_id: String,
+    pub underlying_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub vault_token_account: Pubkey,
+    pub total_assets: u64,
+    pub total_shares: u64,
+    pub epoch: u64,
+    pub utilization_cap_bps: u16,
+    pub last_roll_timestamp: i64,
+    pub pending_withdrawals: u64,
+    // Notional-based exposure tracking (fractional options)
+    pub epoch_notional_exposed: u64,       // Total tokens exposed to options this epoch
+    pub epoch_premium_earned: u64,         // Total premium earned this epoch (raw tokens)
+    pub epoch_premium_per_token_bps: u32,  // Average premium rate in basis points
+    pub bump: u8,
+    // Layout version, new fields are appended below with the version that added them
+    // and get defaults in migrate_vault
+    pub version: u8,
+    // Version 1: settlement of options sold this epoch
+    pub epoch_settled: bool,
+    pub epoch_settlement_price: u64,       // Oracle expiry price (6 decimals)
+    pub epoch_payout: u64,                 // Underlying paid to makers for ITM calls
+    // Version 1: premium paid by makers in the quote token (USDC)
+    pub underlying_decimals: u8,
+    pub quote_mint: Pubkey,                // Default until initialize_quote_account
+    pub quote_token_account: Pubkey,
+    pub total_quote_assets: u64,
+    // Version 1: set aside at epoch roll for processed-later withdrawals and refunded deposits,
+    // excluded from total_assets
+    pub reserved_assets: u64,
+    pub reserved_quote_assets: u64,
+    pub instant_withdraw_fee_bps: u16,
+    pub pending_deposits: u64,             // Queued until the epoch rolls, excluded from total_assets
+    // Version 1: fees, accrued at each epoch roll as shares minted to fee_recipient
+    pub fee_recipient: Pubkey,
+    pub management_fee_bps: u16,           // Annual, on total_assets
+    pub performance_fee_bps: u16,          // On price per share gains above the high-water mark
+    pub high_water_mark: u64,              // NAV per share, scaled by SHARE_PRICE_SCALE
+    // Version 1: guarded launch limits, 0 = unlimited
+    pub deposit_cap: u64,                  // Max total_assets + pending_deposits
+    pub user_deposit_cap: u64,             // Max principal a wallet can deposit, see Depositor
+    pub allowlist_enabled: bool,           // Deposits require an AllowlistEntry
+    // Version 1: epoch history, copied into the EpochRecord at the roll
+    pub epoch_min_strike: u64,             // Strike range of the options settled this epoch
+    pub epoch_max_strike: u64,
+    pub epoch_start_price_per_share: u64,  // NAV per share when the epoch opened
+    // Version 1: strategy. Put vaults hold USDC as underlying and assigned xStock in the quote account.
+    pub strategy: VaultStrategy,
+    pub option_mint: Pubkey,               // Options are written on this xStock
+    pub option_decimals: u8,
+    pub oracle_asset_config: Pubkey,       // Oracle config for option_mint, default = match asset_id
+    pub epoch_collateral: u64,             // Underlying backing this epoch's options
+    // Version 1: roles, the authority changes parameters, the keeper only runs epoch operations
+    pub pending_authority: Pubkey,         // Proposed authority, default if none
+    pub keeper: Pubkey,
+    // Version 2
+    pub share_token_program: Pubkey,       // SPL Token, or Token-2022 for shares with on-mint metadata
+    // Version 3
+    pub epoch_settled_notional: u64,       // Notional of this epoch's RFQs settled so far
+    pub open_auctions: u32,                // Auctions started and not yet recorded or closed
+    // Version 4
+    pub epoch_first_expiry: i64,           // Earliest expiry of this epoch's recorded RFQs
+    // Version 5
+    pub auction_collateral: u64,           // Reserved for open auctions until recorded or closed
+    pub locked_collateral_payer: Pubkey,   // Paid this epoch's locked collateral account rent, authority until then
+    // Version 6
+    pub guarded_deposits: u32,             // Receipts queued this epoch with a min_shares_out, checked at the roll
+}
+
+/// Accounts and settings a new vault is created with
+struct VaultInit {
+    authority: Pubkey,
+    asset_id: String,
+    underlying_mint: Pubkey,
+    underlying_decimals: u8,
+    share_mint: Pubkey,
+    share_token_program: Pubkey,
+    vault_token_account: Pubkey,
+    utilization_cap_bps: u16,
+    seed_amount: u64,
+    bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum VaultStrategy {
+    CoveredCall,
+    CashSecuredPut,
+}
+
+// Version 6 layout size, kept in sync with VAULT_LEN in scripts/build-legacy-fixtures.ts
+const _: () = assert!(Vault::LEN == 699);
+
+impl Vault {
+    // asset_id is a PDA seed, so at most 32 bytes fit; max_len keeps the original 64-byte allowance
+    pub const LEN: usize = 8 + Vault::INIT_SPACE;
+
+    /// Set up a freshly created vault holding its seed deposit
+    fn init(&mut self, init: VaultInit) -> Result<()> {
+        self.authority = init.authority;
+        self.asset_id = init.asset_id;
+        self.underlying_mint = init.underlying_mint;
+        self.share_mint = init.share_mint;
+        self.share_token_program = init.share_token_program;
+        self.vault_token_account = init.vault_token_account;
+        self.underlying_decimals = init.underlying_decimals;
+        self.strategy = VaultStrategy::CoveredCall;
+        self.option_mint = init.underlying_mint;
+        self.option_decimals = init.underlying_decimals;
+        self.oracle_asset_config = Pubkey::default();
+        self.quote_mint = Pubkey::default();
+        self.quote_token_account = Pubkey::default();
+        self.total_quote_assets = 0;
+        self.reserved_assets = 0;
+        self.reserved_quote_assets = 0;
+        self.instant_withdraw_fee_bps = 0;
+        self.pending_deposits = 0;
+        self.guarded_deposits = 0;
+        self.pending_authority = Pubkey::default();
+        self.keeper = init.authority;
+        self.locked_collateral_payer = init.authority;
+        self.fee_recipient = init.authority;
+        self.management_fee_bps = 0;
+        self.performance_fee_bps = 0;
+        self.high_water_mark = SHARE_PRICE_SCALE;
+        self.deposit_cap = 0;
+        self.user_deposit_cap = 0;
+        self.allowlist_enabled = false;
+        self.total_assets = init.seed_amount;
+        self.total_shares = init.seed_amount;
+        self.epoch = 0;
+        self.utilization_cap_bps = init.utilization_cap_bps;
+        self.last_roll_timestamp = Clock::get()?.unix_timestamp;
+        self.pending_withdrawals = 0;
+        // Initialize notional exposure tracking
+        self.epoch_notional_exposed = 0;
+        self.epoch_collateral = 0;
+        self.epoch_premium_earned = 0;
+        self.epoch_premium_per_token_bps = 0;
+        self.epoch_settled = false;
+        self.epoch_settlement_price = 0;
+        self.epoch_settled_notional = 0;
+        self.open_auctions = 0;
+        self.auction_collateral = 0;
+        self.epoch_first_expiry = 0;
+        self.epoch_payout = 0;
+        self.epoch_min_strike = 0;
+        self.epoch_max_strike = 0;
+        self.epoch_start_price_per_share = SHARE_PRICE_SCALE;
+        self.bump = init.bump;
+        self.version = CURRENT_VAULT_VERSION;
+        Ok(())
+    }
+
+    /// Authority or keeper, allowed to run epoch operations
+    pub fn is_operator(&self, signer: &Pubkey) -> bool {
+        *signer == self.authority || *signer == self.keeper
+    }
+
+    /// Max notional that can be exposed this epoch: TVL * utilization_cap_bps / 10000
+    pub fn max_exposure(&self) -> u64 {
+        ((self.total_assets as u128) * (self.utilization_cap_bps as u128) / 10000) as u64
+    }
+
+    /// NAV per share in underlying, scaled by SHARE_PRICE_SCALE, with the quote
+    /// balance worth `quote_value` (see `quote_value`)
+    pub fn nav_per_share(&self, quote_value: u64) -> u64 {
+        if self.total_shares == 0 {
+            return SHARE_PRICE_SCALE;
+        }
+        ((self.total_assets as u128 + quote_value as u128) * (SHARE_PRICE_SCALE as u128)
+            / (self.total_shares as u128)) as u64
+    }
+
+    /// Value of the whole quote balance in underlying at `quote_price`, 0 without one
+    pub fn quote_balance_value(&self, quote_price: Option<u64>) -> Option<u64> {
+        match quote_price {
+            Some(price) => self.quote_value(self.total_quote_assets, price),
+            None => Some(0),
+        }
+    }
+
+    /// NAV in underlying, with the quote balance worth `quote_value`
+    pub fn total_value(&self, quote_value: u64) -> Option<u64> {
+        self.total_assets.checked_add(quote_value)
+    }
+
+    /// Management and performance fees accrued since the last roll, in underlying,
+    /// with the quote balance worth `quote_value`. The performance fee only applies
+    /// to the NAV per share gain above the high-water mark.
+    pub fn accrued_fees(&self, quote_value: u64, now: i64) -> Option<(u64, u64)> {
+        let total_value = self.total_value(quote_value)?;
+        let elapsed = if self.last_roll_timestamp > 0 {
+            now.saturating_sub(self.last_roll_timestamp).max(0)
+        } else {
+            0
+        };
+        let management_fee = u64::try_from(
+            (total_value as u128)
+                .checked_mul(self.management_fee_bps as u128)?
+                .checked_mul(elapsed as u128)?
+                / (10000 * SECONDS_PER_YEAR as u128),
+        )
+        .ok()?;
+        let price_per_share = self.nav_per_share(quote_value);
+        let performance_fee = if price_per_share > self.high_water_mark {
+            let gain = ((price_per_share - self.high_water_mark) as u128)
+                .checked_mul(self.total_shares as u128)?
+                / SHARE_PRICE_SCALE as u128;
+            u64::try_from(gain * (self.performance_fee_bps as u128) / 10000).ok()?
+        } else {
+            0
+        };
+        Some((management_fee, performance_fee))
+    }
+
+    /// Shares worth `fee_assets` at the post-fee price: fee * total_shares / (NAV - fee)
+    pub fn fee_shares(&self, total_value: u64, fee_assets: u64) -> Option<u64> {
+        if fee_assets == 0 || self.total_shares == 0 {
+            return Some(0);
+        }
+        let remaining_assets = total_value.checked_sub(fee_assets).filter(|remaining| *remaining > 0)?;
+        u64::try_from(
+            (fee_assets as u128).checked_mul(self.total_shares as u128)? / remaining_assets as u128,
+        )
+        .ok()
+    }
+
+    /// NAV and share supply as queued deposits would convert at a roll now: the quote
+    /// balance at `quote_price` and fees accrued so far minted as shares. Shares escrowed
+    /// for pending withdrawals are still outstanding until the roll, so they stay in
+    /// the supply.
+    pub fn nav(&self, quote_price: Option<u64>, now: i64) -> Option<Nav> {
+        let quote_value = self.quote_balance_value(quote_price)?;
+        let total_value = self.total_value(quote_value)?;
+        let (management_fee, performance_fee) = self.accrued_fees(quote_value, now)?;
+        let fee_shares = self.fee_shares(total_value, management_fee.checked_add(performance_fee)?)?;
+        Some(Nav {
+            total_value,
+            total_shares: self.total_shares.checked_add(fee_shares)?,
+        })
+    }
+
+    /// Payout for redeeming `shares` immediately, net of the instant withdrawal fee.
+    /// The balances are split over `nav`'s share supply, which counts the shares the
+    /// fees accrued so far would mint, so the fees stay behind in the vault.
+    pub fn preview_redeem(&self, shares: u64, nav: &Nav) -> Option<RedeemPreview> {
+        if nav.total_shares == 0 {
+            return Some(RedeemPreview::default());
+        }
+        let gross_assets = u64::try_from(
+            (shares as u128).checked_mul(self.total_assets as u128)? / nav.total_shares as u128,
+        )
+        .ok()?;
+        let gross_quote_assets = u64::try_from(
+            (shares as u128).checked_mul(self.total_quote_assets as u128)? / nav.total_shares as u128,
+        )
+        .ok()?;
+        let fee = self.instant_withdraw_fee(gross_assets);
+        let quote_fee = self.instant_withdraw_fee(gross_quote_assets);
+
+        Some(RedeemPreview {
+            assets: gross_assets - fee,
+            quote_assets: gross_quote_assets - quote_fee,
+            fee,
+            quote_fee,
+        })
+    }
+
+    /// Assets backing this epoch's options, held in the locked collateral account until settled
+    pub fn locked_assets(&self) -> u64 {
+        if self.epoch_settled {
+            0
+        } else {
+            self.epoch_collateral
+        }
+    }
+
+    /// Whether one of this epoch's options has expired and the epoch is not settled yet
+    pub fn awaiting_settlement(&self, now: i64) -> bool {
+        self.epoch_notional_exposed > 0 && !self.epoch_settled && now >= self.epoch_first_expiry
+    }
+
+    /// Assets not committed to live options or reserved for open auctions, held in
+    /// the vault token account
+    pub fn idle_assets(&self) -> u64 {
+        self.total_assets
+            .saturating_sub(self.locked_assets())
+            .saturating_sub(self.auction_collateral)
+    }
+
+    pub fn instant_withdraw_fee(&self, amount: u64) -> u64 {
+        ((amount as u128) * (self.instant_withdraw_fee_bps as u128) / 10000) as u64
+    }
+
+    pub fn has_quote_account(&self) -> bool {
+        self.quote_token_account != Pubkey::default()
+    }
+
+    /// Premium arrives in the quote token for covered call vaults with a quote account;
+    /// put vaults are paid in their USDC underlying
+    pub fn premium_in_quote(&self) -> bool {
+        self.strategy == VaultStrategy::CoveredCall && self.has_quote_account()
+    }
+
+    /// Token account RFQ makers pay this vault's premium into
+    pub fn premium_account(&self) -> Pubkey {
+        if self.premium_in_quote() {
+            self.quote_token_account
+        } else {
+            self.vault_token_account
+        }
+    }
+
+    /// Whether `asset_config` prices this vault's options
+    pub fn uses_asset_config(&self, asset_config: &Account<AssetConfig>) -> bool {
+        if self.oracle_asset_config == Pubkey::default() {
+            asset_config.asset_id == self.asset_id
+        } else {
+            asset_config.key() == self.oracle_asset_config
+        }
+    }
+
+    /// Value of `amount` of the quote token in underlying base units at `price` (6 decimals):
+    /// USDC converted to xStock for covered calls, assigned xStock to USDC for puts
+    pub fn quote_value(&self, amount: u64, price: u64) -> Option<u64> {
+        if price == 0 {
+            return None;
+        }
+        let value = match self.strategy {
+            VaultStrategy::CoveredCall => {
+                (amount as u128).checked_mul(10u128.pow(self.underlying_decimals as u32))? / price as u128
+            }
+            VaultStrategy::CashSecuredPut => {
+                (amount as u128).checked_mul(price as u128)? / 10u128.pow(self.option_decimals as u32)
+            }
+        };
+        u64::try_from(value).ok()
+    }
+
+    /// Underlying backing `notional_tokens` of options: the tokens themselves for
+    /// covered calls, `notional * strike` USDC for puts
+    pub fn collateral_for(&self, notional_tokens: u64, strike: u64) -> Option<u64> {
+        match self.strategy {
+            VaultStrategy::CoveredCall => Some(notional_tokens),
+            VaultStrategy::CashSecuredPut => u64::try_from(
+                (notional_tokens as u128).checked_mul(strike as u128)?
+                    / 10u128.pow(self.option_decimals as u32),
+            )
+            .ok(),
+        }
+    }
+
+    /// Options notional that `collateral` underlying can back at `strike`
+    pub fn notional_for(&self, collateral: u64, strike: u64) -> Option<u64> {
+        match self.strategy {
+            VaultStrategy::CoveredCall => Some(collateral),
+            VaultStrategy::CashSecuredPut => u64::try_from(
+                (collateral as u128).checked_mul(10u128.pow(self.option_decimals as u32))?
+                    .checked_div(strike as u128)?,
+            )
+            .ok(),
+        }
+    }
+}
+
+/// How the vault sells options, enforced by `start_auction`
+///
+/// Strikes are bounded by their distance from spot, not by delta. Delta depends on
+/// implied volatility, which the program has no on-chain source for, so a keeper
+/// targeting a delta converts it to a strike off-chain and must land inside the band.
+#[account]
+pub struct StrategyParams {
+    pub vault: Pubkey,
+    pub min_otm_bps: u16,                  // Out-of-the-money distance from spot
+    pub max_otm_bps: u16,
+    pub min_tenor_secs: i64,               // Time from auction start to expiry
+    pub max_tenor_secs: i64,
+    pub min_premium_bps: u32,              // Lowest premium floor per token an RFQ may set
+    pub rfq_duration_secs: i64,            // Longest an RFQ may accept quotes
+    pub max_notional_per_epoch: u64,       // 0 = utilization cap only
+    pub bump: u8,
+    pub version: u8,
+}
+
+impl StrategyParams {
+    pub const LEN: usize = 8 + 32 + 2 + 2 + 8 + 8 + 4 + 8 + 8 + 1 + 1;
+
+    pub fn apply(&mut self, config: &StrategyConfig) {
+        self.min_otm_bps = config.min_otm_bps;
+        self.max_otm_bps = config.max_otm_bps;
+        self.min_tenor_secs = config.min_tenor_secs;
+        self.max_tenor_secs = config.max_tenor_secs;
+        self.min_premium_bps = config.min_premium_bps;
+        self.rfq_duration_secs = config.rfq_duration_secs;
+        self.max_notional_per_epoch = config.max_notional_per_epoch;
+    }
+}
+
+/// Strategy parameters as set by the authority
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StrategyConfig {
+    pub min_otm_bps: u16,
+    pub max_otm_bps: u16,
+    pub min_tenor_secs: i64,
+    pub max_tenor_secs: i64,
+    pub min_premium_bps: u32,
+    pub rfq_duration_secs: i64,
+    pub max_notional_per_epoch: u64,
+}
+
+impl StrategyConfig {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.max_otm_bps > 0 && self.min_otm_bps <= self.max_otm_bps,
+            VaultError::InvalidStrategyParams
+        );
+        require!(
+            self.min_tenor_secs > 0 && self.min_tenor_secs <= self.max_tenor_secs,
+            VaultError::InvalidStrategyParams
+        );
+        require!(
+            self.rfq_duration_secs > 0 && self.rfq_duration_secs < self.min_tenor_secs,
+            VaultError::InvalidStrategyParams
+        );
+        Ok(())
+    }
+}
+
+/// Result of `preview_redeem`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct RedeemPreview {
+    pub assets: u64,                       // Underlying, net of fee
+    pub quote_assets: u64,                 // Quote token, net of fee
+    pub fee: u64,
+    pub quote_fee: u64,
+}
+
+/// Vault NAV in underlying and the share supply it is spread over, see `Vault::nav`
+#[derive(Clone, Copy)]
+pub struct Nav {
+    pub total_value: u64,
+    pub total_shares: u64,
+}
+
+impl Nav {
+    /// Shares worth `assets` of underlying, 1:1 for an empty vault
+    pub fn convert_to_shares(&self, assets: u64) -> Option<u64> {
+        if self.total_shares == 0 || self.total_value == 0 {
+            return Some(assets);
+        }
+        u64::try_from((assets as u128).checked_mul(self.total_shares as u128)? / self.total_value as u128).ok()
+    }
+
+    /// Underlying value of `shares`, 1:1 for an empty vault
+    pub fn convert_to_assets(&self, shares: u64) -> Option<u64> {
+        if self.total_shares == 0 {
+            return Some(shares);
+        }
+        u64::try_from((shares as u128).checked_mul(self.total_value as u128)? / self.total_shares as u128).ok()
+    }
+}
+
+/// Closing state and history of a vault epoch, written by `advance_epoch`
+#[account]
+pub struct EpochRecord {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub price_per_share: u64,              // Closing NAV per share, scaled by SHARE_PRICE_SCALE
+    pub closing_assets: u64,
+    pub closing_quote_assets: u64,
+    pub closing_shares: u64,
+    pub withdrawal_shares: u64,            // Shares queued for withdrawal at close
+    pub deposit_assets: u64,               // Queued deposits converted at the roll
+    pub deposit_shares: u64,               // Shares issued for them, minted on claim
+    pub bump: u8,
+    // History
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub start_price_per_share: u64,        // Opening NAV per share, scaled by SHARE_PRICE_SCALE
+    pub notional_exposed: u64,
+    pub premium_earned: u64,               // Underlying
+    pub quote_premium_earned: u64,         // Quote token (USDC)
+    pub premium_per_token_bps: u32,
+    pub min_strike: u64,                   // 0 if no options were settled
+    pub max_strike: u64,
+    pub settlement_price: u64,             // 6 decimals, 0 if no options were settled
+    pub payout: u64,                       // Underlying paid to makers
+    pub realized_pnl: i64,                 // Underlying: premium (quote at settlement price) - payout
+    pub version: u8,
+}
+
+impl EpochRecord {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1
+        + 8 + 8 + 8 + 8 + 8 + 8 + 4 + 8 + 8 + 8 + 8 + 8 + 1;
+
+    /// Shares owed for `amount` deposited during this epoch
+    pub fn claimable_shares(&self, amount: u64) -> Option<u64> {
+        if self.deposit_assets == 0 {
+            return Some(0);
+        }
+        u64::try_from((amount as u128) * (self.deposit_shares as u128) / (self.deposit_assets as u128)).ok()
+    }
+
+    /// Underlying owed for `shares` at this epoch's closing price
+    pub fn redeem_assets(&self, shares: u64) -> Option<u64> {
+        if self.closing_shares == 0 {
+            return Some(0);
+        }
+        u64::try_from((shares as u128) * (self.closing_assets as u128) / (self.closing_shares as u128)).ok()
+    }
+
+    /// Quote token owed for `shares` at this epoch's close
+    pub fn redeem_quote_assets(&self, shares: u64) -> Option<u64> {
+        if self.closing_shares == 0 {
+            return Some(0);
+        }
+        u64::try_from((shares as u128) * (self.closing_quote_assets as u128) / (self.closing_shares as u128)).ok()
+    }
+}
+
+#[account]
+pub struct DepositReceipt {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub amount: u64,
+    pub bump: u8,
+    pub version: u8,
+    pub min_shares_out: u64,               // Sum over the queued deposits, refunded below it
+    pub refunded: bool,                    // The roll priced it below min_shares_out
+}
+
+impl DepositReceipt {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 1;
+}
+
+/// Principal a wallet has deposited into a vault, checked against `user_deposit_cap`.
+/// Withdrawals don't reduce it; refunded deposits do.
+#[account]
+pub struct Depositor {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub principal: u64,
+    pub bump: u8,
+    pub version: u8,
+}
+
+impl Depositor {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 1;
+}
+
+/// Marks a wallet as approved to deposit into an allowlisted vault
+#[account]
+pub struct AllowlistEntry {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub bump: u8,
+    pub version: u8,
+}
+
+impl AllowlistEntry {
+    pub const LEN: usize = 8 + 32 + 32 + 1 + 1;
+}
+
+/// An RFQ started by `start_auction` that has not been recorded or closed yet
+#[account]
+pub struct Auction {
+    pub vault: Pubkey,
+    pub rfq: Pubkey,
+    pub epoch: u64,
+    pub payer: Pubkey,                     // Receives the rent back when the auction closes
+    pub bump: u8,
+    pub collateral: u64,                   // Reserved on the vault for the full notional
+}
+
+impl Auction {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 32 + 1 + 8;
+}
+
+/// Marks a filled RFQ as counted in the vault's epoch exposure and tracks its settlement
+#[account]
+pub struct RecordedRfq {
+    pub vault: Pubkey,
+    pub rfq: Pubkey,
+    pub epoch: u64,
+    pub bump: u8,
+    pub version: u8,
+    pub settled: bool,
+    pub settlement_price: u64,             // Pinned at settlement or attested, 0 until then
+    pub payout: u64,                       // Intrinsic value paid to the maker, in underlying
+    pub maker_option_account: Pubkey,      // Put maker's approved xStock account, default for calls
+}
+
+impl RecordedRfq {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 1 + 1 + 8 + 8 + 32;
+}
+
+#[account]
+pub struct WithdrawalRequest {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub shares: u64,
+    pub request_epoch: u64,
+    pub processed: bool, // Only set on legacy requests, which stayed open once processed
+    pub bump: u8,
+    pub version: u8,
+}
+
+impl WithdrawalRequest {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 1 + 1 + 1;
+}
+
+// ============================================================================
+// Contexts
+// ============================================================================
+
+#[derive(Accounts)]
+#[instruction(asset_id: String)]
+pub struct InitializeVault<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Vault::LEN,
+        seeds = [b"vault", asset_id.as_bytes()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub underlying_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        mint::decimals = underlying_mint.decimals,
+        mint::authority = vault,
+        seeds = [b"shares", vault.key().as_ref()],
+        bump
+    )]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = underlying_mint,
+        token::authority = vault,
+        seeds = [b"vault_tokens", vault.key().as_ref()],
+        bump
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// Authority's underlying account funding the seed deposit
+    #[account(
+        mut,
+        token::mint = underlying_mint,
+        token::authority = authority
+    )]
+    pub authority_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(asset_id: String)]
+pub struct InitializeVaultToken2022<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Vault::LEN,
+        seeds = [b"vault", asset_id.as_bytes()],
+        bump
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+
+    pub underlying_mint: Box<Account<'info, Mint>>,
+
+    /// Token-2022 share mint pointing at its own metadata
+    #[account(
+        init,
+        payer = authority,
+        mint::decimals = underlying_mint.decimals,
+        mint::authority = vault,
+        mint::token_program = share_token_program,
+        extensions::metadata_pointer::authority = vault,
+        extensions::metadata_pointer::metadata_address = share_mint,
+        seeds = [b"shares", vault.key().as_ref()],
+        bump
+    )]
+    pub share_mint: Box<InterfaceAccount<'info, token_interface::Mint>>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = underlying_mint,
+        token::authority = vault,
+        seeds = [b"vault_tokens", vault.key().as_ref()],
+        bump
+    )]
+    pub vault_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// Authority's underlying account funding the seed deposit
+    #[account(
+        mut,
+        token::mint = underlying_mint,
+        token::authority = authority
+    )]
+    pub authority_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        address = vault.share_mint
+    )]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        address = vault.vault_token_account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.underlying_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.share_mint,
+        token::authority = user
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// Tracks the user's deposit queued for the next epoch roll
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = DepositReceipt::LEN,
+        seeds = [b"deposit", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub deposit_receipt: Account<'info, DepositReceipt>,
+
+    /// Tracks the principal counted against the per-wallet cap
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Depositor::LEN,
+        seeds = [b"depositor", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub depositor: Account<'info, Depositor>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    /// Required while the vault's allowlist is enabled
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+
+    /// Oracle accounts, required with a nonzero `min_shares_out` once the vault holds quote assets
+    pub asset_config: Option<Account<'info, AssetConfig>>,
+
+    /// CHECK: Pyth price account, validated by the oracle program against asset_config
+    pub pyth_price_account: Option<UncheckedAccount<'info>>,
+
+    pub oracle_program: Option<Program<'info, Oracle>>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimShares<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program,
+        has_one = vault_token_account
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"deposit", vault.key().as_ref(), user.key().as_ref()],
+        bump = deposit_receipt.bump,
+        has_one = user,
+        has_one = vault
+    )]
+    pub deposit_receipt: Account<'info, DepositReceipt>,
+
+    #[account(
+        seeds = [b"epoch_record", vault.key().as_ref(), deposit_receipt.epoch.to_le_bytes().as_ref()],
+        bump = epoch_record.bump
+    )]
+    pub epoch_record: Account<'info, EpochRecord>,
+
+    #[account(
+        mut,
+        address = vault.share_mint
+    )]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        token::mint = vault.share_mint,
+        token::authority = user
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// Pays out a refunded deposit
+    #[account(mut)]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// Receives a refunded deposit
+    #[account(
+        mut,
+        token::mint = vault.underlying_mint,
+        token::authority = user
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// Credited back for a refunded deposit. Absent for deposits queued before it existed
+    #[account(
+        mut,
+        seeds = [b"depositor", vault.key().as_ref(), user.key().as_ref()],
+        bump = depositor.bump
+    )]
+    pub depositor: Option<Account<'info, Depositor>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RequestWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = user,
+        space = WithdrawalRequest::LEN,
+        seeds = [b"withdrawal", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+
+    #[account(address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// Vault-owned escrow for shares queued for withdrawal
+    #[account(
+        init_if_needed,
+        payer = user,
+        token::mint = share_mint,
+        token::authority = vault,
+        token::token_program = share_token_program,
+        seeds = [b"withdrawal_shares", vault.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_shares: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.share_mint,
+        token::authority = user
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct IncreaseWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"withdrawal", vault.key().as_ref(), user.key().as_ref()],
+        bump = withdrawal_request.bump,
+        has_one = user,
+        has_one = vault
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+
+    #[account(address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"withdrawal_shares", vault.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_shares: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.share_mint,
+        token::authority = user
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub share_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"withdrawal", vault.key().as_ref(), user.key().as_ref()],
+        bump = withdrawal_request.bump,
+        has_one = user,
+        has_one = vault
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+
+    #[account(address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"withdrawal_shares", vault.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_shares: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.share_mint
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub share_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"withdrawal", vault.key().as_ref(), user.key().as_ref()],
+        bump = withdrawal_request.bump,
+        has_one = user,
+        has_one = vault
+    )]
+    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+
+    #[account(
+        seeds = [b"epoch_record", vault.key().as_ref(), withdrawal_request.request_epoch.to_le_bytes().as_ref()],
+        bump = epoch_record.bump
+    )]
+    pub epoch_record: Account<'info, EpochRecord>,
+
+    #[account(
+        mut,
+        address = vault.share_mint
+    )]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"withdrawal_shares", vault.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_shares: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        address = vault.vault_token_account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.underlying_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+
+    /// Quote accounts, required once the vault holds quote premium
+    #[account(
+        mut,
+        address = vault.quote_token_account
+    )]
+    pub vault_quote_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = vault.quote_mint
+    )]
+    pub user_quote_account: Option<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct VaultView<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// Oracle accounts, required to value the quote balance once the vault holds quote assets
+    pub asset_config: Option<Account<'info, AssetConfig>>,
+
+    /// CHECK: Pyth price account, validated by the oracle program against asset_config
+    pub pyth_price_account: Option<UncheckedAccount<'info>>,
+
+    pub oracle_program: Option<Program<'info, Oracle>>,
+}
+
+#[derive(Accounts)]
+pub struct MaxDeposit<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Wallet being queried, only used to derive its PDAs
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"depositor", vault.key().as_ref(), user.key().as_ref()],
+        bump = depositor.bump
+    )]
+    pub depositor: Option<Account<'info, Depositor>>,
+
+    #[account(
+        seeds = [b"allowlist", vault.key().as_ref(), user.key().as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+}
+
+#[derive(Accounts)]
+pub struct MaxWithdraw<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(token::mint = vault.share_mint)]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    /// Oracle accounts, required to value the quote balance once the vault holds quote assets
+    pub asset_config: Option<Account<'info, AssetConfig>>,
+
+    /// CHECK: Pyth price account, validated by the oracle program against asset_config
+    pub pyth_price_account: Option<UncheckedAccount<'info>>,
+
+    pub oracle_program: Option<Program<'info, Oracle>>,
+}
+
+#[derive(Accounts)]
+pub struct InstantWithdraw<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        address = vault.share_mint
+    )]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        mut,
+        address = vault.vault_token_account
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.underlying_mint
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.share_mint,
+        token::authority = user
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+
+    /// Quote accounts, required once the vault holds quote premium
+    #[account(
+        mut,
+        address = vault.quote_token_account
+    )]
+    pub vault_quote_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = vault.quote_mint
+    )]
+    pub user_quote_account: Option<Account<'info, TokenAccount>>,
+
+    /// Oracle accounts, required to value the quote balance once the vault holds quote assets
+    pub asset_config: Option<Account<'info, AssetConfig>>,
+
+    /// CHECK: Pyth price account, validated by the oracle program against asset_config
+    pub pyth_price_account: Option<UncheckedAccount<'info>>,
+
+    pub oracle_program: Option<Program<'info, Oracle>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVaultConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    /// CHECK: Deserialized in the handler after the resize, which checks the
+    /// discriminator, authority and PDA
+    #[account(mut, owner = crate::ID)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Checked against vault.underlying_mint in the handler
+    pub underlying_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_token_program
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Deserialized in the handler after the resize, which checks the
+    /// discriminator, user and vault
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"withdrawal", vault.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_request: UncheckedAccount<'info>,
+
+    #[account(address = vault.share_mint)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        token::mint = share_mint,
+        token::authority = vault,
+        token::token_program = share_token_program,
+        seeds = [b"withdrawal_shares", vault.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_shares: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = vault.share_mint,
+        token::authority = user
+    )]
+    pub user_share_account: InterfaceAccount<'info, token_interface::TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        constraint = vault.pending_authority == pending_authority.key() @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStrategy<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = StrategyParams::LEN,
+        seeds = [b"strategy", vault.key().as_ref()],
+        bump
+    )]
+    pub strategy: Account<'info, StrategyParams>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStrategy<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, StrategyParams>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct AddToAllowlist<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AllowlistEntry::LEN,
+        seeds = [b"allowlist", vault.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFromAllowlist<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"allowlist", vault.key().as_ref(), allowlist_entry.user.as_ref()],
+        bump = allowlist_entry.bump
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdvanceEpoch<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        constraint = vault.is_operator(&authority.key()) @ VaultError::Unauthorized,
+        has_one = vault_token_account
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = EpochRecord::LEN,
+        seeds = [b"epoch_record", vault.key().as_ref(), vault.epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub epoch_record: Account<'info, EpochRecord>,
+
+    /// Vault authority or keeper - pays rent for the epoch record
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub vault_token_account: Account<'info, TokenAccount>,
 
-        Ok(())
-    }
+    /// Required once the vault has a quote account
+    #[account(address = vault.quote_token_account)]
+    pub quote_token_account: Option<Account<'info, TokenAccount>>,
 
-    /// Create metadata for the vault share token
-    /// This uses CPI to Metaplex Token Metadata program
-    pub fn create_share_metadata(
-        ctx: Context<CreateShareMetadata>,
-        name: String,
-        symbol: String,
-        uri: String,
-    ) -> Result<()> {
-        let vault = &ctx.accounts.vault;
-        
-        // Build signer seeds for vault PDA
-        let asset_id = vault.asset_id.as_bytes();
-        let seeds = &[
-            b"vault",
-            asset_id,
-            &[vault.bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
+    pub system_program: Program<'info, System>,
 
-        // Create the metadata instruction data
-        // CreateMetadataAccountV3 instruction discriminator = 33
-        let mut data = vec![33u8];
-        
-        // DataV2 struct
-        // name (string with length prefix)
-        data.extend_from_slice(&(name.len() as u32).to_le_bytes());
-        data.extend_from_slice(name.as_bytes());
-        
-        // symbol (string)
-        data.extend_from_slice(&(symbol.len() as u32).to_le_bytes());
-        data.extend_from_slice(symbol.as_bytes());
-        
-        // uri (string)
-        data.extend_from_slice(&(uri.len() as u32).to_le_bytes());
-        data.extend_from_slice(uri.as_bytes());
-        
-        // sellerFeeBasisPoints (u16) = 0
-        data.extend_from_slice(&0u16.to_le_bytes());
-        
-        // creators: None = 0
-        data.push(0);
-        
-        // collection: None = 0
-        data.push(0);
-        
-        // uses: None = 0
-        data.push(0);
-        
-        // isMutable = true
-        data.push(1);
-        
-        // collectionDetails: None = 0
-        data.push(0);
+    /// Oracle accounts, required to value the quote balance once the vault holds quote assets
+    pub asset_config: Option<Account<'info, AssetConfig>>,
 
-        // Build account metas
-        let accounts = vec![
-            AccountMeta::new(ctx.accounts.metadata.key(), false),
-            AccountMeta::new_readonly(ctx.accounts.share_mint.key(), false),
-            AccountMeta::new_readonly(vault.key(), true), // mint authority (vault PDA)
-            AccountMeta::new(ctx.accounts.payer.key(), true),
-            AccountMeta::new_readonly(ctx.accounts.authority.key(), false), // update authority
-            AccountMeta::new_readonly(ctx.accounts.system_program.key(), false),
-        ];
+    /// CHECK: Pyth price account, validated by the oracle program against asset_config
+    pub pyth_price_account: Option<UncheckedAccount<'info>>,
 
-        let instruction = anchor_lang::solana_program::instruction::Instruction {
-            program_id: ctx.accounts.token_metadata_program.key(),
-            accounts,
-            data,
-        };
+    pub oracle_program: Option<Program<'info, Oracle>>,
 
-        anchor_lang::solana_program::program::invoke_signed(
-            &instruction,
-            &[
-                ctx.accounts.metadata.to_account_info(),
-                ctx.accounts.share_mint.to_account_info(),
-                vault.to_account_info(),
-                ctx.accounts.payer.to_account_info(),
-                ctx.accounts.authority.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            signer_seeds,
-        )?;
+    /// Fee accounts, required when fees are due this roll
+    #[account(
+        mut,
+        address = vault.share_mint
+    )]
+    pub share_mint: Option<InterfaceAccount<'info, token_interface::Mint>>,
 
-        msg!("Created metadata for share token: {}", symbol);
-        Ok(())
-    }
+    #[account(
+        mut,
+        token::mint = vault.share_mint,
+        token::authority = vault.fee_recipient
+    )]
+    pub fee_recipient_share_account: Option<InterfaceAccount<'info, token_interface::TokenAccount>>,
+
+    #[account(address = vault.share_token_program)]
+    pub share_token_program: Option<Interface<'info, TokenInterface>>,
 }
 
-// ============================================================================
-// Account Structures
-// ============================================================================
+#[derive(Accounts)]
+pub struct InitializeQuoteAccount<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
 
-#[account]
-pub struct Vault {
-    pub authority: Pubkey,
-    pub asset_id: String,
-    pub underlying_mint: Pubkey,
-    pub share_mint: Pubkey,
-    pub vault_token_account: Pubkey,
-    pub total_assets: u64,
-    pub total_shares: u64,
-    pub epoch: u64,
-    pub utilization_cap_bps: u16,
-    pub last_roll_timestamp: i64,
-    pub pending_withdrawals: u64,
-    // Notional-based exposure tracking (fractional options)
-    pub epoch_notional_exposed: u64,       // Total tokens exposed to options this epoch
-    pub epoch_premium_earned: u64,         // Total premium earned this epoch (raw tokens)
-    pub epoch_premium_per_token_bps: u32,  // Average premium rate in basis points
-    pub bump: u8,
-}
+    #[account(
+        constraint = quote_mint.decimals == PRICE_DECIMALS as u8 @ VaultError::InvalidQuoteMint
+    )]
+    pub quote_mint: Account<'info, Mint>,
 
-#[account]
-pub struct WithdrawalRequest {
-    pub user: Pubkey,
-    pub vault: Pubkey,
-    pub shares: u64,
-    pub request_epoch: u64,
-    pub processed: bool,
-    pub bump: u8,
-}
+    #[account(
+        init,
+        payer = authority,
+        token::mint = quote_mint,
+        token::authority = vault,
+        seeds = [b"vault_quote", vault.key().as_ref()],
+        bump
+    )]
+    pub quote_token_account: Account<'info, TokenAccount>,
 
-// ============================================================================
-// Contexts
-// ============================================================================
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
 
 #[derive(Accounts)]
-#[instruction(asset_id: String)]
-pub struct InitializeVault<'info> {
+pub struct InitializePutStrategy<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// xStock the vault sells puts on
+    #[account(
+        constraint = option_mint.key() != vault.underlying_mint @ VaultError::InvalidStrategy
+    )]
+    pub option_mint: Account<'info, Mint>,
+
+    /// Oracle config pricing option_mint
+    pub asset_config: Account<'info, AssetConfig>,
+
+    /// Holds xStock received when puts are assigned
     #[account(
         init,
         payer = authority,
-        // Space: 8 (discriminator) + 32 (authority) + 64 (asset_id string) + 32 (underlying_mint) 
-        //        + 32 (share_mint) + 32 (vault_token_account) + 8 (total_assets) + 8 (total_shares)
-        //        + 8 (epoch) + 2 (utilization_cap_bps) + 8 (last_roll_timestamp) + 8 (pending_withdrawals)
-        //        + 8 (epoch_notional_exposed) + 8 (epoch_premium_earned) + 4 (epoch_premium_per_token_bps)
-        //        + 1 (bump)
-        space = 8 + 32 + 64 + 32 + 32 + 32 + 8 + 8 + 8 + 2 + 8 + 8 + 8 + 8 + 4 + 1,
-        seeds = [b"vault", asset_id.as_bytes()],
+        token::mint = option_mint,
+        token::authority = vault,
+        seeds = [b"vault_quote", vault.key().as_ref()],
         bump
     )]
+    pub quote_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RecordNotionalExposure<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        constraint = vault.is_operator(&authority.key()) @ VaultError::Unauthorized,
+        has_one = underlying_mint,
+        has_one = vault_token_account
+    )]
     pub vault: Account<'info, Vault>,
 
-    pub underlying_mint: Account<'info, Mint>,
+    /// Filled RFQ created by the vault's `start_auction`, checked in the handler
+    pub rfq: Account<'info, rfq::Rfq>,
+
+    /// Only auctions started this epoch can be recorded, once
+    #[account(
+        mut,
+        seeds = [b"auction", vault.key().as_ref(), rfq.key().as_ref()],
+        bump = auction.bump,
+        constraint = auction.epoch == vault.epoch @ VaultError::InvalidRfq,
+        close = auction_payer
+    )]
+    pub auction: Account<'info, Auction>,
 
+    /// CHECK: Paid the auction account's rent and gets it back
+    #[account(mut, address = auction.payer)]
+    pub auction_payer: UncheckedAccount<'info>,
+
+    /// Fails to initialize if the RFQ was already recorded
     #[account(
         init,
         payer = authority,
-        mint::decimals = underlying_mint.decimals,
-        mint::authority = vault,
-        seeds = [b"shares", vault.key().as_ref()],
+        space = RecordedRfq::LEN,
+        seeds = [b"recorded_rfq", vault.key().as_ref(), rfq.key().as_ref()],
         bump
     )]
-    pub share_mint: Account<'info, Mint>,
+    pub recorded_rfq: Account<'info, RecordedRfq>,
+
+    pub underlying_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub vault_token_account: Account<'info, TokenAccount>,
 
+    /// Holds the collateral backing this epoch's options until settlement
     #[account(
-        init,
+        init_if_needed,
         payer = authority,
         token::mint = underlying_mint,
         token::authority = vault,
-        seeds = [b"vault_tokens", vault.key().as_ref()],
+        seeds = [b"locked_collateral", vault.key().as_ref(), vault.epoch.to_le_bytes().as_ref()],
         bump
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub locked_collateral_account: Account<'info, TokenAccount>,
+
+    /// The maker's associated xStock account, required for puts: it must approve the
+    /// vault as delegate for the notional so the put can be assigned at settlement
+    #[account(
+        address = get_associated_token_address(&rfq.filled_by, &vault.option_mint) @ VaultError::InvalidMakerAccount
+    )]
+    pub maker_option_account: Option<Account<'info, TokenAccount>>,
 
+    /// Vault authority or keeper
     #[account(mut)]
     pub authority: Signer<'info>,
 
-    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Deposit<'info> {
+pub struct StartAuction<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        constraint = vault.is_operator(&authority.key()) @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"strategy", vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, StrategyParams>,
+
+    /// Vault authority or keeper - pays rent for the RFQ account
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        constraint = vault.uses_asset_config(&asset_config) @ VaultError::InvalidOracleAsset
+    )]
+    pub asset_config: Account<'info, AssetConfig>,
+
+    /// CHECK: Pyth price account, validated by the oracle program against asset_config
+    pub pyth_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Receives the premium, the rfq program checks the vault owns it
+    #[account(address = vault.premium_account() @ VaultError::InvalidPremiumAccount)]
+    pub premium_account: UncheckedAccount<'info>,
+
+    /// CHECK: RFQ config, validated by the rfq program
+    #[account(mut)]
+    pub rfq_config: UncheckedAccount<'info>,
+
+    /// CHECK: RFQ account, created by the rfq program
+    #[account(mut)]
+    pub rfq: UncheckedAccount<'info>,
+
+    /// Tracks the RFQ until its fill is recorded or it is closed unfilled
+    #[account(
+        init,
+        payer = authority,
+        space = Auction::LEN,
+        seeds = [b"auction", vault.key().as_ref(), rfq.key().as_ref()],
+        bump
+    )]
+    pub auction: Account<'info, Auction>,
+
+    pub rfq_program: Program<'info, Rfq>,
+    pub oracle_program: Program<'info, Oracle>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseAuction<'info> {
     #[account(
         mut,
         seeds = [b"vault", vault.asset_id.as_bytes()],
@@ -484,38 +4136,92 @@ pub struct Deposit<'info> {
     )]
     pub vault: Account<'info, Vault>,
 
+    pub rfq: Account<'info, rfq::Rfq>,
+
     #[account(
         mut,
-        address = vault.share_mint
+        seeds = [b"auction", vault.key().as_ref(), rfq.key().as_ref()],
+        bump = auction.bump,
+        close = auction_payer
     )]
-    pub share_mint: Account<'info, Mint>,
+    pub auction: Account<'info, Auction>,
+
+    /// CHECK: Paid the auction account's rent and gets it back
+    #[account(mut, address = auction.payer)]
+    pub auction_payer: UncheckedAccount<'info>,
+
+    /// Holds the premium of a fill that expired unrecorded, required to refund it
+    #[account(
+        mut,
+        address = vault.premium_account() @ VaultError::InvalidPremiumAccount
+    )]
+    pub premium_account: Option<Account<'info, TokenAccount>>,
+
+    /// Maker's account in the premium mint, receives the refund
+    #[account(
+        mut,
+        constraint = maker_premium_account.owner == rfq.filled_by @ VaultError::InvalidMakerAccount,
+        constraint = maker_premium_account.mint == rfq.premium_mint @ VaultError::InvalidMakerAccount
+    )]
+    pub maker_premium_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RejectFill<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        constraint = vault.is_operator(&authority.key()) @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub rfq: Account<'info, rfq::Rfq>,
+
+    #[account(
+        mut,
+        seeds = [b"auction", vault.key().as_ref(), rfq.key().as_ref()],
+        bump = auction.bump,
+        close = auction_payer
+    )]
+    pub auction: Account<'info, Auction>,
 
+    /// CHECK: Paid the auction account's rent and gets it back
+    #[account(mut, address = auction.payer)]
+    pub auction_payer: UncheckedAccount<'info>,
+
+    /// The maker's associated xStock account, the only one `record_notional_exposure`
+    /// accepts the approval from, so the rejection can't be pointed at another account
     #[account(
-        mut,
-        address = vault.vault_token_account
+        address = get_associated_token_address(&rfq.filled_by, &vault.option_mint) @ VaultError::InvalidMakerAccount
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub maker_option_account: Account<'info, TokenAccount>,
 
+    /// Holds the fill's premium
     #[account(
         mut,
-        token::mint = vault.underlying_mint
+        address = vault.premium_account() @ VaultError::InvalidPremiumAccount
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub premium_account: Account<'info, TokenAccount>,
 
+    /// Maker's account in the premium mint, receives the refund
     #[account(
         mut,
-        token::mint = vault.share_mint
+        constraint = maker_premium_account.owner == rfq.filled_by @ VaultError::InvalidMakerAccount,
+        constraint = maker_premium_account.mint == rfq.premium_mint @ VaultError::InvalidMakerAccount
     )]
-    pub user_share_account: Account<'info, TokenAccount>,
+    pub maker_premium_account: Account<'info, TokenAccount>,
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    /// Vault authority or keeper
+    pub authority: Signer<'info>,
 
     pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct RequestWithdrawal<'info> {
+pub struct SettleRfq<'info> {
     #[account(
         mut,
         seeds = [b"vault", vault.asset_id.as_bytes()],
@@ -523,99 +4229,118 @@ pub struct RequestWithdrawal<'info> {
     )]
     pub vault: Account<'info, Vault>,
 
-    #[account(
-        init,
-        payer = user,
-        space = 8 + 32 + 32 + 8 + 8 + 1 + 1,
-        seeds = [b"withdrawal", vault.key().as_ref(), user.key().as_ref()],
-        bump
-    )]
-    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+    pub rfq: Account<'info, rfq::Rfq>,
 
+    /// Only RFQs recorded this epoch can be settled
     #[account(
-        token::mint = vault.share_mint,
-        token::authority = user
+        mut,
+        seeds = [b"recorded_rfq", vault.key().as_ref(), rfq.key().as_ref()],
+        bump = recorded_rfq.bump,
+        constraint = recorded_rfq.epoch == vault.epoch @ VaultError::InvalidRfq
     )]
-    pub user_share_account: Account<'info, TokenAccount>,
-
-    #[account(mut)]
-    pub user: Signer<'info>,
+    pub recorded_rfq: Account<'info, RecordedRfq>,
 
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct ProcessWithdrawal<'info> {
+    /// This epoch's locked collateral, pays the maker
     #[account(
         mut,
-        seeds = [b"vault", vault.asset_id.as_bytes()],
-        bump = vault.bump
+        seeds = [b"locked_collateral", vault.key().as_ref(), vault.epoch.to_le_bytes().as_ref()],
+        bump
     )]
-    pub vault: Account<'info, Vault>,
+    pub locked_collateral_account: Account<'info, TokenAccount>,
 
+    /// Receives the call payout or the put strike
     #[account(
         mut,
-        seeds = [b"withdrawal", vault.key().as_ref(), user.key().as_ref()],
-        bump = withdrawal_request.bump,
-        has_one = user,
-        has_one = vault
+        constraint = maker_token_account.owner == rfq.filled_by @ VaultError::InvalidMakerAccount,
+        constraint = maker_token_account.mint == vault.underlying_mint @ VaultError::InvalidMakerAccount
     )]
-    pub withdrawal_request: Account<'info, WithdrawalRequest>,
+    pub maker_token_account: Account<'info, TokenAccount>,
 
+    /// Delivers the xStock of an assigned put, required for puts that finished in the money.
+    /// Must be the account whose approval was checked when the put was recorded.
     #[account(
         mut,
-        address = vault.share_mint
+        address = recorded_rfq.maker_option_account @ VaultError::InvalidMakerAccount
     )]
-    pub share_mint: Account<'info, Mint>,
+    pub maker_option_account: Option<Account<'info, TokenAccount>>,
 
+    /// Receives assigned xStock, required when a put vault settles an assigned put
     #[account(
         mut,
-        address = vault.vault_token_account
+        address = vault.quote_token_account
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub quote_token_account: Option<Account<'info, TokenAccount>>,
 
     #[account(
-        mut,
-        token::mint = vault.underlying_mint
+        constraint = vault.uses_asset_config(&asset_config) @ VaultError::InvalidOracleAsset
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub asset_config: Account<'info, AssetConfig>,
 
     #[account(
-        mut,
-        token::mint = vault.share_mint
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump = price_history.bump,
+        seeds::program = oracle_program.key()
     )]
-    pub user_share_account: Account<'info, TokenAccount>,
-
-    #[account(mut)]
-    pub user: Signer<'info>,
+    pub price_history: Box<Account<'info, PriceHistory>>,
 
+    pub oracle_program: Program<'info, Oracle>,
     pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct AdvanceEpoch<'info> {
+pub struct AttestSettlementPrice<'info> {
     #[account(
-        mut,
         seeds = [b"vault", vault.asset_id.as_bytes()],
         bump = vault.bump,
         has_one = authority
     )]
     pub vault: Account<'info, Vault>,
 
+    pub rfq: Account<'info, rfq::Rfq>,
+
+    #[account(
+        mut,
+        seeds = [b"recorded_rfq", vault.key().as_ref(), rfq.key().as_ref()],
+        bump = recorded_rfq.bump,
+        constraint = recorded_rfq.epoch == vault.epoch @ VaultError::InvalidRfq
+    )]
+    pub recorded_rfq: Account<'info, RecordedRfq>,
+
     pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct RecordNotionalExposure<'info> {
+pub struct SettleEpoch<'info> {
     #[account(
         mut,
         seeds = [b"vault", vault.asset_id.as_bytes()],
         bump = vault.bump,
-        has_one = authority
+        constraint = vault.is_operator(&authority.key()) @ VaultError::Unauthorized,
+        has_one = vault_token_account
     )]
     pub vault: Account<'info, Vault>,
 
+    /// Vault authority or keeper
     pub authority: Signer<'info>,
+
+    /// CHECK: Paid the locked collateral account's rent and gets it back.
+    /// Required together with the locked collateral account.
+    #[account(mut, address = vault.locked_collateral_payer @ VaultError::Unauthorized)]
+    pub locked_collateral_payer: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    /// This epoch's locked collateral, released and closed.
+    /// Required whenever there is something to settle.
+    #[account(
+        mut,
+        seeds = [b"locked_collateral", vault.key().as_ref(), vault.epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub locked_collateral_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
@@ -635,7 +4360,7 @@ pub struct CreateShareMetadata<'info> {
     #[account(mut)]
     pub metadata: UncheckedAccount<'info>,
 
-    /// Vault authority - will become the update authority for metadata
+    /// Vault authority. The vault PDA becomes the metadata's update authority.
     pub authority: Signer<'info>,
 
     /// Payer for the metadata account
@@ -649,6 +4374,69 @@ pub struct CreateShareMetadata<'info> {
     pub token_metadata_program: UncheckedAccount<'info>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateShareMetadata<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = authority,
+        has_one = share_mint,
+        has_one = share_token_program,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut)]
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// Vault authority. Pays rent when Token-2022 metadata grows.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub share_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: Metaplex metadata PDA of the share mint, required for SPL Token share mints
+    #[account(
+        mut,
+        seeds = [b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), share_mint.key().as_ref()],
+        seeds::program = TOKEN_METADATA_PROGRAM_ID,
+        bump
+    )]
+    pub metadata: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Metaplex Token Metadata Program
+    #[account(address = TOKEN_METADATA_PROGRAM_ID)]
+    pub token_metadata_program: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct HandOverShareMetadata<'info> {
+    #[account(
+        seeds = [b"vault", vault.asset_id.as_bytes()],
+        bump = vault.bump,
+        has_one = share_mint,
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub share_mint: InterfaceAccount<'info, token_interface::Mint>,
+
+    /// CHECK: Metaplex metadata PDA of the share mint
+    #[account(
+        mut,
+        seeds = [b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), share_mint.key().as_ref()],
+        seeds::program = TOKEN_METADATA_PROGRAM_ID,
+        bump
+    )]
+    pub metadata: UncheckedAccount<'info>,
+
+    /// Current Metaplex update authority, checked by the metadata program
+    pub update_authority: Signer<'info>,
+
+    /// CHECK: Metaplex Token Metadata Program
+    #[account(address = TOKEN_METADATA_PROGRAM_ID)]
+    pub token_metadata_program: UncheckedAccount<'info>,
+}
+
 // ============================================================================
 // Events
 // ============================================================================
@@ -662,6 +4450,42 @@ pub struct DepositEvent {
     pub epoch: u64,
 }
 
+#[event]
+pub struct DepositQueuedEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub total_queued: u64,
+    pub epoch: u64,
+}
+
+#[event]
+pub struct DepositRefundedEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub min_shares_out: u64,
+    pub deposit_epoch: u64,
+}
+
+#[event]
+pub struct RefundClaimedEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub deposit_epoch: u64,
+}
+
+#[event]
+pub struct SharesClaimedEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub deposit_epoch: u64,
+}
+
 #[event]
 pub struct WithdrawalRequestedEvent {
     pub vault: Pubkey,
@@ -670,13 +4494,126 @@ pub struct WithdrawalRequestedEvent {
     pub request_epoch: u64,
 }
 
+#[event]
+pub struct WithdrawalIncreasedEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub shares: u64,
+    pub total_shares: u64,
+    pub request_epoch: u64,
+}
+
+#[event]
+pub struct WithdrawalCancelledEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub shares: u64,
+    pub request_epoch: u64,
+}
+
 #[event]
 pub struct WithdrawalProcessedEvent {
     pub vault: Pubkey,
     pub user: Pubkey,
     pub shares: u64,
     pub amount: u64,
+    pub quote_amount: u64,
+    pub epoch: u64,
+}
+
+#[event]
+pub struct InstantWithdrawEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+    pub quote_amount: u64,
+    pub fee: u64,
+    pub quote_fee: u64,
+    pub epoch: u64,
+}
+
+#[event]
+pub struct InstantWithdrawFeeUpdatedEvent {
+    pub vault: Pubkey,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct AuthorityProposedEvent {
+    pub vault: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityTransferredEvent {
+    pub vault: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct VaultMigratedEvent {
+    pub vault: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct WithdrawalMigratedEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub shares: u64,                       // Escrowed, 0 if the request was closed
+    pub request_epoch: u64,
+}
+
+#[event]
+pub struct ShareMetadataUpdatedEvent {
+    pub vault: Pubkey,
+    pub share_mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+#[event]
+pub struct KeeperUpdatedEvent {
+    pub vault: Pubkey,
+    pub keeper: Pubkey,
+}
+
+#[event]
+pub struct DepositLimitsUpdatedEvent {
+    pub vault: Pubkey,
+    pub deposit_cap: u64,
+    pub user_deposit_cap: u64,
+    pub allowlist_enabled: bool,
+}
+
+#[event]
+pub struct AllowlistUpdatedEvent {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub allowed: bool,
+}
+
+#[event]
+pub struct FeesUpdatedEvent {
+    pub vault: Pubkey,
+    pub management_fee_bps: u16,
+    pub performance_fee_bps: u16,
+    pub fee_recipient: Pubkey,
+}
+
+#[event]
+pub struct FeesAccruedEvent {
+    pub vault: Pubkey,
     pub epoch: u64,
+    pub management_fee: u64,
+    pub performance_fee: u64,
+    pub fee_shares: u64,
+    pub high_water_mark: u64,
 }
 
 #[event]
@@ -684,9 +4621,11 @@ pub struct EpochAdvancedEvent {
     pub vault: Pubkey,
     pub new_epoch: u64,
     pub premium_earned: u64,
+    pub quote_premium_earned: u64,
     pub notional_exposed: u64,
     pub avg_premium_bps: u32,
     pub total_assets: u64,
+    pub total_quote_assets: u64,
     pub total_shares: u64,
 }
 
@@ -694,13 +4633,87 @@ pub struct EpochAdvancedEvent {
 pub struct NotionalExposureEvent {
     pub vault: Pubkey,
     pub epoch: u64,
+    pub rfq: Pubkey,
     pub notional_tokens: u64,
     pub premium: u64,
+    pub collateral: u64,                   // Underlying moved into the locked collateral account
     pub total_notional_this_epoch: u64,
     pub total_premium_this_epoch: u64,
     pub avg_premium_bps: u32,
 }
 
+#[event]
+pub struct PutStrategyInitializedEvent {
+    pub vault: Pubkey,
+    pub option_mint: Pubkey,
+    pub asset_config: Pubkey,
+    pub assigned_token_account: Pubkey,
+}
+
+#[event]
+pub struct StrategyUpdatedEvent {
+    pub vault: Pubkey,
+    pub config: StrategyConfig,
+}
+
+#[event]
+pub struct AuctionStartedEvent {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub rfq: Pubkey,
+    pub strike: u64,
+    pub notional_tokens: u64,
+    pub premium_floor_per_token_bps: u32,
+    pub oracle_price: u64,
+    pub expiry_ts: i64,
+    pub valid_until_ts: i64,
+}
+
+#[event]
+pub struct AuctionClosedEvent {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub rfq: Pubkey,
+    pub premium_refunded: u64,             // Paid back to the maker of a fill that expired unrecorded
+}
+
+#[event]
+pub struct QuoteAccountInitializedEvent {
+    pub vault: Pubkey,
+    pub quote_mint: Pubkey,
+    pub quote_token_account: Pubkey,
+}
+
+#[event]
+pub struct RfqSettledEvent {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub rfq: Pubkey,
+    pub settlement_price: u64,
+    pub notional_tokens: u64,
+    pub payout: u64,                       // Intrinsic value if ITM, in underlying
+    pub assigned: u64,                     // xStock taken on an assigned put
+}
+
+#[event]
+pub struct SettlementPriceAttestedEvent {
+    pub vault: Pubkey,
+    pub rfq: Pubkey,
+    pub price: u64,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct EpochSettledEvent {
+    pub vault: Pubkey,
+    pub epoch: u64,
+    pub settlement_price: u64,             // Price of the last RFQ settled
+    pub notional_settled: u64,
+    pub payout: u64,                       // Intrinsic value of ITM options, in underlying
+    pub collateral_released: u64,          // Returned from the locked collateral account
+    pub total_assets: u64,
+}
+
 // ============================================================================
 // Errors
 // ============================================================================
@@ -721,4 +4734,110 @@ pub enum VaultError {
     Overflow,
     #[msg("Exceeds utilization cap")]
     ExceedsUtilizationCap,
+    #[msg("Oracle asset does not match the vault")]
+    InvalidOracleAsset,
+    #[msg("Oracle status is not OK")]
+    OracleNotOk,
+    #[msg("Invalid oracle price")]
+    InvalidOraclePrice,
+    #[msg("Strike must be out of the money and within the strategy's OTM range")]
+    InvalidStrike,
+    #[msg("Epoch already settled")]
+    EpochAlreadySettled,
+    #[msg("No options to settle this epoch")]
+    NothingToSettle,
+    #[msg("Maker token account is not the RFQ filler's, is in the wrong mint or is not the one pinned at record time")]
+    InvalidMakerAccount,
+    #[msg("RFQ does not belong to this vault's current epoch")]
+    InvalidRfq,
+    #[msg("Options have not expired yet")]
+    OptionsNotExpired,
+    #[msg("Vault token balance is below total assets")]
+    UnbackedAssets,
+    #[msg("Premium recorded this epoch has not been received")]
+    UnbackedPremium,
+    #[msg("Quote mint must use 6 decimals")]
+    InvalidQuoteMint,
+    #[msg("Quote account already initialized")]
+    QuoteAccountAlreadyInitialized,
+    #[msg("Quote token accounts are required once the vault holds quote premium")]
+    MissingQuoteAccounts,
+    #[msg("Oracle accounts are required to value the vault's quote premium")]
+    MissingOracleAccounts,
+    #[msg("Withdrawal request can only be changed in the epoch it was filed")]
+    WithdrawalEpochPassed,
+    #[msg("Not enough idle assets outside live option collateral")]
+    InsufficientIdleAssets,
+    #[msg("Fee exceeds the maximum")]
+    InvalidFee,
+    #[msg("Claim the shares from an earlier deposit first")]
+    UnclaimedDeposit,
+    #[msg("Seed deposit is below the minimum")]
+    SeedTooSmall,
+    #[msg("Deposit would receive fewer shares than min_shares_out")]
+    SlippageExceeded,
+    #[msg("Share mint and fee recipient accounts are required to pay fees")]
+    MissingFeeAccounts,
+    #[msg("Deposit would exceed the vault deposit cap")]
+    DepositCapExceeded,
+    #[msg("Deposit would exceed the per-wallet deposit cap")]
+    UserDepositCapExceeded,
+    #[msg("Wallet is not on the vault allowlist")]
+    NotAllowlisted,
+    #[msg("Invalid strategy parameters")]
+    InvalidStrategyParams,
+    #[msg("Option tenor is outside the strategy's range")]
+    InvalidTenor,
+    #[msg("RFQ window exceeds the strategy's RFQ duration")]
+    InvalidAuctionWindow,
+    #[msg("Premium floor is below the strategy minimum")]
+    PremiumFloorTooLow,
+    #[msg("Instruction does not apply to this vault's strategy")]
+    InvalidStrategy,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Vault is already at the current version")]
+    VaultAlreadyMigrated,
+    #[msg("Vault must be migrated to the current version first")]
+    VaultNotMigrated,
+    #[msg("Withdrawal request is already at the current version")]
+    WithdrawalAlreadyMigrated,
+    #[msg("Mint does not match the vault")]
+    InvalidMint,
+    #[msg("RFQ has not been filled")]
+    RfqNotFilled,
+    #[msg("Locked collateral account is required to settle")]
+    MissingCollateralAccount,
+    #[msg("Metaplex metadata accounts are required for SPL Token share mints")]
+    MissingMetadataAccounts,
+    #[msg("RFQ already settled")]
+    RfqAlreadySettled,
+    #[msg("Every RFQ recorded this epoch must be settled first")]
+    RfqsNotSettled,
+    #[msg("The oracle settlement grace period has not passed")]
+    SettlementGracePeriod,
+    #[msg("Premium account is not the vault's premium account")]
+    InvalidPremiumAccount,
+    #[msg("Every auction this epoch must be recorded or closed first")]
+    AuctionsOpen,
+    #[msg("Auction can still be filled, or is filled and must be recorded")]
+    AuctionStillOpen,
+    #[msg("An option has expired and the epoch is not settled yet")]
+    AwaitingSettlement,
+    #[msg("Put maker has not approved the vault to take delivery of the notional")]
+    MakerNotApproved,
+    #[msg("Option has expired")]
+    OptionExpired,
+    #[msg("Premium and maker accounts are required to refund a fill")]
+    MissingRefundAccounts,
+    #[msg("Put maker has approved delivery, record the fill instead")]
+    MakerApproved,
+    #[msg("Queued deposits must convert at the next roll first")]
+    DepositsQueued,
+    #[msg("Too many deposits with a minimum are queued this epoch")]
+    TooManyGuardedDeposits,
+    #[msg("Every deposit queued this epoch with a minimum must be passed to the roll")]
+    InvalidDepositReceipts,
+    #[msg("Puts need the maker's associated xStock account")]
+    MissingMakerAccount,
 }
@@ -1,8 +1,28 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
 use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use oracle::program::Oracle;
+use oracle::{AssetConfig, AssetMint, OracleStatus, PriceHistory};
+use vault::program::Vault as VaultProgram;
 
 declare_id!("9SPZCWiT2xcYA7DDZTpSbhGFdDugSr9VqeQ7PVFBFgN");
 
+// Barriers, and the strikes of binary and Asian options, are per-unit prices with 6
+// decimals, compared with the oracle price scaled to match. A physically settled
+// call's strike is the total quote paid on exercise.
+pub const PRICE_DECIMALS: u32 = 6;
+
+// If cash-settled options are still unsettled this long after expiry, the seller can
+// reclaim the escrow
+pub const SETTLEMENT_GRACE_SECS: i64 = 24 * 60 * 60;
+
+// Royalties on secondary sales are capped at 10% of the sale price
+pub const MAX_ROYALTY_BPS: u16 = 1_000;
+
+// Longest averaging window the oracle price history can cover
+pub const MAX_AVERAGING_WINDOW_SECS: i64 =
+    oracle::MAX_OBSERVATIONS as i64 * oracle::OBSERVATION_SLOT_SECS;
+
 /// xStock Options Program
 /// 
 /// A Solana program for trading American-style covered call options on xStock synthetic equities.
@@ -14,6 +34,9 @@ declare_id!("9SPZCWiT2xcYA7DDZTpSbhGFdDugSr9VqeQ7PVFBFgN");
 pub mod xstock_options {
     use super::*;
 
+    /// Create a covered call
+    ///
+    /// `royalty_bps` is the share of every secondary sale premium paid back to the seller.
     pub fn create_covered_call(
         ctx: Context<CreateCoveredCall>,
         uid: u64,
@@ -21,31 +44,363 @@ pub mod xstock_options {
         premium: u64,
         expiry_ts: i64,
         amount: u64,
+        royalty_bps: u16,
     ) -> Result<()> {
+        require!(royalty_bps <= MAX_ROYALTY_BPS, ErrorCode::InvalidRoyalty);
+
         let covered_call = &mut ctx.accounts.covered_call;
-        covered_call.seller = ctx.accounts.seller.key();
-        covered_call.xstock_mint = ctx.accounts.xstock_mint.key();
-        covered_call.quote_mint = ctx.accounts.quote_mint.key();
-        covered_call.uid = uid;
-        covered_call.strike = strike;
-        covered_call.premium = premium;
-        covered_call.expiry_ts = expiry_ts;
-        covered_call.amount = amount;
-        covered_call.exercised = false;
-        covered_call.cancelled = false;
-        covered_call.buyer = None;
-        covered_call.buyer_exercised = false;
-        covered_call.is_listed = true;
-        covered_call.ask_price = premium;
+        covered_call.init(
+            ctx.accounts.seller.key(),
+            ctx.accounts.xstock_mint.key(),
+            ctx.accounts.quote_mint.key(),
+            uid,
+            strike,
+            premium,
+            expiry_ts,
+            amount,
+        );
+        covered_call.royalty_bps = royalty_bps;
+
+        escrow(
+            &ctx.accounts.seller_xstock_account,
+            &ctx.accounts.vault_account,
+            &ctx.accounts.seller,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Create a barrier covered call (up-and-out or down-and-in)
+    ///
+    /// The barrier is monitored with the oracle asset passed here and is triggered
+    /// through `trigger_barrier`. An up-and-out call dies once triggered, while a
+    /// down-and-in call can only be exercised after it has been triggered.
+    pub fn create_barrier_call(
+        ctx: Context<CreateOracleCall>,
+        uid: u64,
+        strike: u64,
+        premium: u64,
+        expiry_ts: i64,
+        amount: u64,
+        barrier: BarrierParams,
+    ) -> Result<()> {
+        require!(barrier.barrier_type != BarrierType::None, ErrorCode::InvalidBarrier);
+        require!(barrier.level > 0, ErrorCode::InvalidBarrier);
+
+        let covered_call = &mut ctx.accounts.covered_call;
+        covered_call.init(
+            ctx.accounts.seller.key(),
+            ctx.accounts.xstock_mint.key(),
+            ctx.accounts.quote_mint.key(),
+            uid,
+            strike,
+            premium,
+            expiry_ts,
+            amount,
+        );
+        covered_call.barrier_type = barrier.barrier_type;
+        covered_call.barrier = barrier.level;
+        covered_call.oracle_asset = ctx.accounts.asset_config.key();
+
+        escrow(
+            &ctx.accounts.seller_xstock_account,
+            &ctx.accounts.vault_account,
+            &ctx.accounts.seller,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Create a cash-settled binary (digital) option
+    ///
+    /// The seller escrows `payout` quote tokens, which go to the buyer if the oracle
+    /// price at expiry is above (`DigitalCall`) or below (`DigitalPut`) the strike and
+    /// back to the seller otherwise. The option is listed and bought like a covered call,
+    /// with `ask_price` quoted per 1.0 of payout.
+    pub fn create_binary_option(
+        ctx: Context<CreateBinaryOption>,
+        uid: u64,
+        strike: u64,
+        premium: u64,
+        expiry_ts: i64,
+        payout: u64,
+        payoff: Payoff,
+    ) -> Result<()> {
+        require!(payoff != Payoff::Physical, ErrorCode::InvalidPayoff);
+
+        let covered_call = &mut ctx.accounts.covered_call;
+        covered_call.init(
+            ctx.accounts.seller.key(),
+            ctx.accounts.xstock_mint.key(),
+            ctx.accounts.quote_mint.key(),
+            uid,
+            strike,
+            premium,
+            expiry_ts,
+            payout,
+        );
+        covered_call.oracle_asset = ctx.accounts.asset_config.key();
+        covered_call.payoff = payoff;
+
+        escrow(
+            &ctx.accounts.seller_quote_account,
+            &ctx.accounts.vault_account,
+            &ctx.accounts.seller,
+            &ctx.accounts.token_program,
+            payout,
+        )?;
+
+        Ok(())
+    }
+
+    /// Settle a binary option against the oracle price at expiry (anyone can call)
+    ///
+    /// Uses the observation the oracle's price history holds for the expiry slot
+    /// (`oracle::get_settlement_price`), which anyone can record with
+    /// `record_observation` once the slot opens, so every settler sees the same
+    /// price. The escrowed payout goes to the buyer if the option finished in the
+    /// money, otherwise back to the seller. If no observation is recorded within
+    /// `SETTLEMENT_GRACE_SECS`, the seller can take the escrow back with `reclaim`.
+    pub fn settle_binary(ctx: Context<SettleBinary>) -> Result<()> {
+        let covered_call = &mut ctx.accounts.covered_call;
+        require!(covered_call.payoff != Payoff::Physical, ErrorCode::InvalidPayoff);
+        require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
+        require!(!covered_call.cancelled, ErrorCode::OptionCancelled);
+        let buyer = covered_call.buyer.ok_or(ErrorCode::OptionNotSold)?;
+        require!(ctx.accounts.buyer_quote_account.owner == buyer, ErrorCode::InvalidPayoutAccount);
+
+        let clock = Clock::get()?;
+        require!(clock.unix_timestamp >= covered_call.expiry_ts, ErrorCode::OptionNotExpired);
+
+        let cpi_accounts = oracle::cpi::accounts::GetSettlementPrice {
+            asset_config: ctx.accounts.asset_config.to_account_info(),
+            price_history: ctx.accounts.price_history.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.oracle_program.to_account_info();
+        let price = oracle::cpi::get_settlement_price(
+            CpiContext::new(cpi_program, cpi_accounts),
+            covered_call.expiry_ts,
+        )?
+        .get();
+
+        let spot = price
+            .scaled_price(PRICE_DECIMALS)
+            .ok_or(ErrorCode::InvalidOraclePrice)?;
+        let in_the_money = match covered_call.payoff {
+            Payoff::DigitalCall => spot > covered_call.strike,
+            Payoff::DigitalPut => spot < covered_call.strike,
+            Payoff::Physical => false,
+        };
 
+        let seeds = &[
+            b"vault",
+            covered_call.to_account_info().key.as_ref(),
+            &[ctx.bumps.vault_account],
+        ];
+        let signer = &[&seeds[..]];
+
+        let recipient = if in_the_money {
+            ctx.accounts.buyer_quote_account.to_account_info()
+        } else {
+            ctx.accounts.seller_quote_account.to_account_info()
+        };
         let cpi_accounts = Transfer {
-            from: ctx.accounts.seller_xstock_account.to_account_info(),
-            to: ctx.accounts.vault_account.to_account_info(),
-            authority: ctx.accounts.seller.to_account_info(),
+            from: ctx.accounts.vault_account.to_account_info(),
+            to: recipient,
+            authority: ctx.accounts.vault_account.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token::transfer(cpi_ctx, ctx.accounts.vault_account.amount)?;
+
+        covered_call.exercised = true;
+        covered_call.buyer_exercised = in_the_money;
+
+        Ok(())
+    }
+
+    /// Create an Asian (average-price) covered call
+    ///
+    /// Settles on the oracle TWAP over the `averaging_window` seconds before expiry
+    /// instead of a single print. The escrowed xStock is split by `settle_average`.
+    pub fn create_asian_call(
+        ctx: Context<CreateOracleCall>,
+        uid: u64,
+        strike: u64,
+        premium: u64,
+        expiry_ts: i64,
+        amount: u64,
+        averaging_window: i64,
+    ) -> Result<()> {
+        require!(
+            (oracle::OBSERVATION_SLOT_SECS..=MAX_AVERAGING_WINDOW_SECS).contains(&averaging_window),
+            ErrorCode::InvalidAveragingWindow
+        );
+
+        let covered_call = &mut ctx.accounts.covered_call;
+        covered_call.init(
+            ctx.accounts.seller.key(),
+            ctx.accounts.xstock_mint.key(),
+            ctx.accounts.quote_mint.key(),
+            uid,
+            strike,
+            premium,
+            expiry_ts,
+            amount,
+        );
+        covered_call.oracle_asset = ctx.accounts.asset_config.key();
+        covered_call.averaging_window = averaging_window;
+
+        escrow(
+            &ctx.accounts.seller_xstock_account,
+            &ctx.accounts.vault_account,
+            &ctx.accounts.seller,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    /// Fold the elapsed part of an Asian call's averaging window into the option
+    /// (anyone can call)
+    ///
+    /// The oracle only keeps 24 hours of observations, so the running average is
+    /// stored on the option up to the start of the current observation slot.
+    /// Settlement then only needs the history after the last checkpoint.
+    pub fn checkpoint_average(ctx: Context<CheckpointAverage>) -> Result<()> {
+        let covered_call = &mut ctx.accounts.covered_call;
+        require!(covered_call.averaging_window > 0, ErrorCode::InvalidPayoff);
+        require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
+        require!(!covered_call.cancelled, ErrorCode::OptionCancelled);
+
+        let now = Clock::get()?.unix_timestamp;
+        let slot_start = now - now.rem_euclid(oracle::OBSERVATION_SLOT_SECS);
+        let end_ts = slot_start.min(covered_call.expiry_ts);
+        require!(end_ts > covered_call.average_start(), ErrorCode::NothingToCheckpoint);
+
+        accumulate_average(
+            covered_call,
+            &ctx.accounts.asset_config,
+            &ctx.accounts.price_history,
+            &ctx.accounts.oracle_program,
+            end_ts,
+        )
+    }
+
+    /// Settle an Asian call on the oracle TWAP (anyone can call after expiry)
+    ///
+    /// Averages the checkpointed part of the window plus the history since the
+    /// last checkpoint, and requires an observation in every slot of the window. If the average price is above the strike the buyer receives the
+    /// payoff in xStock, `amount * (average - strike) / average`, and the rest of
+    /// the escrow goes back to the seller.
+    pub fn settle_average(ctx: Context<SettleAverage>) -> Result<()> {
+        let covered_call = &mut ctx.accounts.covered_call;
+        require!(covered_call.averaging_window > 0, ErrorCode::InvalidPayoff);
+        require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
+        require!(!covered_call.cancelled, ErrorCode::OptionCancelled);
+        let buyer = covered_call.buyer.ok_or(ErrorCode::OptionNotSold)?;
+        require!(ctx.accounts.buyer_xstock_account.owner == buyer, ErrorCode::InvalidPayoutAccount);
+
+        let clock = Clock::get()?;
+        require!(clock.unix_timestamp >= covered_call.expiry_ts, ErrorCode::OptionNotExpired);
+
+        let expiry_ts = covered_call.expiry_ts;
+        if covered_call.average_start() < expiry_ts {
+            accumulate_average(
+                covered_call,
+                &ctx.accounts.asset_config,
+                &ctx.accounts.price_history,
+                &ctx.accounts.oracle_program,
+                expiry_ts,
+            )?;
+        }
+        require!(
+            covered_call.average_observations >= covered_call.min_average_observations(),
+            ErrorCode::TooFewObservations
+        );
+        let average_price = u64::try_from(covered_call.average_sum / covered_call.averaging_window as u128)
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        require!(average_price > 0, ErrorCode::InvalidOraclePrice);
+
+        let escrowed = ctx.accounts.vault_account.amount;
+        let buyer_amount = if average_price > covered_call.strike {
+            ((escrowed as u128)
+                .checked_mul((average_price - covered_call.strike) as u128)
+                .ok_or(ErrorCode::MathOverflow)?
+                / average_price as u128) as u64
+        } else {
+            0
+        };
+        let seller_amount = escrowed - buyer_amount;
+
+        let seeds = &[
+            b"vault",
+            covered_call.to_account_info().key.as_ref(),
+            &[ctx.bumps.vault_account],
+        ];
+        let signer = &[&seeds[..]];
+
+        for (recipient, amount) in [
+            (ctx.accounts.buyer_xstock_account.to_account_info(), buyer_amount),
+            (ctx.accounts.seller_xstock_account.to_account_info(), seller_amount),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.vault_account.to_account_info(),
+                to: recipient,
+                authority: ctx.accounts.vault_account.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+            token::transfer(cpi_ctx, amount)?;
+        }
+
+        covered_call.exercised = true;
+        covered_call.buyer_exercised = buyer_amount > 0;
+
+        Ok(())
+    }
+
+    /// Trigger the barrier of a barrier call (anyone can call)
+    ///
+    /// Reads the price through `oracle::get_price` and requires an `Ok` reading
+    /// that touches or crosses the barrier before expiry.
+    pub fn trigger_barrier(ctx: Context<TriggerBarrier>) -> Result<()> {
+        let covered_call = &mut ctx.accounts.covered_call;
+        require!(covered_call.barrier_type != BarrierType::None, ErrorCode::NotBarrierOption);
+        require!(!covered_call.barrier_triggered, ErrorCode::BarrierAlreadyTriggered);
+        require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
+        require!(!covered_call.cancelled, ErrorCode::OptionCancelled);
+
+        let clock = Clock::get()?;
+        require!(clock.unix_timestamp < covered_call.expiry_ts, ErrorCode::OptionExpired);
+
+        let cpi_accounts = oracle::cpi::accounts::GetPrice {
+            asset_config: ctx.accounts.asset_config.to_account_info(),
+            pyth_price_account: ctx.accounts.pyth_price_account.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.oracle_program.to_account_info();
+        let price = oracle::cpi::get_price(CpiContext::new(cpi_program, cpi_accounts))?.get();
+        require!(price.status == OracleStatus::Ok, ErrorCode::OracleNotOk);
+
+        let spot = price
+            .scaled_price(PRICE_DECIMALS)
+            .ok_or(ErrorCode::InvalidOraclePrice)?;
+        let crossed = match covered_call.barrier_type {
+            BarrierType::UpAndOut => spot >= covered_call.barrier,
+            BarrierType::DownAndIn => spot <= covered_call.barrier,
+            BarrierType::None => false,
+        };
+        require!(crossed, ErrorCode::BarrierNotCrossed);
+
+        covered_call.barrier_triggered = true;
 
         Ok(())
     }
@@ -55,6 +410,7 @@ pub mod xstock_options {
         require!(covered_call.is_listed, ErrorCode::OptionNotListed);
         require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
         require!(!covered_call.cancelled, ErrorCode::OptionCancelled);
+        require!(!covered_call.is_knocked_out(), ErrorCode::OptionKnockedOut);
         
         let clock = Clock::get()?;
         require!(clock.unix_timestamp < covered_call.expiry_ts, ErrorCode::OptionExpired);
@@ -76,6 +432,33 @@ pub mod xstock_options {
             .checked_div(1_000_000)
             .ok_or(ErrorCode::MathOverflow)?;
 
+        // Secondary sales pay the writer's royalty out of the premium
+        let royalty = if covered_call.buyer.is_some() && covered_call.royalty_bps > 0 {
+            (total_premium as u128)
+                .checked_mul(covered_call.royalty_bps as u128)
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_div(10_000)
+                .ok_or(ErrorCode::MathOverflow)? as u64
+        } else {
+            0
+        };
+
+        if royalty > 0 {
+            let seller_quote_account = ctx
+                .accounts
+                .seller_quote_account
+                .as_ref()
+                .ok_or(ErrorCode::MissingRoyaltyAccount)?;
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.buyer_quote_account.to_account_info(),
+                to: seller_quote_account.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            token::transfer(cpi_ctx, royalty)?;
+        }
+
         let cpi_accounts = Transfer {
             from: ctx.accounts.buyer_quote_account.to_account_info(),
             to: ctx.accounts.payment_account.to_account_info(),
@@ -83,7 +466,7 @@ pub mod xstock_options {
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, total_premium)?;
+        token::transfer(cpi_ctx, total_premium - royalty)?;
 
         covered_call.buyer = Some(ctx.accounts.buyer.key());
         covered_call.is_listed = false; 
@@ -99,8 +482,14 @@ pub mod xstock_options {
     pub fn exercise(ctx: Context<Exercise>) -> Result<()> {
         let covered_call = &mut ctx.accounts.covered_call;
         require!(covered_call.buyer == Some(ctx.accounts.buyer.key()), ErrorCode::Unauthorized);
+        require!(!covered_call.is_cash_settled(), ErrorCode::InvalidPayoff);
         require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
         require!(!covered_call.cancelled, ErrorCode::OptionCancelled);
+        require!(!covered_call.is_knocked_out(), ErrorCode::OptionKnockedOut);
+        require!(
+            covered_call.barrier_type != BarrierType::DownAndIn || covered_call.barrier_triggered,
+            ErrorCode::OptionNotKnockedIn
+        );
         
         // American-style: allow exercise anytime before expiration
         let clock = Clock::get()?;
@@ -143,10 +532,7 @@ pub mod xstock_options {
         require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
 
         let clock = Clock::get()?;
-        let is_expired = clock.unix_timestamp >= covered_call.expiry_ts;
-        let is_unsold = covered_call.buyer.is_none();
-
-        require!(is_expired || is_unsold, ErrorCode::OptionNotExpired);
+        require!(covered_call.can_reclaim(clock.unix_timestamp), ErrorCode::OptionNotExpired);
 
         let seeds = &[
             b"vault",
@@ -173,12 +559,76 @@ pub mod xstock_options {
         Ok(())
     }
 
+    /// Move reclaimable collateral into the matching V2 vault in one transaction
+    ///
+    /// Same conditions as `reclaim`. The xStock is routed through the seller's account
+    /// and deposited with `vault::deposit`, so the seller is the depositor and
+    /// receives the vault shares. Unless the vault is empty no shares arrive in this
+    /// transaction: the deposit is queued until the next epoch roll, which prices it,
+    /// and the seller collects the shares with `vault::claim_shares`. A nonzero
+    /// `min_shares_out` is passed through to the deposit, which checks it against the
+    /// current price and again at the roll, where a deposit priced below it is
+    /// returned by `claim_shares` instead. The vault's oracle accounts are then needed
+    /// once it holds quote assets.
+    pub fn migrate_to_vault(ctx: Context<MigrateToVault>, min_shares_out: u64) -> Result<()> {
+        let covered_call = &mut ctx.accounts.covered_call;
+        require!(covered_call.seller == ctx.accounts.seller.key(), ErrorCode::Unauthorized);
+        require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
+
+        let clock = Clock::get()?;
+        require!(covered_call.can_reclaim(clock.unix_timestamp), ErrorCode::OptionNotExpired);
+
+        let seeds = &[
+            b"vault",
+            covered_call.to_account_info().key.as_ref(),
+            &[ctx.bumps.vault_account],
+        ];
+        let signer = &[&seeds[..]];
+
+        let amount = ctx.accounts.vault_account.amount;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vault_account.to_account_info(),
+            to: ctx.accounts.seller_xstock_account.to_account_info(),
+            authority: ctx.accounts.vault_account.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        token::transfer(cpi_ctx, amount)?;
+
+        let cpi_accounts = vault::cpi::accounts::Deposit {
+            vault: ctx.accounts.earn_vault.to_account_info(),
+            share_mint: ctx.accounts.share_mint.to_account_info(),
+            vault_token_account: ctx.accounts.vault_token_account.to_account_info(),
+            user_token_account: ctx.accounts.seller_xstock_account.to_account_info(),
+            user_share_account: ctx.accounts.seller_share_account.to_account_info(),
+            user: ctx.accounts.seller.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            share_token_program: ctx.accounts.share_token_program.to_account_info(),
+            deposit_receipt: ctx.accounts.deposit_receipt.to_account_info(),
+            depositor: ctx.accounts.depositor.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            allowlist_entry: None,
+            asset_config: ctx.accounts.asset_config.as_ref().map(|account| account.to_account_info()),
+            pyth_price_account: ctx.accounts.pyth_price_account.as_ref().map(|account| account.to_account_info()),
+            oracle_program: ctx.accounts.oracle_program.as_ref().map(|account| account.to_account_info()),
+        };
+        let cpi_program = ctx.accounts.vault_program.to_account_info();
+        vault::cpi::deposit(CpiContext::new(cpi_program, cpi_accounts), amount, min_shares_out)?;
+
+        covered_call.exercised = true;
+        covered_call.cancelled = true;
+
+        Ok(())
+    }
+
     pub fn list_for_sale(ctx: Context<ListForSale>, price: u64) -> Result<()> {
         let covered_call = &mut ctx.accounts.covered_call;
         let current_owner = covered_call.buyer.unwrap_or(covered_call.seller);
         require!(current_owner == ctx.accounts.signer.key(), ErrorCode::Unauthorized);
         require!(!covered_call.exercised, ErrorCode::OptionAlreadyExercised);
         require!(!covered_call.cancelled, ErrorCode::OptionCancelled);
+        require!(!covered_call.is_knocked_out(), ErrorCode::OptionKnockedOut);
         
         covered_call.is_listed = true;
         covered_call.ask_price = price;
@@ -195,6 +645,93 @@ pub mod xstock_options {
         
         Ok(())
     }
+
+    /// Grow a covered call created before barriers, binaries, Asian calls and
+    /// royalties to the current layout (anyone can call and pays the extra rent)
+    ///
+    /// The resize zero-fills, which reads back as no barrier, oracle asset,
+    /// averaging window or royalty and a physical payoff, i.e. the old terms.
+    /// The option is taken unchecked because the old layout is too short to
+    /// deserialize before the resize.
+    pub fn migrate_covered_call(ctx: Context<MigrateCoveredCall>) -> Result<()> {
+        let covered_call_info = ctx.accounts.covered_call.to_account_info();
+        require!(
+            covered_call_info.data_len() < CoveredCall::LEN,
+            ErrorCode::CoveredCallAlreadyMigrated
+        );
+
+        let required = Rent::get()?.minimum_balance(CoveredCall::LEN);
+        let top_up = required.saturating_sub(covered_call_info.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: covered_call_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        covered_call_info.resize(CoveredCall::LEN)?;
+
+        // Checks the discriminator
+        CoveredCall::try_deserialize(&mut &covered_call_info.try_borrow_data()?[..])?;
+
+        Ok(())
+    }
+}
+
+/// Move the seller's collateral into the option's escrow account
+fn escrow<'info>(
+    from: &Account<'info, TokenAccount>,
+    vault_account: &Account<'info, TokenAccount>,
+    seller: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    let cpi_accounts = Transfer {
+        from: from.to_account_info(),
+        to: vault_account.to_account_info(),
+        authority: seller.to_account_info(),
+    };
+    token::transfer(CpiContext::new(token_program.to_account_info(), cpi_accounts), amount)
+}
+
+/// Add the oracle TWAP from the last checkpoint up to `end_ts` to an Asian call's
+/// running average
+fn accumulate_average<'info>(
+    covered_call: &mut CoveredCall,
+    asset_config: &Account<'info, AssetConfig>,
+    price_history: &Account<'info, PriceHistory>,
+    oracle_program: &Program<'info, Oracle>,
+    end_ts: i64,
+) -> Result<()> {
+    let start_ts = covered_call.average_start();
+    let cpi_accounts = oracle::cpi::accounts::GetAveragePrice {
+        asset_config: asset_config.to_account_info(),
+        price_history: price_history.to_account_info(),
+    };
+    let average = oracle::cpi::get_average_price(
+        CpiContext::new(oracle_program.to_account_info(), cpi_accounts),
+        start_ts,
+        end_ts,
+    )?
+    .get();
+    let average_price = average
+        .scaled_price(PRICE_DECIMALS)
+        .ok_or(ErrorCode::InvalidOraclePrice)?;
+
+    covered_call.average_sum = covered_call
+        .average_sum
+        .checked_add(average_price as u128 * (end_ts - start_ts) as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    covered_call.average_observations = covered_call
+        .average_observations
+        .saturating_add(average.observations);
+    covered_call.averaged_until = end_ts;
+    Ok(())
 }
 
 #[derive(Accounts)]
@@ -207,7 +744,7 @@ pub struct CreateCoveredCall<'info> {
     #[account(
         init, 
         payer = seller, 
-        space = 8 + CoveredCall::INIT_SPACE,
+        space = CoveredCall::LEN,
         seeds = [b"covered_call", seller.key().as_ref(), xstock_mint.key().as_ref(), &uid.to_le_bytes()],
         bump
     )]
@@ -232,6 +769,192 @@ pub struct CreateCoveredCall<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+/// Shared by the xStock-escrowed options that reference an oracle asset:
+/// barrier calls and Asian calls
+#[derive(Accounts)]
+#[instruction(uid: u64)]
+pub struct CreateOracleCall<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    pub xstock_mint: Account<'info, Mint>,
+    pub quote_mint: Account<'info, Mint>,
+    /// Oracle asset used to monitor the barrier or average the price
+    pub asset_config: Account<'info, AssetConfig>,
+    /// Ties asset_config to xstock_mint
+    #[account(
+        seeds = [b"asset_mint", asset_config.key().as_ref()],
+        bump = asset_mint.bump,
+        seeds::program = oracle_program.key(),
+        constraint = asset_mint.mint == xstock_mint.key() @ ErrorCode::InvalidOracleAsset
+    )]
+    pub asset_mint: Account<'info, AssetMint>,
+    #[account(
+        init, 
+        payer = seller, 
+        space = CoveredCall::LEN,
+        seeds = [b"covered_call", seller.key().as_ref(), xstock_mint.key().as_ref(), &uid.to_le_bytes()],
+        bump
+    )]
+    pub covered_call: Account<'info, CoveredCall>,
+    #[account(
+        mut,
+        constraint = seller_xstock_account.mint == xstock_mint.key(),
+        constraint = seller_xstock_account.owner == seller.key()
+    )]
+    pub seller_xstock_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = seller,
+        seeds = [b"vault", covered_call.key().as_ref()],
+        bump,
+        token::mint = xstock_mint,
+        token::authority = vault_account
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub oracle_program: Program<'info, Oracle>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(uid: u64)]
+pub struct CreateBinaryOption<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    pub xstock_mint: Account<'info, Mint>,
+    pub quote_mint: Account<'info, Mint>,
+    /// Oracle asset used to settle the option
+    pub asset_config: Account<'info, AssetConfig>,
+    /// Ties asset_config to xstock_mint
+    #[account(
+        seeds = [b"asset_mint", asset_config.key().as_ref()],
+        bump = asset_mint.bump,
+        seeds::program = oracle_program.key(),
+        constraint = asset_mint.mint == xstock_mint.key() @ ErrorCode::InvalidOracleAsset
+    )]
+    pub asset_mint: Account<'info, AssetMint>,
+    #[account(
+        init, 
+        payer = seller, 
+        space = CoveredCall::LEN,
+        seeds = [b"covered_call", seller.key().as_ref(), xstock_mint.key().as_ref(), &uid.to_le_bytes()],
+        bump
+    )]
+    pub covered_call: Account<'info, CoveredCall>,
+    #[account(
+        mut,
+        constraint = seller_quote_account.mint == quote_mint.key(),
+        constraint = seller_quote_account.owner == seller.key()
+    )]
+    pub seller_quote_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = seller,
+        seeds = [b"vault", covered_call.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = vault_account
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub oracle_program: Program<'info, Oracle>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SettleBinary<'info> {
+    #[account(mut)]
+    pub covered_call: Account<'info, CoveredCall>,
+    #[account(
+        mut,
+        seeds = [b"vault", covered_call.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(address = covered_call.oracle_asset)]
+    pub asset_config: Account<'info, AssetConfig>,
+    #[account(
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump = price_history.bump,
+        seeds::program = oracle_program.key()
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+    #[account(
+        mut,
+        constraint = buyer_quote_account.mint == covered_call.quote_mint
+    )]
+    pub buyer_quote_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = seller_quote_account.mint == covered_call.quote_mint,
+        constraint = seller_quote_account.owner == covered_call.seller
+    )]
+    pub seller_quote_account: Account<'info, TokenAccount>,
+    pub oracle_program: Program<'info, Oracle>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CheckpointAverage<'info> {
+    #[account(mut)]
+    pub covered_call: Account<'info, CoveredCall>,
+    #[account(address = covered_call.oracle_asset)]
+    pub asset_config: Account<'info, AssetConfig>,
+    #[account(
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump = price_history.bump,
+        seeds::program = oracle_program.key()
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+    pub oracle_program: Program<'info, Oracle>,
+}
+
+#[derive(Accounts)]
+pub struct SettleAverage<'info> {
+    #[account(mut)]
+    pub covered_call: Account<'info, CoveredCall>,
+    #[account(
+        mut,
+        seeds = [b"vault", covered_call.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(address = covered_call.oracle_asset)]
+    pub asset_config: Account<'info, AssetConfig>,
+    #[account(
+        seeds = [b"history", asset_config.key().as_ref()],
+        bump = price_history.bump,
+        seeds::program = oracle_program.key()
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+    #[account(
+        mut,
+        constraint = buyer_xstock_account.mint == covered_call.xstock_mint
+    )]
+    pub buyer_xstock_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = seller_xstock_account.mint == covered_call.xstock_mint,
+        constraint = seller_xstock_account.owner == covered_call.seller
+    )]
+    pub seller_xstock_account: Account<'info, TokenAccount>,
+    pub oracle_program: Program<'info, Oracle>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct TriggerBarrier<'info> {
+    #[account(mut)]
+    pub covered_call: Account<'info, CoveredCall>,
+    #[account(address = covered_call.oracle_asset)]
+    pub asset_config: Account<'info, AssetConfig>,
+    /// CHECK: Pyth price account, validated by the oracle program against asset_config
+    pub pyth_price_account: UncheckedAccount<'info>,
+    pub oracle_program: Program<'info, Oracle>,
+}
+
 #[derive(Accounts)]
 pub struct BuyOption<'info> {
     #[account(mut)]
@@ -249,6 +972,13 @@ pub struct BuyOption<'info> {
         constraint = payment_account.mint == covered_call.quote_mint
     )]
     pub payment_account: Account<'info, TokenAccount>,
+    /// Receives the writer's royalty, required for secondary sales with a royalty
+    #[account(
+        mut,
+        constraint = seller_quote_account.mint == covered_call.quote_mint,
+        constraint = seller_quote_account.owner == covered_call.seller
+    )]
+    pub seller_quote_account: Option<Account<'info, TokenAccount>>,
     pub token_program: Program<'info, Token>,
 }
 
@@ -287,6 +1017,31 @@ pub struct Exercise<'info> {
 
 #[derive(Accounts)]
 pub struct Reclaim<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    #[account(
+        mut,
+        constraint = covered_call.seller == seller.key(),
+    )]
+    pub covered_call: Account<'info, CoveredCall>,
+    #[account(
+        mut,
+        seeds = [b"vault", covered_call.key().as_ref()],
+        bump,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    /// Receives the escrowed tokens (xStock, or quote tokens for binaries)
+    #[account(
+        mut,
+        constraint = seller_xstock_account.mint == vault_account.mint,
+        constraint = seller_xstock_account.owner == seller.key()
+    )]
+    pub seller_xstock_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateToVault<'info> {
     #[account(mut)]
     pub seller: Signer<'info>,
     #[account(
@@ -306,7 +1061,39 @@ pub struct Reclaim<'info> {
         constraint = seller_xstock_account.owner == seller.key()
     )]
     pub seller_xstock_account: Account<'info, TokenAccount>,
+    /// V2 vault for the same xStock
+    #[account(
+        mut,
+        constraint = earn_vault.underlying_mint == vault_account.mint
+    )]
+    pub earn_vault: Box<Account<'info, vault::Vault>>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub share_mint: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub vault_token_account: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub seller_share_account: UncheckedAccount<'info>,
+    pub vault_program: Program<'info, VaultProgram>,
     pub token_program: Program<'info, Token>,
+    /// CHECK: Validated by the vault program
+    pub share_token_program: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub deposit_receipt: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub depositor: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: Validated by the vault program, only needed to check a nonzero
+    /// `min_shares_out` once the vault holds quote assets
+    pub asset_config: Option<UncheckedAccount<'info>>,
+    /// CHECK: Validated by the vault program
+    pub pyth_price_account: Option<UncheckedAccount<'info>>,
+    /// CHECK: Validated by the vault program
+    pub oracle_program: Option<UncheckedAccount<'info>>,
 }
 
 #[derive(Accounts)]
@@ -325,6 +1112,16 @@ pub struct CancelListing<'info> {
     pub covered_call: Account<'info, CoveredCall>,
 }
 
+#[derive(Accounts)]
+pub struct MigrateCoveredCall<'info> {
+    /// CHECK: Deserialized in the handler after the resize, which checks the discriminator
+    #[account(mut, owner = crate::ID)]
+    pub covered_call: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct CoveredCall {
@@ -333,7 +1130,7 @@ pub struct CoveredCall {
     pub xstock_mint: Pubkey,
     pub quote_mint: Pubkey,
     pub uid: u64,
-    pub strike: u64,
+    pub strike: u64, // Total paid on physical exercise, a per-unit price for binary and Asian options
     pub premium: u64,
     pub expiry_ts: i64,
     pub amount: u64,
@@ -342,6 +1139,112 @@ pub struct CoveredCall {
     pub cancelled: bool,
     pub is_listed: bool,
     pub ask_price: u64,
+    pub barrier_type: BarrierType,
+    pub barrier: u64,
+    pub oracle_asset: Pubkey,
+    pub barrier_triggered: bool,
+    pub payoff: Payoff,
+    pub averaging_window: i64, // Seconds before expiry averaged for settlement (0 = single print)
+    pub royalty_bps: u16,      // Writer's share of secondary sale premiums
+    pub averaged_until: i64,   // End of the checkpointed part of the averaging window
+    pub average_sum: u128,     // Sum of price * seconds over the checkpointed part
+    pub average_observations: u16, // Oracle observations inside the checkpointed part
+}
+
+impl CoveredCall {
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+
+    /// Set the terms every option type shares. The option is listed at its premium,
+    /// with no barrier, oracle asset, averaging window or royalty and a physical payoff.
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        &mut self,
+        seller: Pubkey,
+        xstock_mint: Pubkey,
+        quote_mint: Pubkey,
+        uid: u64,
+        strike: u64,
+        premium: u64,
+        expiry_ts: i64,
+        amount: u64,
+    ) {
+        self.seller = seller;
+        self.xstock_mint = xstock_mint;
+        self.quote_mint = quote_mint;
+        self.uid = uid;
+        self.strike = strike;
+        self.premium = premium;
+        self.expiry_ts = expiry_ts;
+        self.amount = amount;
+        self.exercised = false;
+        self.cancelled = false;
+        self.buyer = None;
+        self.buyer_exercised = false;
+        self.is_listed = true;
+        self.ask_price = premium;
+        self.barrier_type = BarrierType::None;
+        self.barrier = 0;
+        self.oracle_asset = Pubkey::default();
+        self.barrier_triggered = false;
+        self.payoff = Payoff::Physical;
+        self.averaging_window = 0;
+        self.royalty_bps = 0;
+        self.averaged_until = 0;
+        self.average_sum = 0;
+        self.average_observations = 0;
+    }
+
+    /// An up-and-out call whose barrier has been hit can no longer be exercised
+    pub fn is_knocked_out(&self) -> bool {
+        self.barrier_type == BarrierType::UpAndOut && self.barrier_triggered
+    }
+
+    /// Start of the part of the averaging window that is not checkpointed yet
+    pub fn average_start(&self) -> i64 {
+        self.averaged_until.max(self.expiry_ts - self.averaging_window)
+    }
+
+    /// Observations an Asian call needs to settle: one for every slot starting in the window
+    pub fn min_average_observations(&self) -> u16 {
+        let window_start = self.expiry_ts - self.averaging_window;
+        ((oracle::settlement_slot(self.expiry_ts) - oracle::settlement_slot(window_start))
+            / oracle::OBSERVATION_SLOT_SECS) as u16
+    }
+
+    /// Binaries and Asian calls settle against the oracle instead of through `exercise`
+    pub fn is_cash_settled(&self) -> bool {
+        self.payoff != Payoff::Physical || self.averaging_window > 0
+    }
+
+    /// Whether the seller can take back the escrow: unsold, knocked out, or expired.
+    /// Sold cash-settled options pay out through their settle instruction instead,
+    /// unless they are still unsettled `SETTLEMENT_GRACE_SECS` after expiry.
+    pub fn can_reclaim(&self, now: i64) -> bool {
+        let is_expired = now >= self.expiry_ts;
+        let is_unsold = self.buyer.is_none();
+        let settlement_lapsed = now >= self.expiry_ts.saturating_add(SETTLEMENT_GRACE_SECS);
+        (is_expired && (!self.is_cash_settled() || settlement_lapsed)) || is_unsold || self.is_knocked_out()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum Payoff {
+    Physical,    // Covered call: buyer pays the strike and receives the escrowed xStock
+    DigitalCall, // Pays the escrowed amount if the expiry price is above the strike
+    DigitalPut,  // Pays the escrowed amount if the expiry price is below the strike
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum BarrierType {
+    None,
+    UpAndOut,  // Knocked out once the price trades at or above the barrier
+    DownAndIn, // Activated once the price trades at or below the barrier
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct BarrierParams {
+    pub barrier_type: BarrierType,
+    pub level: u64,
 }
 
 #[error_code]
@@ -364,4 +1267,38 @@ pub enum ErrorCode {
     CannotBuyOwnOption,
     #[msg("Math overflow")]
     MathOverflow,
+    #[msg("Invalid barrier parameters")]
+    InvalidBarrier,
+    #[msg("Option has no barrier")]
+    NotBarrierOption,
+    #[msg("Barrier already triggered")]
+    BarrierAlreadyTriggered,
+    #[msg("Oracle price has not crossed the barrier")]
+    BarrierNotCrossed,
+    #[msg("Option has been knocked out")]
+    OptionKnockedOut,
+    #[msg("Option has not been knocked in")]
+    OptionNotKnockedIn,
+    #[msg("Oracle status is not OK")]
+    OracleNotOk,
+    #[msg("Invalid oracle price")]
+    InvalidOraclePrice,
+    #[msg("Instruction does not support this payoff type")]
+    InvalidPayoff,
+    #[msg("Option has not been sold")]
+    OptionNotSold,
+    #[msg("Invalid averaging window")]
+    InvalidAveragingWindow,
+    #[msg("Royalty exceeds the maximum")]
+    InvalidRoyalty,
+    #[msg("Seller quote account is required to pay the royalty")]
+    MissingRoyaltyAccount,
+    #[msg("No new part of the averaging window to checkpoint")]
+    NothingToCheckpoint,
+    #[msg("Too few oracle observations in the averaging window")]
+    TooFewObservations,
+    #[msg("Oracle asset does not price this xStock")]
+    InvalidOracleAsset,
+    #[msg("Covered call already has the current layout")]
+    CoveredCallAlreadyMigrated,
 }
\ No newline at end of file
@@ -22,32 +22,49 @@ async function main() {
 
     const args = process.argv.slice(2);
     const assetIdArg = args.find(arg => arg.startsWith("--asset="));
-    const premiumArg = args.find(arg => arg.startsWith("--premium="));
 
     const assetId = assetIdArg ? assetIdArg.split("=")[1] : "NVDAx";
-    const premium = premiumArg ? Number(premiumArg.split("=")[1]) : 0; // Base units for now (e.g. 0 premium)
 
     console.log("=================================");
     console.log("Advance Epoch Script");
     console.log("=================================");
     console.log(`Asset: ${assetId}`);
-    console.log(`Premium to distribute: ${premium}`);
 
     const [vaultPda] = PublicKey.findProgramAddressSync(
         [Buffer.from("vault"), Buffer.from(assetId)],
         program.programId
     );
+    const [vaultTokenAccountPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault_tokens"), vaultPda.toBuffer()],
+        program.programId
+    );
 
     try {
         const vaultBefore = await program.account.vault.fetch(vaultPda);
         console.log(`Current Epoch: ${vaultBefore.epoch.toString()}`);
 
+        const [epochRecordPda] = PublicKey.findProgramAddressSync(
+            [Buffer.from("epoch_record"), vaultPda.toBuffer(), vaultBefore.epoch.toArrayLike(Buffer, "le", 8)],
+            program.programId
+        );
+
+        // Deposits queued this epoch with a minimum are checked against the roll's price
+        const receipts = await program.account.depositReceipt.all([
+            { memcmp: { offset: 40, bytes: vaultPda.toBase58() } },
+        ]);
+        const guardedReceipts = receipts
+            .filter(entry => entry.account.epoch.eq(vaultBefore.epoch) && !entry.account.minSharesOut.isZero())
+            .map(entry => ({ pubkey: entry.publicKey, isSigner: false, isWritable: true }));
+
         const tx = await program.methods
-            .advanceEpoch(new anchor.BN(premium))
-            .accounts({
+            .advanceEpoch()
+            .accountsPartial({
                 vault: vaultPda,
+                epochRecord: epochRecordPda,
                 authority: wallet.publicKey,
+                vaultTokenAccount: vaultTokenAccountPda,
             })
+            .remainingAccounts(guardedReceipts)
             .rpc();
 
         console.log("\n✅ Epoch advanced successfully!");
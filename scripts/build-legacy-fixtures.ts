@@ -0,0 +1,750 @@
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { AccountLayout, MintLayout, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { createHash } from "crypto";
+import * as fs from "fs";
+import * as path from "path";
+
+// Writes the tests/fixtures accounts loaded by Anchor.toml: a vault and a withdrawal
+// request in the original, unversioned layout, plus the token accounts around them.
+// The migration tests in tests/vault.ts upgrade them with migrate_vault and
+// migrate_withdrawal. Also writes two vaults in the current layout that the localnet
+// tests can't build without a live oracle price, one holding a quote balance and one
+// whose options expired unsettled, a call recorded in an epoch whose settlement grace
+// period is long over, and for tests/xstock.ts a covered call in the original layout,
+// which it upgrades with migrate_covered_call, a sold Asian call that expired in
+// 2023 with the price history of its averaging window, and a sold digital call and
+// digital put that expired in 2023 with the observation recorded at their expiry.
+// Re-run after changing the vault or options program ID or the Vault layout:
+//   npx ts-node scripts/build-legacy-fixtures.ts
+
+const VAULT_PROGRAM_ID = new PublicKey("8gJHdrseXDiqUuBUBtMuNgn6G6GoLZ8oLiPHwru7NuPY");
+const OPTIONS_PROGRAM_ID = new PublicKey("9SPZCWiT2xcYA7DDZTpSbhGFdDugSr9VqeQ7PVFBFgN");
+const RFQ_PROGRAM_ID = new PublicKey("3M2K6htNbWyZHtvvUyUME19f5GUS6x8AtGmitFENDT5Z");
+const ORACLE_PROGRAM_ID = new PublicKey("5MnuN6ahpRSp5F3R2uXvy9pSN4TQmhSydywQSoxszuZk");
+const FIXTURES_DIR = path.join(__dirname, "..", "tests", "fixtures");
+
+const LEGACY_ASSET_ID = "LEGACYx";
+const LEGACY_TOTAL_ASSETS = 5_000_000;
+const LEGACY_EPOCH = 3;
+const LEGACY_WITHDRAWAL_SHARES = 2_000_000;
+
+// Original space: 8 + 32 + 64 + 32 + 32 + 32 + 8 + 8 + 8 + 2 + 8 + 8 + 8 + 8 + 4 + 1
+const LEGACY_VAULT_LEN = 263;
+// Original space: 8 + 32 + 32 + 8 + 8 + 1 + 1
+const LEGACY_WITHDRAWAL_LEN = 90;
+
+const QUOTE_ASSET_ID = "QUOTEx";
+const QUOTE_VAULT_ASSETS = 1_000_000;
+const QUOTE_VAULT_QUOTE_ASSETS = 500_000;
+const FEE_ASSET_ID = "FEEx";
+const FEE_VAULT_ASSETS = 1_000_000;
+const EXPIRED_ASSET_ID = "EXPIREDx";
+const EXPIRED_VAULT_ASSETS = 2_000_000;
+const EXPIRED_VAULT_NOTIONAL = 1_000_000;
+const ATTEST_ASSET_ID = "ATTESTx";
+const ATTEST_VAULT_ASSETS = 2_000_000;
+const ATTEST_NOTIONAL = 1_000_000;
+const ATTEST_STRIKE = 110_000_000;
+// Far past the RFQ ids the localnet tests create
+const ATTEST_RFQ_ID = 1_000_000;
+// Vault::LEN at version 6, 8 + Vault::INIT_SPACE with the fields grouped by the version that added
+// them. The program asserts the same 699 bytes at compile time.
+const VAULT_ASSET_ID_MAX_LEN = 60;
+const VAULT_LEN =
+    8                                                                       // discriminator
+    + 32 + 4 + VAULT_ASSET_ID_MAX_LEN + 32 + 32 + 32 + 8 + 8 + 8 + 2 + 8 + 8 // version 0
+    + 8 + 8 + 4 + 1
+    + 1                                                                     // version
+    + 1 + 8 + 8 + 1 + 32 + 32 + 8 + 8 + 8 + 2 + 8 + 32 + 2 + 2 + 8         // version 1
+    + 8 + 8 + 1 + 8 + 8 + 8 + 1 + 32 + 1 + 32 + 8 + 32 + 32
+    + 32                                                                    // version 2
+    + 8 + 4                                                                 // version 3
+    + 8                                                                     // version 4
+    + 8 + 32                                                                // version 5
+    + 4;                                                                    // version 6
+const VAULT_VERSION = 6;
+
+// RecordedRfq::LEN
+const RECORDED_RFQ_LEN = 131;
+// rfq::Rfq::LEN at version 2
+const RFQ_LEN = 253;
+const RFQ_VERSION = 2;
+// Original Rfq space, before the premium account was pinned:
+// 8 + 8 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 1 + 32 + 8 + 8 + 1
+const LEGACY_RFQ_LEN = 188;
+const LEGACY_RFQ_ID = 1_000_001;
+
+// Original CoveredCall space: 8 + 32 + 33 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 4 + 8
+const LEGACY_COVERED_CALL_LEN = 189;
+// CoveredCall::LEN
+const COVERED_CALL_LEN = 268;
+
+// The Asian call averages the hour before expiry, slot-aligned, over four observations
+const AVERAGE_ASSET_ID = "NVDAxAVERAGE";
+const AVERAGE_EXPIRY = 1_700_001_000;
+const AVERAGE_WINDOW = 3_600;
+const AVERAGE_PRICES = [100_000_000, 110_000_000, 120_000_000, 130_000_000];
+const AVERAGE_AMOUNT = 100_000_000;
+// Same call on an asset whose history skips a slot of the window, so it cannot settle
+const GAPPED_AVERAGE_ASSET_ID = "NVDAxGAPPED";
+// The binaries expire on a slot boundary and settle on the observation recorded for it
+const BINARY_ASSET_ID = "NVDAxBINARY";
+const BINARY_EXPIRY = 1_700_001_000;
+const BINARY_STRIKE = 150_000_000;
+const BINARY_SETTLEMENT_PRICE = 160_000_000;
+const BINARY_PAYOUT = 100_000_000;
+// PriceHistory::LEN: 8 + 32 + 4 + 2 + 2 + MAX_OBSERVATIONS * 16 + 1
+const MAX_OBSERVATIONS = 96;
+const PRICE_HISTORY_LEN = 1_585;
+
+const discriminator = (name: string) =>
+    createHash("sha256").update(`account:${name}`).digest().subarray(0, 8);
+
+const rentExempt = (len: number) => (128 + len) * 6960;
+
+class Writer {
+    private buf: Buffer;
+    private offset = 0;
+
+    constructor(len: number) {
+        this.buf = Buffer.alloc(len);
+    }
+
+    bytes(data: Uint8Array) {
+        this.buf.set(data, this.offset);
+        this.offset += data.length;
+        return this;
+    }
+
+    u8(value: number) {
+        this.buf.writeUInt8(value, this.offset);
+        this.offset += 1;
+        return this;
+    }
+
+    u16(value: number) {
+        this.buf.writeUInt16LE(value, this.offset);
+        this.offset += 2;
+        return this;
+    }
+
+    u32(value: number) {
+        this.buf.writeUInt32LE(value, this.offset);
+        this.offset += 4;
+        return this;
+    }
+
+    u64(value: number | bigint) {
+        this.buf.writeBigUInt64LE(BigInt(value), this.offset);
+        this.offset += 8;
+        return this;
+    }
+
+    u128(value: number | bigint) {
+        this.u64(BigInt(value) & BigInt("0xffffffffffffffff"));
+        return this.u64(BigInt(value) >> BigInt(64));
+    }
+
+    i32(value: number) {
+        this.buf.writeInt32LE(value, this.offset);
+        this.offset += 4;
+        return this;
+    }
+
+    i64(value: number | bigint) {
+        this.buf.writeBigInt64LE(BigInt(value), this.offset);
+        this.offset += 8;
+        return this;
+    }
+
+    string(value: string) {
+        this.u32(value.length);
+        return this.bytes(Buffer.from(value));
+    }
+
+    done() {
+        return this.buf;
+    }
+}
+
+function writeFixture(name: string, address: PublicKey, owner: PublicKey, data: Buffer) {
+    const fixture = {
+        pubkey: address.toBase58(),
+        account: {
+            lamports: rentExempt(data.length),
+            data: [data.toString("base64"), "base64"],
+            owner: owner.toBase58(),
+            executable: false,
+            rentEpoch: 0,
+            space: data.length,
+        },
+    };
+    fs.writeFileSync(path.join(FIXTURES_DIR, `${name}.json`), JSON.stringify(fixture, null, 2) + "\n");
+    console.log(`${name}: ${address.toBase58()}`);
+}
+
+interface CurrentVaultParams {
+    assetId: string;
+    totalAssets: number;                        // Also total_shares, at 1 share per token
+    quoteMint: PublicKey | null;
+    totalQuoteAssets: number;
+    instantWithdrawFeeBps: number;
+    epochNotionalExposed: number;               // Backed by as much locked collateral
+    epochFirstExpiry: number;
+    performanceFeeBps?: number;
+    highWaterMark?: number;                     // 1 token per share if not given
+}
+
+// A vault in the current layout, at epoch 1 and owned by `authority`
+function currentVault(authority: PublicKey, underlyingMint: PublicKey, params: CurrentVaultParams) {
+    const [address, bump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), Buffer.from(params.assetId)],
+        VAULT_PROGRAM_ID
+    );
+    const [shareMint] = PublicKey.findProgramAddressSync(
+        [Buffer.from("shares"), address.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+    const [vaultTokenAccount] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault_tokens"), address.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+    const quoteTokenAccount = params.quoteMint
+        ? PublicKey.findProgramAddressSync([Buffer.from("vault_quote"), address.toBuffer()], VAULT_PROGRAM_ID)[0]
+        : PublicKey.default;
+
+    const data = new Writer(VAULT_LEN)
+        .bytes(discriminator("Vault"))
+        .bytes(authority.toBytes())
+        .string(params.assetId)
+        .bytes(underlyingMint.toBytes())
+        .bytes(shareMint.toBytes())
+        .bytes(vaultTokenAccount.toBytes())
+        .u64(params.totalAssets)                // total_assets
+        .u64(params.totalAssets)                // total_shares
+        .u64(1)                                 // epoch
+        .u16(7500)                              // utilization_cap_bps
+        .i64(1_700_000_000)                     // last_roll_timestamp
+        .u64(0)                                 // pending_withdrawals
+        .u64(params.epochNotionalExposed)
+        .u64(0)                                 // epoch_premium_earned
+        .u32(0)                                 // epoch_premium_per_token_bps
+        .u8(bump)
+        .u8(VAULT_VERSION)
+        .u8(0)                                  // epoch_settled
+        .u64(0)                                 // epoch_settlement_price
+        .u64(0)                                 // epoch_payout
+        .u8(6)                                  // underlying_decimals
+        .bytes((params.quoteMint ?? PublicKey.default).toBytes())
+        .bytes(quoteTokenAccount.toBytes())
+        .u64(params.totalQuoteAssets)
+        .u64(0)                                 // reserved_assets
+        .u64(0)                                 // reserved_quote_assets
+        .u16(params.instantWithdrawFeeBps)
+        .u64(0)                                 // pending_deposits
+        .bytes(authority.toBytes())             // fee_recipient
+        .u16(0)                                 // management_fee_bps
+        .u16(params.performanceFeeBps ?? 0)
+        .u64(params.highWaterMark ?? 1_000_000_000)
+        .u64(0)                                 // deposit_cap
+        .u64(0)                                 // user_deposit_cap
+        .u8(0)                                  // allowlist_enabled
+        .u64(0)                                 // epoch_min_strike
+        .u64(0)                                 // epoch_max_strike
+        .u64(1_000_000_000)                     // epoch_start_price_per_share
+        .u8(0)                                  // strategy: CoveredCall
+        .bytes(underlyingMint.toBytes())        // option_mint
+        .u8(6)                                  // option_decimals
+        .bytes(PublicKey.default.toBytes())     // oracle_asset_config
+        .u64(params.epochNotionalExposed)       // epoch_collateral
+        .bytes(PublicKey.default.toBytes())     // pending_authority
+        .bytes(authority.toBytes())             // keeper
+        .bytes(TOKEN_PROGRAM_ID.toBytes())      // share_token_program
+        .u64(0)                                 // epoch_settled_notional
+        .u32(0)                                 // open_auctions
+        .i64(params.epochFirstExpiry)
+        .u64(0)                                 // auction_collateral
+        .bytes(authority.toBytes())             // locked_collateral_payer
+        .u32(0)                                 // guarded_deposits
+        .done();
+    return { address, shareMint, vaultTokenAccount, data };
+}
+
+function main() {
+    fs.mkdirSync(FIXTURES_DIR, { recursive: true });
+
+    // The legacy authority also holds every share and filed the withdrawal request
+    const userPath = path.join(FIXTURES_DIR, "legacy-user.json");
+    const user = fs.existsSync(userPath)
+        ? Keypair.fromSecretKey(Uint8Array.from(JSON.parse(fs.readFileSync(userPath, "utf8"))))
+        : Keypair.generate();
+    fs.writeFileSync(userPath, JSON.stringify(Array.from(user.secretKey)) + "\n");
+
+    const underlyingMint = new PublicKey(
+        createHash("sha256").update("legacy-underlying-mint").digest()
+    );
+    const [vault, vaultBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), Buffer.from(LEGACY_ASSET_ID)],
+        VAULT_PROGRAM_ID
+    );
+    const [shareMint] = PublicKey.findProgramAddressSync(
+        [Buffer.from("shares"), vault.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+    const [vaultTokenAccount] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault_tokens"), vault.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+    const [withdrawalRequest, withdrawalBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("withdrawal"), vault.toBuffer(), user.publicKey.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+    const userShareAccount = new PublicKey(
+        createHash("sha256").update("legacy-user-shares").digest()
+    );
+
+    const vaultData = new Writer(LEGACY_VAULT_LEN)
+        .bytes(discriminator("Vault"))
+        .bytes(user.publicKey.toBytes())        // authority
+        .string(LEGACY_ASSET_ID)
+        .bytes(underlyingMint.toBytes())
+        .bytes(shareMint.toBytes())
+        .bytes(vaultTokenAccount.toBytes())
+        .u64(LEGACY_TOTAL_ASSETS)               // total_assets
+        .u64(LEGACY_TOTAL_ASSETS)               // total_shares
+        .u64(LEGACY_EPOCH)
+        .u16(8000)                              // utilization_cap_bps
+        .i64(1_700_000_000)                     // last_roll_timestamp
+        .u64(LEGACY_WITHDRAWAL_SHARES)          // pending_withdrawals
+        .u64(1_000_000)                         // epoch_notional_exposed
+        .u64(10_000)                            // epoch_premium_earned
+        .u32(100)                               // epoch_premium_per_token_bps
+        .u8(vaultBump)
+        .done();
+    writeFixture("legacy-vault", vault, VAULT_PROGRAM_ID, vaultData);
+
+    const withdrawalData = new Writer(LEGACY_WITHDRAWAL_LEN)
+        .bytes(discriminator("WithdrawalRequest"))
+        .bytes(user.publicKey.toBytes())
+        .bytes(vault.toBytes())
+        .u64(LEGACY_WITHDRAWAL_SHARES)
+        .u64(LEGACY_EPOCH)                      // request_epoch
+        .u8(0)                                  // processed
+        .u8(withdrawalBump)
+        .done();
+    writeFixture("legacy-withdrawal", withdrawalRequest, VAULT_PROGRAM_ID, withdrawalData);
+
+    const mint = (authority: PublicKey, supply: number) => {
+        const data = Buffer.alloc(MintLayout.span);
+        MintLayout.encode(
+            {
+                mintAuthorityOption: 1,
+                mintAuthority: authority,
+                supply: BigInt(supply),
+                decimals: 6,
+                isInitialized: true,
+                freezeAuthorityOption: 0,
+                freezeAuthority: PublicKey.default,
+            },
+            data
+        );
+        return data;
+    };
+    writeFixture("legacy-underlying-mint", underlyingMint, TOKEN_PROGRAM_ID, mint(user.publicKey, LEGACY_TOTAL_ASSETS));
+    writeFixture("legacy-share-mint", shareMint, TOKEN_PROGRAM_ID, mint(vault, LEGACY_TOTAL_ASSETS));
+
+    const tokenAccount = (mintKey: PublicKey, owner: PublicKey, amount: number) => {
+        const data = Buffer.alloc(AccountLayout.span);
+        AccountLayout.encode(
+            {
+                mint: mintKey,
+                owner,
+                amount: BigInt(amount),
+                delegateOption: 0,
+                delegate: PublicKey.default,
+                state: 1,
+                isNativeOption: 0,
+                isNative: BigInt(0),
+                delegatedAmount: BigInt(0),
+                closeAuthorityOption: 0,
+                closeAuthority: PublicKey.default,
+            },
+            data
+        );
+        return data;
+    };
+    writeFixture(
+        "legacy-vault-tokens",
+        vaultTokenAccount,
+        TOKEN_PROGRAM_ID,
+        tokenAccount(underlyingMint, vault, LEGACY_TOTAL_ASSETS)
+    );
+    writeFixture(
+        "legacy-user-shares",
+        userShareAccount,
+        TOKEN_PROGRAM_ID,
+        tokenAccount(shareMint, user.publicKey, LEGACY_TOTAL_ASSETS)
+    );
+
+    // The views only read the vault, so its mints and token accounts aren't written
+    const quoteMint = new PublicKey(createHash("sha256").update("quote-vault-quote-mint").digest());
+    const quoteVault = currentVault(user.publicKey, underlyingMint, {
+        assetId: QUOTE_ASSET_ID,
+        totalAssets: QUOTE_VAULT_ASSETS,
+        quoteMint,
+        totalQuoteAssets: QUOTE_VAULT_QUOTE_ASSETS,
+        instantWithdrawFeeBps: 50,
+        epochNotionalExposed: 0,
+        epochFirstExpiry: 0,
+    });
+    writeFixture("quote-vault", quoteVault.address, VAULT_PROGRAM_ID, quoteVault.data);
+
+    // NAV of 1 token per share against a 0.9 high-water mark, so a 10% performance fee
+    // has accrued since the roll
+    const feeVault = currentVault(user.publicKey, underlyingMint, {
+        assetId: FEE_ASSET_ID,
+        totalAssets: FEE_VAULT_ASSETS,
+        quoteMint: null,
+        totalQuoteAssets: 0,
+        instantWithdrawFeeBps: 0,
+        epochNotionalExposed: 0,
+        epochFirstExpiry: 0,
+        performanceFeeBps: 1_000,
+        highWaterMark: 900_000_000,
+    });
+    writeFixture("fee-vault", feeVault.address, VAULT_PROGRAM_ID, feeVault.data);
+
+    // max_withdraw only reads the vault and the user's share account
+    const expiredVault = currentVault(user.publicKey, underlyingMint, {
+        assetId: EXPIRED_ASSET_ID,
+        totalAssets: EXPIRED_VAULT_ASSETS,
+        quoteMint: null,
+        totalQuoteAssets: 0,
+        instantWithdrawFeeBps: 0,
+        epochNotionalExposed: EXPIRED_VAULT_NOTIONAL,
+        epochFirstExpiry: 1_700_000_000,
+    });
+    writeFixture("expired-vault", expiredVault.address, VAULT_PROGRAM_ID, expiredVault.data);
+    writeFixture(
+        "expired-vault-user-shares",
+        new PublicKey(createHash("sha256").update("expired-vault-user-shares").digest()),
+        TOKEN_PROGRAM_ID,
+        tokenAccount(expiredVault.shareMint, user.publicKey, EXPIRED_VAULT_ASSETS)
+    );
+
+    // A call struck at 110 on 1 token, recorded in epoch 1 and expired in 2023 without
+    // an oracle price to settle it. settle_rfq reads the vault, the RFQ, its record and
+    // the epoch's locked collateral; the legacy user is the maker.
+    const attestVault = currentVault(user.publicKey, underlyingMint, {
+        assetId: ATTEST_ASSET_ID,
+        totalAssets: ATTEST_VAULT_ASSETS,
+        quoteMint: null,
+        totalQuoteAssets: 0,
+        instantWithdrawFeeBps: 0,
+        epochNotionalExposed: ATTEST_NOTIONAL,
+        epochFirstExpiry: 1_700_000_000,
+    });
+    writeFixture("attest-vault", attestVault.address, VAULT_PROGRAM_ID, attestVault.data);
+
+    const [attestRfq, attestRfqBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("rfq"), new Writer(8).u64(ATTEST_RFQ_ID).done()],
+        RFQ_PROGRAM_ID
+    );
+    const rfqData = new Writer(RFQ_LEN)
+        .bytes(discriminator("Rfq"))
+        .u64(ATTEST_RFQ_ID)
+        .bytes(attestVault.address.toBytes())   // creator
+        .bytes(underlyingMint.toBytes())        // underlying
+        .u8(0)                                  // option_type: Call
+        .i64(1_700_000_000)                     // expiry_ts
+        .u64(ATTEST_STRIKE)
+        .u64(ATTEST_NOTIONAL)                   // notional_tokens
+        .u32(50)                                // premium_floor_per_token_bps
+        .i64(1_699_400_000)                     // valid_until_ts
+        .u8(0)                                  // settlement: Cash
+        .u64(100_000_000)                       // oracle_price
+        .i64(1_699_399_000)                     // oracle_ts
+        .u8(1)                                  // status: Filled
+        .bytes(user.publicKey.toBytes())        // filled_by
+        .u64(10_000)                            // filled_premium
+        .u32(100)                               // filled_premium_per_token_bps
+        .i64(1_699_399_000)                     // created_at
+        .u8(attestRfqBump)
+        .bytes(underlyingMint.toBytes())        // premium_mint
+        .bytes(attestVault.vaultTokenAccount.toBytes())
+        .u8(RFQ_VERSION)
+        .done();
+    writeFixture("attest-rfq", attestRfq, RFQ_PROGRAM_ID, rfqData);
+
+    const [recordedRfq, recordedRfqBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("recorded_rfq"), attestVault.address.toBuffer(), attestRfq.toBuffer()],
+        VAULT_PROGRAM_ID
+    );
+    const recordedRfqData = new Writer(RECORDED_RFQ_LEN)
+        .bytes(discriminator("RecordedRfq"))
+        .bytes(attestVault.address.toBytes())
+        .bytes(attestRfq.toBytes())
+        .u64(1)                                 // epoch
+        .u8(recordedRfqBump)
+        .u8(VAULT_VERSION)
+        .u8(0)                                  // settled
+        .u64(0)                                 // settlement_price
+        .u64(0)                                 // payout
+        .bytes(PublicKey.default.toBytes())     // maker_option_account
+        .done();
+    writeFixture("attest-recorded-rfq", recordedRfq, VAULT_PROGRAM_ID, recordedRfqData);
+
+    const [lockedCollateral] = PublicKey.findProgramAddressSync(
+        [Buffer.from("locked_collateral"), attestVault.address.toBuffer(), new Writer(8).u64(1).done()],
+        VAULT_PROGRAM_ID
+    );
+    writeFixture(
+        "attest-locked-collateral",
+        lockedCollateral,
+        TOKEN_PROGRAM_ID,
+        tokenAccount(underlyingMint, attestVault.address, ATTEST_NOTIONAL)
+    );
+
+    // Still open, so migrate_rfq has to pin a premium account before it can be filled
+    const [legacyRfq, legacyRfqBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("rfq"), new Writer(8).u64(LEGACY_RFQ_ID).done()],
+        RFQ_PROGRAM_ID
+    );
+    const legacyRfqData = new Writer(LEGACY_RFQ_LEN)
+        .bytes(discriminator("Rfq"))
+        .u64(LEGACY_RFQ_ID)
+        .bytes(user.publicKey.toBytes())        // creator
+        .bytes(underlyingMint.toBytes())        // underlying
+        .u8(0)                                  // option_type: Call
+        .i64(1_700_000_000)                     // expiry_ts
+        .u64(110_000_000)                       // strike
+        .u64(1_000_000)                         // notional_tokens
+        .u32(50)                                // premium_floor_per_token_bps
+        .i64(1_699_400_000)                     // valid_until_ts
+        .u8(0)                                  // settlement: Cash
+        .u64(100_000_000)                       // oracle_price
+        .i64(1_699_399_000)                     // oracle_ts
+        .u8(0)                                  // status: Open
+        .bytes(PublicKey.default.toBytes())     // filled_by
+        .u64(0)                                 // filled_premium
+        .u32(0)                                 // filled_premium_per_token_bps
+        .i64(1_699_399_000)                     // created_at
+        .u8(legacyRfqBump)
+        .done();
+    writeFixture("legacy-rfq", legacyRfq, RFQ_PROGRAM_ID, legacyRfqData);
+
+    // Unsold, so the buyer is None and the tail of the account is zero padding
+    const legacyCallUid = 1;
+    const [legacyCoveredCall] = PublicKey.findProgramAddressSync(
+        [
+            Buffer.from("covered_call"),
+            user.publicKey.toBuffer(),
+            underlyingMint.toBuffer(),
+            new Writer(8).u64(legacyCallUid).done(),
+        ],
+        OPTIONS_PROGRAM_ID
+    );
+    const coveredCallData = new Writer(LEGACY_COVERED_CALL_LEN)
+        .bytes(discriminator("CoveredCall"))
+        .bytes(user.publicKey.toBytes())        // seller
+        .u8(0)                                  // buyer: None
+        .bytes(underlyingMint.toBytes())        // xstock_mint
+        .bytes(quoteMint.toBytes())             // quote_mint
+        .u64(legacyCallUid)
+        .u64(150_000_000)                       // strike
+        .u64(5_000_000)                         // premium
+        .i64(1_700_000_000)                     // expiry_ts
+        .u64(1_000_000)                         // amount
+        .u8(0)                                  // exercised
+        .u8(0)                                  // buyer_exercised
+        .u8(0)                                  // cancelled
+        .u8(1)                                  // is_listed
+        .u64(5_000_000)                         // ask_price
+        .done();
+    writeFixture("legacy-covered-call", legacyCoveredCall, OPTIONS_PROGRAM_ID, coveredCallData);
+
+    // Sold to a buyer who holds only the token account the payoff goes to. The asset
+    // configs are created by the tests, so only their PDAs are needed here.
+    const averageBuyer = new PublicKey(createHash("sha256").update("average-call-buyer").digest());
+    const averages = [
+        { name: "average", uid: 2, assetId: AVERAGE_ASSET_ID, slots: [0, 1, 2, 3] },
+        { name: "gapped-average", uid: 5, assetId: GAPPED_AVERAGE_ASSET_ID, slots: [0, 1, 3] },
+    ];
+    for (const average of averages) {
+        const [averageAssetConfig] = PublicKey.findProgramAddressSync(
+            [Buffer.from("asset"), Buffer.from(average.assetId)],
+            ORACLE_PROGRAM_ID
+        );
+        const [averageCall] = PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("covered_call"),
+                user.publicKey.toBuffer(),
+                underlyingMint.toBuffer(),
+                new Writer(8).u64(average.uid).done(),
+            ],
+            OPTIONS_PROGRAM_ID
+        );
+        const [averageEscrow] = PublicKey.findProgramAddressSync(
+            [Buffer.from("vault"), averageCall.toBuffer()],
+            OPTIONS_PROGRAM_ID
+        );
+        const averageCallData = new Writer(COVERED_CALL_LEN)
+            .bytes(discriminator("CoveredCall"))
+            .bytes(user.publicKey.toBytes())    // seller
+            .u8(1)                              // buyer: Some
+            .bytes(averageBuyer.toBytes())
+            .bytes(underlyingMint.toBytes())    // xstock_mint
+            .bytes(quoteMint.toBytes())         // quote_mint
+            .u64(average.uid)
+            .u64(100_000_000)                   // strike, per unit
+            .u64(5_000_000)                     // premium
+            .i64(AVERAGE_EXPIRY)
+            .u64(AVERAGE_AMOUNT)
+            .u8(0)                              // exercised
+            .u8(0)                              // buyer_exercised
+            .u8(0)                              // cancelled
+            .u8(0)                              // is_listed
+            .u64(0)                             // ask_price
+            .u8(0)                              // barrier_type: None
+            .u64(0)                             // barrier
+            .bytes(averageAssetConfig.toBytes()) // oracle_asset
+            .u8(0)                              // barrier_triggered
+            .u8(0)                              // payoff: Physical
+            .i64(AVERAGE_WINDOW)
+            .u16(0)                             // royalty_bps
+            .i64(0)                             // averaged_until
+            .u128(0)                            // average_sum
+            .u16(0)                             // average_observations
+            .done();
+        writeFixture(`${average.name}-call`, averageCall, OPTIONS_PROGRAM_ID, averageCallData);
+        writeFixture(
+            `${average.name}-call-escrow`,
+            averageEscrow,
+            TOKEN_PROGRAM_ID,
+            tokenAccount(underlyingMint, averageEscrow, AVERAGE_AMOUNT)
+        );
+
+        // One observation per recorded 15 minute slot from the start of the window, 6 decimals
+        const [priceHistory, priceHistoryBump] = PublicKey.findProgramAddressSync(
+            [Buffer.from("history"), averageAssetConfig.toBuffer()],
+            ORACLE_PROGRAM_ID
+        );
+        const history = new Writer(PRICE_HISTORY_LEN)
+            .bytes(discriminator("PriceHistory"))
+            .bytes(averageAssetConfig.toBytes())
+            .i32(-6)                            // exponent
+            .u16(average.slots.length)          // head
+            .u16(average.slots.length);         // count
+        for (const slot of average.slots) {
+            history.i64(AVERAGE_PRICES[slot]).i64(AVERAGE_EXPIRY - AVERAGE_WINDOW + slot * 900);
+        }
+        for (let i = average.slots.length; i < MAX_OBSERVATIONS; i++) {
+            history.i64(0).i64(0);
+        }
+        writeFixture(
+            `${average.name}-price-history`,
+            priceHistory,
+            ORACLE_PROGRAM_ID,
+            history.u8(priceHistoryBump).done()
+        );
+    }
+    writeFixture(
+        "average-call-buyer-xstock",
+        new PublicKey(createHash("sha256").update("average-call-buyer-xstock").digest()),
+        TOKEN_PROGRAM_ID,
+        tokenAccount(underlyingMint, averageBuyer, 0)
+    );
+
+    // Both binaries are sold to the same buyer, who holds only the quote account the
+    // payout goes to; the seller's quote account receives it otherwise
+    const [binaryAssetConfig] = PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(BINARY_ASSET_ID)],
+        ORACLE_PROGRAM_ID
+    );
+    const binaryBuyer = new PublicKey(createHash("sha256").update("binary-buyer").digest());
+    const binaries = [
+        { name: "binary-call", uid: 3, payoff: 1 },   // DigitalCall, in the money
+        { name: "binary-put", uid: 4, payoff: 2 },    // DigitalPut, out of the money
+    ];
+    for (const binary of binaries) {
+        const [option] = PublicKey.findProgramAddressSync(
+            [
+                Buffer.from("covered_call"),
+                user.publicKey.toBuffer(),
+                underlyingMint.toBuffer(),
+                new Writer(8).u64(binary.uid).done(),
+            ],
+            OPTIONS_PROGRAM_ID
+        );
+        const [escrow] = PublicKey.findProgramAddressSync(
+            [Buffer.from("vault"), option.toBuffer()],
+            OPTIONS_PROGRAM_ID
+        );
+        const optionData = new Writer(COVERED_CALL_LEN)
+            .bytes(discriminator("CoveredCall"))
+            .bytes(user.publicKey.toBytes())    // seller
+            .u8(1)                              // buyer: Some
+            .bytes(binaryBuyer.toBytes())
+            .bytes(underlyingMint.toBytes())    // xstock_mint
+            .bytes(quoteMint.toBytes())         // quote_mint
+            .u64(binary.uid)
+            .u64(BINARY_STRIKE)                 // strike, per unit
+            .u64(5_000_000)                     // premium
+            .i64(BINARY_EXPIRY)
+            .u64(BINARY_PAYOUT)                 // amount
+            .u8(0)                              // exercised
+            .u8(0)                              // buyer_exercised
+            .u8(0)                              // cancelled
+            .u8(0)                              // is_listed
+            .u64(0)                             // ask_price
+            .u8(0)                              // barrier_type: None
+            .u64(0)                             // barrier
+            .bytes(binaryAssetConfig.toBytes()) // oracle_asset
+            .u8(0)                              // barrier_triggered
+            .u8(binary.payoff)
+            .done();
+        writeFixture(binary.name, option, OPTIONS_PROGRAM_ID, optionData);
+        writeFixture(
+            `${binary.name}-escrow`,
+            escrow,
+            TOKEN_PROGRAM_ID,
+            tokenAccount(quoteMint, escrow, BINARY_PAYOUT)
+        );
+    }
+    writeFixture(
+        "binary-buyer-quote",
+        new PublicKey(createHash("sha256").update("binary-buyer-quote").digest()),
+        TOKEN_PROGRAM_ID,
+        tokenAccount(quoteMint, binaryBuyer, 0)
+    );
+    writeFixture(
+        "binary-seller-quote",
+        new PublicKey(createHash("sha256").update("binary-seller-quote").digest()),
+        TOKEN_PROGRAM_ID,
+        tokenAccount(quoteMint, user.publicKey, 0)
+    );
+
+    const [binaryHistory, binaryHistoryBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("history"), binaryAssetConfig.toBuffer()],
+        ORACLE_PROGRAM_ID
+    );
+    const binaryHistoryData = new Writer(PRICE_HISTORY_LEN)
+        .bytes(discriminator("PriceHistory"))
+        .bytes(binaryAssetConfig.toBytes())
+        .i32(-6)                                // exponent
+        .u16(1)                                 // head
+        .u16(1)                                 // count
+        .i64(BINARY_SETTLEMENT_PRICE)
+        .i64(BINARY_EXPIRY);
+    for (let i = 1; i < MAX_OBSERVATIONS; i++) {
+        binaryHistoryData.i64(0).i64(0);
+    }
+    writeFixture(
+        "binary-price-history",
+        binaryHistory,
+        ORACLE_PROGRAM_ID,
+        binaryHistoryData.u8(binaryHistoryBump).done()
+    );
+}
+
+main();
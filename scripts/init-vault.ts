@@ -4,6 +4,7 @@ import { Vault } from "../target/types/vault";
 import { Connection, PublicKey, Keypair } from "@solana/web3.js";
 import {
     createMint,
+    getAssociatedTokenAddress,
     TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
 
@@ -41,6 +42,7 @@ async function main() {
     const assetId = assetIdArg ? assetIdArg.split("=")[1] : "NVDAx";
     const existingMintStr = mintArg ? mintArg.split("=")[1] : null;
     const utilizationCapBps = 8000; // 80% utilization cap
+    const seedAmount = 1_000; // MIN_SEED_DEPOSIT, locked in the vault; the wallet must hold it
 
     console.log(`\nPARAMS: Asset=${assetId}, Mint=${existingMintStr || "(Create New)"}`);
 
@@ -108,12 +110,13 @@ async function main() {
 
     try {
         const tx = await program.methods
-            .initializeVault(assetId, utilizationCapBps)
+            .initializeVault(assetId, utilizationCapBps, new anchor.BN(seedAmount))
             .accountsPartial({
                 vault: vaultPda,
                 underlyingMint: underlyingMint,
                 shareMint: shareMintPda,
                 vaultTokenAccount: vaultTokenAccountPda,
+                authorityTokenAccount: await getAssociatedTokenAddress(underlyingMint, wallet.publicKey),
                 authority: wallet.publicKey,
                 tokenProgram: TOKEN_PROGRAM_ID,
                 systemProgram: anchor.web3.SystemProgram.programId,
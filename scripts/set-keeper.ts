@@ -0,0 +1,68 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Vault } from "../target/types/vault";
+import { Connection, PublicKey } from "@solana/web3.js";
+
+async function main() {
+    const connection = new Connection("https://api.devnet.solana.com", "confirmed");
+    const wallet = anchor.Wallet.local();
+    const provider = new anchor.AnchorProvider(connection, wallet, {
+        commitment: "confirmed",
+    });
+    anchor.setProvider(provider);
+
+    const program = new Program(
+        require("../target/idl/vault.json"),
+        provider
+    ) as Program<Vault>;
+
+    const args = process.argv.slice(2);
+    const assetIdArg = args.find(arg => arg.startsWith("--asset="));
+    const keeperArg = args.find(arg => arg.startsWith("--keeper="));
+
+    const assetId = assetIdArg ? assetIdArg.split("=")[1] : "NVDAx";
+    if (!keeperArg) {
+        console.error("Usage: set-keeper.ts --keeper=<pubkey> [--asset=NVDAx]");
+        process.exit(1);
+    }
+    const keeper = new PublicKey(keeperArg.split("=")[1]);
+
+    console.log("=================================");
+    console.log("Set Keeper Script");
+    console.log("=================================");
+    console.log(`Asset: ${assetId}`);
+    console.log(`Keeper: ${keeper.toBase58()}`);
+
+    const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), Buffer.from(assetId)],
+        program.programId
+    );
+
+    try {
+        // Must be signed by the vault authority
+        const tx = await program.methods
+            .setKeeper(keeper)
+            .accountsPartial({
+                vault: vaultPda,
+                authority: wallet.publicKey,
+            })
+            .rpc();
+
+        console.log("\n✅ Keeper updated successfully!");
+        console.log("Transaction Signature:", tx);
+    } catch (e: any) {
+        console.error("\n❌ Error setting keeper:");
+        console.error(e);
+        if (e.logs) {
+            console.error("\nLogs:");
+            e.logs.forEach((log: string) => console.log(log));
+        }
+    }
+}
+
+main()
+    .then(() => process.exit(0))
+    .catch((error) => {
+        console.error(error);
+        process.exit(1);
+    });
@@ -0,0 +1,99 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Vault } from "../target/types/vault";
+import { Connection, PublicKey } from "@solana/web3.js";
+import { TOKEN_2022_PROGRAM_ID } from "@solana/spl-token";
+
+// Metaplex Token Metadata Program ID
+const TOKEN_METADATA_PROGRAM_ID = new PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+const GITHUB_RAW_BASE = "https://raw.githubusercontent.com/feeniks01/xstock-options/main/app/public";
+
+async function main() {
+    const connection = new Connection("https://api.devnet.solana.com", "confirmed");
+    const wallet = anchor.Wallet.local();
+    const provider = new anchor.AnchorProvider(connection, wallet, {
+        commitment: "confirmed",
+    });
+    anchor.setProvider(provider);
+
+    const program = new Program(
+        require("../target/idl/vault.json"),
+        provider
+    ) as Program<Vault>;
+
+    const args = process.argv.slice(2);
+    const getArg = (name: string) => args.find(arg => arg.startsWith(`--${name}=`))?.split("=").slice(1).join("=");
+
+    const assetId = getArg("asset") ?? "NVDAx";
+    const name = getArg("name") ?? "Vault NVDAx";
+    const symbol = getArg("symbol") ?? "vNVDAx";
+    const uri = getArg("uri") ?? `${GITHUB_RAW_BASE}/metadata/vnvdax.json`;
+
+    console.log("=================================");
+    console.log("Update Share Metadata Script");
+    console.log("=================================");
+    console.log(`Asset: ${assetId}`);
+    console.log(`Name: ${name}`);
+    console.log(`Symbol: ${symbol}`);
+    console.log(`URI: ${uri}`);
+
+    const [vaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), Buffer.from(assetId)],
+        program.programId
+    );
+    const vault = await program.account.vault.fetch(vaultPda);
+
+    // Token-2022 share mints carry their own metadata, SPL Token ones use Metaplex
+    const onMint = vault.shareTokenProgram.equals(TOKEN_2022_PROGRAM_ID);
+    const [metadataPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("metadata"), TOKEN_METADATA_PROGRAM_ID.toBuffer(), vault.shareMint.toBuffer()],
+        TOKEN_METADATA_PROGRAM_ID
+    );
+
+    try {
+        // Metaplex metadata created before the vault PDA became its update authority
+        // is handed over first, signed by its current update authority
+        if (args.includes("--hand-over") && !onMint) {
+            const handOverTx = await program.methods
+                .handOverShareMetadata()
+                .accountsPartial({
+                    vault: vaultPda,
+                    shareMint: vault.shareMint,
+                    metadata: metadataPda,
+                    updateAuthority: wallet.publicKey,
+                })
+                .rpc();
+            console.log("\n✅ Update authority handed over to the vault");
+            console.log("Transaction Signature:", handOverTx);
+        }
+
+        // Must be signed by the vault authority
+        const tx = await program.methods
+            .updateShareMetadata(name, symbol, uri)
+            .accountsPartial({
+                vault: vaultPda,
+                authority: wallet.publicKey,
+                metadata: onMint ? null : metadataPda,
+                tokenMetadataProgram: onMint ? null : TOKEN_METADATA_PROGRAM_ID,
+            })
+            .rpc();
+
+        console.log("\n✅ Share metadata updated successfully!");
+        console.log("Transaction Signature:", tx);
+    } catch (e: any) {
+        console.error("\n❌ Error updating share metadata:");
+        console.error(e);
+        if (e.logs) {
+            console.error("\nLogs:");
+            e.logs.forEach((log: string) => console.log(log));
+        }
+    }
+}
+
+main()
+    .then(() => process.exit(0))
+    .catch((error) => {
+        console.error(error);
+        process.exit(1);
+    });
@@ -0,0 +1,88 @@
+import * as anchor from "@coral-xyz/anchor";
+
+// The oracle program reads a Pyth price from fixed offsets of any account, so the
+// tests stand one up in a BPF upgradeable loader buffer: its data can be rewritten
+// by the buffer authority at any time, which lets a test move the price.
+const LOADER_ID = new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+// Buffer state: enum tag (u32) then the authority as Option<Pubkey>
+const BUFFER_HEADER_LEN = 37;
+// price (i64), conf (u64), expo (i32), padding, publish_time (i64)
+const PRICE_OFFSET = 208;
+const PRICE_LEN = 32;
+
+/**
+ * Create a price account the provider's wallet can update with setMockPythPrice
+ */
+export async function createMockPythAccount(
+  provider: anchor.AnchorProvider
+): Promise<anchor.web3.PublicKey> {
+  const buffer = anchor.web3.Keypair.generate();
+  const space = BUFFER_HEADER_LEN + PRICE_OFFSET + PRICE_LEN;
+  const lamports = await provider.connection.getMinimumBalanceForRentExemption(space);
+
+  const initializeBuffer = new anchor.web3.TransactionInstruction({
+    programId: LOADER_ID,
+    keys: [
+      { pubkey: buffer.publicKey, isSigner: false, isWritable: true },
+      { pubkey: provider.wallet.publicKey, isSigner: false, isWritable: false },
+    ],
+    data: Buffer.from([0, 0, 0, 0]),
+  });
+
+  await provider.sendAndConfirm(
+    new anchor.web3.Transaction().add(
+      anchor.web3.SystemProgram.createAccount({
+        fromPubkey: provider.wallet.publicKey,
+        newAccountPubkey: buffer.publicKey,
+        lamports,
+        space,
+        programId: LOADER_ID,
+      }),
+      initializeBuffer
+    ),
+    [buffer]
+  );
+  return buffer.publicKey;
+}
+
+/**
+ * Write a price with 6 decimals and no confidence interval, published at
+ * `publishTime` (defaults to the validator's clock, so the reading is fresh)
+ */
+export async function setMockPythPrice(
+  provider: anchor.AnchorProvider,
+  account: anchor.web3.PublicKey,
+  price: number,
+  publishTime?: number
+) {
+  if (publishTime === undefined) {
+    const slot = await provider.connection.getSlot();
+    publishTime = (await provider.connection.getBlockTime(slot)) ?? Math.floor(Date.now() / 1000);
+  }
+
+  const fields = Buffer.alloc(PRICE_LEN);
+  fields.writeBigInt64LE(BigInt(price), 0);
+  fields.writeBigUInt64LE(BigInt(0), 8);
+  fields.writeInt32LE(-6, 16);
+  fields.writeBigInt64LE(BigInt(publishTime), 24);
+
+  // Write { offset: u32, bytes: Vec<u8> }, offset relative to the buffer header
+  const data = Buffer.alloc(4 + 4 + 8 + PRICE_LEN);
+  data.writeUInt32LE(1, 0);
+  data.writeUInt32LE(PRICE_OFFSET - BUFFER_HEADER_LEN, 4);
+  data.writeBigUInt64LE(BigInt(PRICE_LEN), 8);
+  fields.copy(data, 16);
+
+  await provider.sendAndConfirm(
+    new anchor.web3.Transaction().add(
+      new anchor.web3.TransactionInstruction({
+        programId: LOADER_ID,
+        keys: [
+          { pubkey: account, isSigner: false, isWritable: true },
+          { pubkey: provider.wallet.publicKey, isSigner: true, isWritable: false },
+        ],
+        data,
+      })
+    )
+  );
+}
@@ -1,6 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Oracle } from "../target/types/oracle";
+import { createMint } from "@solana/spl-token";
 import { expect } from "chai";
 
 describe("oracle (Pyth integration)", () => {
@@ -72,6 +73,82 @@ describe("oracle (Pyth integration)", () => {
         }
     });
 
+    it("registers the mint an asset prices", async () => {
+        const mint = await createMint(
+            provider.connection,
+            (provider.wallet as anchor.Wallet).payer,
+            authority.publicKey,
+            null,
+            6
+        );
+        const [assetMintPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("asset_mint"), assetConfigPda.toBuffer()],
+            program.programId
+        );
+
+        await program.methods
+            .registerAssetMint()
+            .accounts({ assetConfig: assetConfigPda, mint })
+            .rpc();
+
+        const assetMint = await program.account.assetMint.fetch(assetMintPda);
+        expect(assetMint.assetConfig.toString()).to.equal(assetConfigPda.toString());
+        expect(assetMint.mint.toString()).to.equal(mint.toString());
+    });
+
+    it("initializes the price history", async () => {
+        const [priceHistoryPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("history"), assetConfigPda.toBuffer()],
+            program.programId
+        );
+
+        const tx = await program.methods
+            .initializePriceHistory()
+            .accounts({
+                assetConfig: assetConfigPda,
+            })
+            .rpc();
+
+        console.log("Initialize price history tx:", tx);
+
+        const history = await program.account.priceHistory.fetch(priceHistoryPda);
+        expect(history.assetConfig.toString()).to.equal(assetConfigPda.toString());
+        expect(history.count).to.equal(0);
+        expect(history.head).to.equal(0);
+    });
+
+    it("rejects averages without observations", async () => {
+        const now = Math.floor(Date.now() / 1000);
+
+        try {
+            await program.methods
+                .getAveragePrice(new anchor.BN(now - 3600), new anchor.BN(now))
+                .accounts({
+                    assetConfig: assetConfigPda,
+                })
+                .view();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("InsufficientObservations");
+        }
+    });
+
+    it("rejects a settlement price for a slot with no observation", async () => {
+        const now = Math.floor(Date.now() / 1000);
+
+        try {
+            await program.methods
+                .getSettlementPrice(new anchor.BN(now))
+                .accounts({
+                    assetConfig: assetConfigPda,
+                })
+                .view();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("ObservationMissing");
+        }
+    });
+
     // Note: get_price instruction requires an actual Pyth price account with valid data
     // For testing on devnet, use a real Pyth price account like:
     // NVDAX: Feed ID 0x4244d07890e4610f46bbde67de8f43a4bf8b569eebe904f136b469f148503b7f
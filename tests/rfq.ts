@@ -79,6 +79,38 @@ describe("rfq", () => {
         );
     });
 
+    // Create a call RFQ struck at $150 on 10 tokens, paying its premium into `premiumAccount`
+    async function createRfq(premiumAccount: anchor.web3.PublicKey): Promise<anchor.web3.PublicKey> {
+        const config = await program.account.config.fetch(configPda);
+        const [pda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("rfq"), config.rfqCount.toArrayLike(Buffer, "le", 8)],
+            program.programId
+        );
+        const now = Math.floor(Date.now() / 1000);
+
+        await program.methods
+            .createRfq({
+                underlying: anchor.web3.Keypair.generate().publicKey,
+                optionType: { call: {} },
+                expiryTs: new anchor.BN(now + 86400),
+                strike: new anchor.BN(150_000_000),
+                notionalTokens: new anchor.BN(10_000_000),
+                premiumFloorPerTokenBps: 500,
+                validUntilTs: new anchor.BN(now + 300),
+                settlement: { cash: {} },
+                oraclePrice: new anchor.BN(145_000_000),
+                oracleTs: new anchor.BN(now),
+            })
+            .accountsPartial({
+                config: configPda,
+                rfq: pda,
+                creator: authority.publicKey,
+                premiumAccount,
+            })
+            .rpc();
+        return pda;
+    }
+
     it("initializes the RFQ config", async () => {
         const tx = await program.methods
             .initialize()
@@ -129,7 +161,7 @@ describe("rfq", () => {
         const oracleTs = new anchor.BN(Math.floor(Date.now() / 1000));
 
         const tx = await program.methods
-            .createRfq(
+            .createRfq({
                 underlying,
                 optionType,
                 expiryTs,
@@ -139,12 +171,13 @@ describe("rfq", () => {
                 validUntilTs,
                 settlement,
                 oraclePrice,
-                oracleTs
-            )
+                oracleTs,
+            })
             .accountsPartial({
                 config: configPda,
                 rfq: rfqPda,
                 creator: authority.publicKey,
+                premiumAccount: creatorTokenAccount,
             })
             .rpc();
 
@@ -155,6 +188,7 @@ describe("rfq", () => {
         expect(rfq.strike.toNumber()).to.equal(150_000_000);
         expect(rfq.premiumFloorPerTokenBps).to.equal(500);
         expect(rfq.status.open).to.exist;
+        expect(rfq.version).to.equal(2);
     });
 
     it("fills an RFQ", async () => {
@@ -183,4 +217,110 @@ describe("rfq", () => {
         const makerAccount = await program.account.makerAccount.fetch(makerAccountPda);
         expect(makerAccount.totalFills.toNumber()).to.equal(1);
     });
+
+    it("rejects a fill paying premium into an account other than the RFQ's", async () => {
+        const otherRfqPda = await createRfq(creatorTokenAccount);
+
+        try {
+            await program.methods
+                .fillRfq(600)
+                .accountsPartial({
+                    config: configPda,
+                    rfq: otherRfqPda,
+                    makerAccount: makerAccountPda,
+                    creatorTokenAccount: makerTokenAccount,
+                    makerTokenAccount: makerTokenAccount,
+                    maker: makerKeypair.publicKey,
+                })
+                .signers([makerKeypair])
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("InvalidCreatorAccount");
+        }
+    });
+
+    it("rejects a premium account the creator does not own", async () => {
+        try {
+            await createRfq(makerTokenAccount);
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("InvalidCreatorAccount");
+        }
+    });
+
+    describe("migration", () => {
+        // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts: an open RFQ
+        // created by legacy-user before the premium account was pinned
+        const legacyUser = anchor.web3.Keypair.fromSecretKey(
+            Uint8Array.from(require("./fixtures/legacy-user.json"))
+        );
+        const legacyRfqPda = new anchor.web3.PublicKey("5MuEHhLtjekECEujXDxiJAdz2GzkRRGaU1bHHBgMrwbN");
+        let legacyPremiumAccount: anchor.web3.PublicKey;
+
+        before(async () => {
+            await provider.connection.confirmTransaction(
+                await provider.connection.requestAirdrop(legacyUser.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+            );
+            legacyPremiumAccount = (
+                await getOrCreateAssociatedTokenAccount(
+                    provider.connection,
+                    (provider.wallet as anchor.Wallet).payer,
+                    usdcMint,
+                    legacyUser.publicKey
+                )
+            ).address;
+        });
+
+        it("only lets the creator migrate an RFQ", async () => {
+            try {
+                await program.methods
+                    .migrateRfq()
+                    .accounts({
+                        rfq: legacyRfqPda,
+                        premiumAccount: creatorTokenAccount,
+                        creator: authority.publicKey,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("NotCreator");
+            }
+        });
+
+        it("grows an old RFQ to the current layout and pins its premium account", async () => {
+            await program.methods
+                .migrateRfq()
+                .accounts({
+                    rfq: legacyRfqPda,
+                    premiumAccount: legacyPremiumAccount,
+                    creator: legacyUser.publicKey,
+                })
+                .signers([legacyUser])
+                .rpc();
+
+            const rfq = await program.account.rfq.fetch(legacyRfqPda);
+            expect(rfq.id.toNumber()).to.equal(1_000_001);
+            expect(rfq.strike.toNumber()).to.equal(110_000_000);
+            expect(rfq.status.open).to.exist;
+            expect(rfq.premiumMint.toBase58()).to.equal(usdcMint.toBase58());
+            expect(rfq.premiumAccount.toBase58()).to.equal(legacyPremiumAccount.toBase58());
+            expect(rfq.version).to.equal(2);
+
+            try {
+                await program.methods
+                    .migrateRfq()
+                    .accounts({
+                        rfq: legacyRfqPda,
+                        premiumAccount: legacyPremiumAccount,
+                        creator: legacyUser.publicKey,
+                    })
+                    .signers([legacyUser])
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("RfqAlreadyMigrated");
+            }
+        });
+    });
 });
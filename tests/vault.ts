@@ -1,8 +1,20 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Vault } from "../target/types/vault";
-import { TOKEN_PROGRAM_ID, createMint, getOrCreateAssociatedTokenAccount, mintTo } from "@solana/spl-token";
+import { Oracle } from "../target/types/oracle";
+import { Rfq } from "../target/types/rfq";
+import {
+    TOKEN_2022_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+    createMint,
+    getAccount,
+    getOrCreateAssociatedTokenAccount,
+    getTokenMetadata,
+    mintTo,
+    transfer,
+} from "@solana/spl-token";
 import { expect } from "chai";
+import { createMockPythAccount, setMockPythPrice } from "./mock-pyth";
 
 describe("vault", () => {
     const provider = anchor.AnchorProvider.env();
@@ -11,10 +23,25 @@ describe("vault", () => {
     const program = anchor.workspace.Vault as Program<Vault>;
     const authority = provider.wallet;
     const assetId = "NVDAx";
+    const SEED_AMOUNT = 1_000; // MIN_SEED_DEPOSIT
+
+    // RFQ 0 is created by the wallet and filled in the rfq tests
+    const rfqProgram = anchor.workspace.Rfq as Program<Rfq>;
+    const [foreignRfqPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("rfq"), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        rfqProgram.programId
+    );
+
+    const deriveEpochRecordPda = (epoch: number) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("epoch_record"), vaultPda.toBuffer(), new anchor.BN(epoch).toArrayLike(Buffer, "le", 8)],
+            program.programId
+        )[0];
 
     let vaultPda: anchor.web3.PublicKey;
     let shareMintPda: anchor.web3.PublicKey;
     let vaultTokenAccountPda: anchor.web3.PublicKey;
+    let withdrawalSharesPda: anchor.web3.PublicKey;
     let underlyingMint: anchor.web3.PublicKey;
     let userTokenAccount: anchor.web3.PublicKey;
     let userShareAccount: anchor.web3.PublicKey;
@@ -36,6 +63,11 @@ describe("vault", () => {
             program.programId
         );
 
+        [withdrawalSharesPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("withdrawal_shares"), vaultPda.toBuffer()],
+            program.programId
+        );
+
         // Create underlying token mint
         underlyingMint = await createMint(
             provider.connection,
@@ -69,23 +101,40 @@ describe("vault", () => {
         const utilizationCapBps = 7500; // 75%
 
         const tx = await program.methods
-            .initializeVault(assetId, utilizationCapBps)
+            .initializeVault(assetId, utilizationCapBps, new anchor.BN(SEED_AMOUNT))
             .accounts({
                 underlyingMint: underlyingMint,
+                authorityTokenAccount: userTokenAccount,
             })
             .rpc();
 
         console.log("Initialize vault tx:", tx);
 
+        // The seed deposit's shares are locked in the vault
         const vault = await program.account.vault.fetch(vaultPda);
         expect(vault.assetId).to.equal(assetId);
         expect(vault.utilizationCapBps).to.equal(utilizationCapBps);
-        expect(vault.totalAssets.toNumber()).to.equal(0);
-        expect(vault.totalShares.toNumber()).to.equal(0);
+        expect(vault.totalAssets.toNumber()).to.equal(SEED_AMOUNT);
+        expect(vault.totalShares.toNumber()).to.equal(SEED_AMOUNT);
         expect(vault.epoch.toNumber()).to.equal(0);
     });
 
-    it("deposits tokens and mints shares", async () => {
+    it("rejects a seed below the minimum", async () => {
+        try {
+            await program.methods
+                .initializeVault("TSLAx", 7500, new anchor.BN(SEED_AMOUNT - 1))
+                .accounts({
+                    underlyingMint: underlyingMint,
+                    authorityTokenAccount: userTokenAccount,
+                })
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("SeedTooSmall");
+        }
+    });
+
+    it("queues a deposit and mints shares at the roll", async () => {
         // Create user's share account
         const userShareAccountInfo = await getOrCreateAssociatedTokenAccount(
             provider.connection,
@@ -97,8 +146,31 @@ describe("vault", () => {
 
         const depositAmount = new anchor.BN(100_000_000); // 100 tokens
 
+        const [depositReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("deposit"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+
+        // Asking for more shares than the current price gives fails
+        try {
+            await program.methods
+                .deposit(depositAmount, new anchor.BN(100_000_001))
+                .accountsPartial({
+                    vault: vaultPda,
+                    shareMint: shareMintPda,
+                    vaultTokenAccount: vaultTokenAccountPda,
+                    userTokenAccount: userTokenAccount,
+                    userShareAccount: userShareAccount,
+                    user: authority.publicKey,
+                })
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("SlippageExceeded");
+        }
+
         const tx = await program.methods
-            .deposit(depositAmount)
+            .deposit(depositAmount, depositAmount)
             .accountsPartial({
                 vault: vaultPda,
                 shareMint: shareMintPda,
@@ -111,28 +183,98 @@ describe("vault", () => {
 
         console.log("Deposit tx:", tx);
 
+        // Shares are issued at the roll, which checks the deposit's minimum
+        await program.methods
+            .advanceEpoch()
+            .accountsPartial({
+                vault: vaultPda,
+                epochRecord: deriveEpochRecordPda(0),
+                authority: authority.publicKey,
+                vaultTokenAccount: vaultTokenAccountPda,
+            })
+            .remainingAccounts([{ pubkey: depositReceiptPda, isSigner: false, isWritable: true }])
+            .rpc();
+
+        await program.methods
+            .claimShares()
+            .accountsPartial({
+                vault: vaultPda,
+                depositReceipt: depositReceiptPda,
+                epochRecord: deriveEpochRecordPda(0),
+                shareMint: shareMintPda,
+                userShareAccount: userShareAccount,
+                userTokenAccount: userTokenAccount,
+                user: authority.publicKey,
+            })
+            .rpc();
+
         const vault = await program.account.vault.fetch(vaultPda);
-        expect(vault.totalAssets.toNumber()).to.equal(100_000_000);
-        expect(vault.totalShares.toNumber()).to.equal(100_000_000); // 1:1 for first deposit
+        expect(vault.totalAssets.toNumber()).to.equal(100_001_000);
+        expect(vault.totalShares.toNumber()).to.equal(100_001_000); // 1:1 with the seed
+
+        const shares = await provider.connection.getTokenAccountBalance(userShareAccount);
+        expect(Number(shares.value.amount)).to.equal(100_000_000);
     });
 
     it("advances epoch with premium", async () => {
-        const premiumEarned = new anchor.BN(5_000_000); // 5 tokens premium
+        // Premium lands in the vault token account before the roll
+        await transfer(
+            provider.connection,
+            (provider.wallet as anchor.Wallet).payer,
+            userTokenAccount,
+            vaultTokenAccountPda,
+            authority.publicKey,
+            5_000_000 // 5 tokens premium
+        );
 
         const tx = await program.methods
-            .advanceEpoch(premiumEarned)
+            .advanceEpoch()
             .accountsPartial({
                 vault: vaultPda,
+                epochRecord: deriveEpochRecordPda(1),
                 authority: authority.publicKey,
+                vaultTokenAccount: vaultTokenAccountPda,
             })
             .rpc();
 
         console.log("Advance epoch tx:", tx);
 
         const vault = await program.account.vault.fetch(vaultPda);
-        expect(vault.epoch.toNumber()).to.equal(1);
-        expect(vault.totalAssets.toNumber()).to.equal(105_000_000); // Original + premium
-        expect(vault.totalShares.toNumber()).to.equal(100_000_000); // Shares unchanged
+        expect(vault.epoch.toNumber()).to.equal(2);
+        expect(vault.totalAssets.toNumber()).to.equal(105_001_000); // Original + premium
+        expect(vault.totalShares.toNumber()).to.equal(100_001_000); // Shares unchanged
+
+        const record = await program.account.epochRecord.fetch(deriveEpochRecordPda(1));
+        expect(record.pricePerShare.toNumber()).to.equal(1_049_999_500); // ~1.05 scaled by 1e9
+    });
+
+    it("refuses to settle an epoch with nothing sold", async () => {
+        const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+        const [assetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("asset"), Buffer.from(assetId)],
+            oracleProgram.programId
+        );
+        if (!(await provider.connection.getAccountInfo(assetConfigPda))) {
+            await oracleProgram.methods
+                .initializeAsset(assetId, anchor.web3.Keypair.generate().publicKey)
+                .rpc();
+        }
+
+        try {
+            await program.methods
+                .settleEpoch()
+                .accountsPartial({
+                    vault: vaultPda,
+                    authority: authority.publicKey,
+                    lockedCollateralPayer: null,
+                    vaultTokenAccount: vaultTokenAccountPda,
+                    lockedCollateralAccount: null,
+                })
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("NothingToSettle");
+        }
     });
 
     it("requests withdrawal", async () => {
@@ -148,6 +290,7 @@ describe("vault", () => {
             .accountsPartial({
                 vault: vaultPda,
                 withdrawalRequest: withdrawalPda,
+                withdrawalShares: withdrawalSharesPda,
                 userShareAccount: userShareAccount,
                 user: authority.publicKey,
             })
@@ -157,7 +300,1878 @@ describe("vault", () => {
 
         const withdrawal = await program.account.withdrawalRequest.fetch(withdrawalPda);
         expect(withdrawal.shares.toNumber()).to.equal(50_000_000);
-        expect(withdrawal.requestEpoch.toNumber()).to.equal(1);
+        expect(withdrawal.requestEpoch.toNumber()).to.equal(2);
         expect(withdrawal.processed).to.be.false;
+
+        // Shares are escrowed in the vault
+        const escrow = await provider.connection.getTokenAccountBalance(withdrawalSharesPda);
+        expect(Number(escrow.value.amount)).to.equal(50_000_000);
+    });
+
+    it("increases a withdrawal request", async () => {
+        const [withdrawalPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("withdrawal"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+
+        await program.methods
+            .increaseWithdrawal(new anchor.BN(10_000_000))
+            .accountsPartial({
+                vault: vaultPda,
+                withdrawalRequest: withdrawalPda,
+                withdrawalShares: withdrawalSharesPda,
+                userShareAccount: userShareAccount,
+                user: authority.publicKey,
+            })
+            .rpc();
+
+        const withdrawal = await program.account.withdrawalRequest.fetch(withdrawalPda);
+        expect(withdrawal.shares.toNumber()).to.equal(60_000_000);
+
+        const vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.pendingWithdrawals.toNumber()).to.equal(60_000_000);
+    });
+
+    it("cancels a withdrawal request", async () => {
+        const [withdrawalPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("withdrawal"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+
+        await program.methods
+            .cancelWithdrawal()
+            .accountsPartial({
+                vault: vaultPda,
+                withdrawalRequest: withdrawalPda,
+                withdrawalShares: withdrawalSharesPda,
+                userShareAccount: userShareAccount,
+                user: authority.publicKey,
+            })
+            .rpc();
+
+        const vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.pendingWithdrawals.toNumber()).to.equal(0);
+
+        const shares = await provider.connection.getTokenAccountBalance(userShareAccount);
+        expect(Number(shares.value.amount)).to.equal(100_000_000);
+
+        // Request account is closed so a new one can be filed
+        expect(await provider.connection.getAccountInfo(withdrawalPda)).to.be.null;
+    });
+
+    it("redeems at the closing price of the request epoch", async () => {
+        const [withdrawalPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("withdrawal"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+
+        await program.methods
+            .requestWithdrawal(new anchor.BN(50_000_000))
+            .accountsPartial({
+                vault: vaultPda,
+                withdrawalRequest: withdrawalPda,
+                withdrawalShares: withdrawalSharesPda,
+                userShareAccount: userShareAccount,
+                user: authority.publicKey,
+            })
+            .rpc();
+
+        await program.methods
+            .advanceEpoch()
+            .accountsPartial({
+                vault: vaultPda,
+                epochRecord: deriveEpochRecordPda(2),
+                authority: authority.publicKey,
+                vaultTokenAccount: vaultTokenAccountPda,
+            })
+            .rpc();
+
+        // Half the user's shares are set aside at the ~1.05 closing price
+        let vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.reservedAssets.toNumber()).to.equal(52_499_975);
+        expect(vault.totalAssets.toNumber()).to.equal(52_501_025);
+        expect(vault.totalShares.toNumber()).to.equal(50_001_000);
+
+        const balanceBefore = await provider.connection.getTokenAccountBalance(userTokenAccount);
+
+        await program.methods
+            .processWithdrawal()
+            .accountsPartial({
+                vault: vaultPda,
+                withdrawalRequest: withdrawalPda,
+                epochRecord: deriveEpochRecordPda(2),
+                shareMint: shareMintPda,
+                withdrawalShares: withdrawalSharesPda,
+                vaultTokenAccount: vaultTokenAccountPda,
+                userTokenAccount: userTokenAccount,
+                user: authority.publicKey,
+            })
+            .rpc();
+
+        const balanceAfter = await provider.connection.getTokenAccountBalance(userTokenAccount);
+        expect(Number(balanceAfter.value.amount) - Number(balanceBefore.value.amount)).to.equal(52_499_975);
+
+        vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.reservedAssets.toNumber()).to.equal(0);
+    });
+
+    it("initializes a quote account for USDC premium", async () => {
+        const quoteMint = await createMint(
+            provider.connection,
+            (provider.wallet as anchor.Wallet).payer,
+            authority.publicKey,
+            null,
+            6 // USDC decimals
+        );
+
+        const [quoteTokenAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("vault_quote"), vaultPda.toBuffer()],
+            program.programId
+        );
+
+        await program.methods
+            .initializeQuoteAccount()
+            .accountsPartial({
+                vault: vaultPda,
+                quoteMint,
+                quoteTokenAccount: quoteTokenAccountPda,
+                authority: authority.publicKey,
+            })
+            .rpc();
+
+        const vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.quoteMint.toString()).to.equal(quoteMint.toString());
+        expect(vault.quoteTokenAccount.toString()).to.equal(quoteTokenAccountPda.toString());
+        expect(vault.totalQuoteAssets.toNumber()).to.equal(0);
+    });
+
+    it("instantly withdraws idle assets for a fee", async () => {
+        await program.methods
+            .setInstantWithdrawFee(50) // 0.5%
+            .accountsPartial({
+                vault: vaultPda,
+                authority: authority.publicKey,
+            })
+            .rpc();
+
+        const balanceBefore = await provider.connection.getTokenAccountBalance(userTokenAccount);
+
+        await program.methods
+            .instantWithdraw(new anchor.BN(10_000_000))
+            .accountsPartial({
+                vault: vaultPda,
+                shareMint: shareMintPda,
+                vaultTokenAccount: vaultTokenAccountPda,
+                userTokenAccount: userTokenAccount,
+                userShareAccount: userShareAccount,
+                user: authority.publicKey,
+            })
+            .rpc();
+
+        // 10 shares at ~1.05 = 10.499995 tokens, less the 0.5% fee
+        const balanceAfter = await provider.connection.getTokenAccountBalance(userTokenAccount);
+        expect(Number(balanceAfter.value.amount) - Number(balanceBefore.value.amount)).to.equal(10_447_496);
+
+        // The fee stays with the remaining shares
+        const vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.totalShares.toNumber()).to.equal(40_001_000);
+        expect(vault.totalAssets.toNumber()).to.equal(42_053_529);
+    });
+
+    it("queues deposits until the epoch rolls", async () => {
+        const [depositReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("deposit"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+        const [quoteTokenAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("vault_quote"), vaultPda.toBuffer()],
+            program.programId
+        );
+
+        await program.methods
+            .deposit(new anchor.BN(10_000_000), new anchor.BN(0))
+            .accountsPartial({
+                vault: vaultPda,
+                shareMint: shareMintPda,
+                vaultTokenAccount: vaultTokenAccountPda,
+                userTokenAccount: userTokenAccount,
+                userShareAccount: userShareAccount,
+                depositReceipt: depositReceiptPda,
+                user: authority.publicKey,
+            })
+            .rpc();
+
+        // No shares until the roll
+        let vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.pendingDeposits.toNumber()).to.equal(10_000_000);
+        expect(vault.totalShares.toNumber()).to.equal(40_001_000);
+
+        await program.methods
+            .advanceEpoch()
+            .accountsPartial({
+                vault: vaultPda,
+                epochRecord: deriveEpochRecordPda(3),
+                authority: authority.publicKey,
+                vaultTokenAccount: vaultTokenAccountPda,
+                quoteTokenAccount: quoteTokenAccountPda,
+            })
+            .rpc();
+
+        await program.methods
+            .claimShares()
+            .accountsPartial({
+                vault: vaultPda,
+                depositReceipt: depositReceiptPda,
+                epochRecord: deriveEpochRecordPda(3),
+                shareMint: shareMintPda,
+                userShareAccount: userShareAccount,
+                userTokenAccount: userTokenAccount,
+                user: authority.publicKey,
+            })
+            .rpc();
+
+        // 10 tokens buy in at the closing price of 42.053529 tokens / 40.001 shares
+        const shares = await provider.connection.getTokenAccountBalance(userShareAccount);
+        expect(Number(shares.value.amount)).to.equal(49_511_924);
+
+        vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.pendingDeposits.toNumber()).to.equal(0);
+        expect(vault.totalAssets.toNumber()).to.equal(52_053_529);
+    });
+
+    it("charges the performance fee above the high-water mark", async () => {
+        const [quoteTokenAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("vault_quote"), vaultPda.toBuffer()],
+            program.programId
+        );
+
+        // Fees above the caps are rejected
+        try {
+            await program.methods
+                .setFees(501, 1_000, authority.publicKey)
+                .accountsPartial({
+                    vault: vaultPda,
+                    authority: authority.publicKey,
+                })
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("InvalidFee");
+        }
+
+        await program.methods
+            .setFees(0, 1_000, authority.publicKey) // 10% performance fee
+            .accountsPartial({
+                vault: vaultPda,
+                authority: authority.publicKey,
+            })
+            .rpc();
+
+        let vault = await program.account.vault.fetch(vaultPda);
+        const highWaterMark = vault.highWaterMark.toNumber();
+        expect(highWaterMark).to.equal(1_051_311_942); // Closing price of epoch 3
+
+        await transfer(
+            provider.connection,
+            (provider.wallet as anchor.Wallet).payer,
+            userTokenAccount,
+            vaultTokenAccountPda,
+            authority.publicKey,
+            5_000_000 // 5 tokens premium
+        );
+
+        await program.methods
+            .advanceEpoch()
+            .accountsPartial({
+                vault: vaultPda,
+                epochRecord: deriveEpochRecordPda(4),
+                authority: authority.publicKey,
+                vaultTokenAccount: vaultTokenAccountPda,
+                quoteTokenAccount: quoteTokenAccountPda,
+                shareMint: shareMintPda,
+                feeRecipientShareAccount: userShareAccount,
+                shareTokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+
+        // 10% of the 5 token gain above the mark, paid as 437,752 new shares
+        vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.totalAssets.toNumber()).to.equal(57_053_529);
+        expect(vault.totalShares.toNumber()).to.equal(49_950_676);
+        expect(vault.highWaterMark.toNumber()).to.equal(1_142_197_334);
+
+        const shares = await provider.connection.getTokenAccountBalance(userShareAccount);
+        expect(Number(shares.value.amount)).to.equal(49_949_676);
+    });
+
+    it("keeps epoch history in the epoch record", async () => {
+        const record = await program.account.epochRecord.fetch(deriveEpochRecordPda(4));
+        expect(record.epoch.toNumber()).to.equal(4);
+        expect(record.endTimestamp.toNumber()).to.be.at.least(record.startTimestamp.toNumber());
+        expect(record.startPricePerShare.toNumber()).to.equal(1_051_311_956);
+        expect(record.pricePerShare.toNumber()).to.equal(1_142_197_334); // After the performance fee
+        expect(record.premiumEarned.toNumber()).to.equal(5_000_000);
+        expect(record.quotePremiumEarned.toNumber()).to.equal(0);
+        // No options were sold, so nothing was settled or paid out
+        expect(record.notionalExposed.toNumber()).to.equal(0);
+        expect(record.settlementPrice.toNumber()).to.equal(0);
+        expect(record.payout.toNumber()).to.equal(0);
+        expect(record.realizedPnl.toNumber()).to.equal(5_000_000);
+    });
+
+    it("enforces deposit caps and the allowlist", async () => {
+        const [depositReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("deposit"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+        const [allowlistEntryPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("allowlist"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+        const depositAccounts = {
+            vault: vaultPda,
+            shareMint: shareMintPda,
+            vaultTokenAccount: vaultTokenAccountPda,
+            userTokenAccount: userTokenAccount,
+            userShareAccount: userShareAccount,
+            depositReceipt: depositReceiptPda,
+            user: authority.publicKey,
+        };
+        const setLimits = (depositCap: number, userDepositCap: number, allowlistEnabled: boolean) =>
+            program.methods
+                .setDepositLimits(new anchor.BN(depositCap), new anchor.BN(userDepositCap), allowlistEnabled)
+                .accountsPartial({
+                    vault: vaultPda,
+                    authority: authority.publicKey,
+                })
+                .rpc();
+
+        // TVL is ~57.05 tokens, so a 60 token cap leaves room for less than 5
+        await setLimits(60_000_000, 0, false);
+        try {
+            await program.methods
+                .deposit(new anchor.BN(5_000_000), new anchor.BN(0))
+                .accountsPartial(depositAccounts)
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("DepositCapExceeded");
+        }
+
+        // The wallet has deposited 110 tokens of principal, whatever it did with the shares
+        const [depositorPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("depositor"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+        const depositor = await program.account.depositor.fetch(depositorPda);
+        expect(depositor.principal.toNumber()).to.equal(110_000_000);
+        await setLimits(0, 110_500_000, false);
+        try {
+            await program.methods
+                .deposit(new anchor.BN(1_000_000), new anchor.BN(0))
+                .accountsPartial(depositAccounts)
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("UserDepositCapExceeded");
+        }
+
+        await setLimits(0, 0, true);
+        try {
+            await program.methods
+                .deposit(new anchor.BN(1_000_000), new anchor.BN(0))
+                .accountsPartial(depositAccounts)
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("NotAllowlisted");
+        }
+
+        await program.methods
+            .addToAllowlist(authority.publicKey)
+            .accountsPartial({
+                vault: vaultPda,
+                allowlistEntry: allowlistEntryPda,
+                authority: authority.publicKey,
+            })
+            .rpc();
+
+        await program.methods
+            .deposit(new anchor.BN(1_000_000), new anchor.BN(0))
+            .accountsPartial({ ...depositAccounts, allowlistEntry: allowlistEntryPda })
+            .rpc();
+
+        const vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.pendingDeposits.toNumber()).to.equal(1_000_000);
+
+        await program.methods
+            .removeFromAllowlist()
+            .accountsPartial({
+                vault: vaultPda,
+                allowlistEntry: allowlistEntryPda,
+                authority: authority.publicKey,
+            })
+            .rpc();
+        await setLimits(0, 0, false);
+    });
+
+    it("previews conversions, redemptions and limits", async () => {
+        const [depositorPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("depositor"), vaultPda.toBuffer(), authority.publicKey.toBuffer()],
+            program.programId
+        );
+
+        // ~1.142 tokens per share after the performance fee
+        const shares = await program.methods
+            .convertToShares(new anchor.BN(1_000_000))
+            .accountsPartial({ vault: vaultPda })
+            .view();
+        expect(shares.toNumber()).to.equal(875_505);
+
+        const previewShares = await program.methods
+            .previewDeposit(new anchor.BN(1_000_000))
+            .accountsPartial({ vault: vaultPda })
+            .view();
+        expect(previewShares.toNumber()).to.equal(875_505);
+
+        const assets = await program.methods
+            .convertToAssets(new anchor.BN(1_000_000))
+            .accountsPartial({ vault: vaultPda })
+            .view();
+        expect(assets.toNumber()).to.equal(1_142_197);
+
+        // Redeeming instantly pays the 0.5% fee
+        const redeem = await program.methods
+            .previewRedeem(new anchor.BN(10_000_000))
+            .accountsPartial({ vault: vaultPda })
+            .view();
+        expect(redeem.assets.toNumber()).to.equal(11_364_864);
+        expect(redeem.fee.toNumber()).to.equal(57_109);
+
+        const maxWithdraw = await program.methods
+            .maxWithdraw()
+            .accountsPartial({ vault: vaultPda, userShareAccount: userShareAccount })
+            .view();
+        expect(maxWithdraw.toNumber()).to.equal(56_767_125);
+
+        // The vault cap counts the 1 token still queued
+        await program.methods
+            .setDepositLimits(new anchor.BN(60_000_000), new anchor.BN(0), false)
+            .accountsPartial({ vault: vaultPda, authority: authority.publicKey })
+            .rpc();
+        const maxDeposit = await program.methods
+            .maxDeposit()
+            .accountsPartial({
+                vault: vaultPda,
+                user: authority.publicKey,
+                depositor: depositorPda,
+            })
+            .view();
+        expect(maxDeposit.toNumber()).to.equal(1_946_471);
+
+        await program.methods
+            .setDepositLimits(new anchor.BN(0), new anchor.BN(0), false)
+            .accountsPartial({ vault: vaultPda, authority: authority.publicKey })
+            .rpc();
+    });
+
+    it("sets and updates strategy parameters", async () => {
+        const [strategyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("strategy"), vaultPda.toBuffer()],
+            program.programId
+        );
+        const config = {
+            minOtmBps: 300,                      // 3-10% OTM
+            maxOtmBps: 1_000,
+            minTenorSecs: new anchor.BN(6 * 24 * 60 * 60),
+            maxTenorSecs: new anchor.BN(8 * 24 * 60 * 60),
+            minPremiumBps: 20,
+            rfqDurationSecs: new anchor.BN(15 * 60),
+            maxNotionalPerEpoch: new anchor.BN(0),
+        };
+
+        await program.methods
+            .initializeStrategy(config)
+            .accountsPartial({
+                vault: vaultPda,
+                strategy: strategyPda,
+                authority: authority.publicKey,
+            })
+            .rpc();
+
+        let strategy = await program.account.strategyParams.fetch(strategyPda);
+        expect(strategy.vault.toBase58()).to.equal(vaultPda.toBase58());
+        expect(strategy.minOtmBps).to.equal(300);
+        expect(strategy.maxOtmBps).to.equal(1_000);
+        expect(strategy.rfqDurationSecs.toNumber()).to.equal(900);
+
+        // The OTM band must not be inverted
+        try {
+            await program.methods
+                .updateStrategy({ ...config, minOtmBps: 2_000 })
+                .accountsPartial({
+                    vault: vaultPda,
+                    strategy: strategyPda,
+                    authority: authority.publicKey,
+                })
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("InvalidStrategyParams");
+        }
+
+        await program.methods
+            .updateStrategy({ ...config, maxNotionalPerEpoch: new anchor.BN(25_000_000) })
+            .accountsPartial({
+                vault: vaultPda,
+                strategy: strategyPda,
+                authority: authority.publicKey,
+            })
+            .rpc();
+
+        strategy = await program.account.strategyParams.fetch(strategyPda);
+        expect(strategy.maxNotionalPerEpoch.toNumber()).to.equal(25_000_000);
+    });
+
+    describe("cash-secured put vault", () => {
+        const putAssetId = "NVDAx-PUT";
+        let putVaultPda: anchor.web3.PublicKey;
+        let usdcMint: anchor.web3.PublicKey;
+        let userUsdcAccount: anchor.web3.PublicKey;
+
+        before(async () => {
+            [putVaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault"), Buffer.from(putAssetId)],
+                program.programId
+            );
+
+            usdcMint = await createMint(
+                provider.connection,
+                (provider.wallet as anchor.Wallet).payer,
+                authority.publicKey,
+                null,
+                6
+            );
+            const userUsdc = await getOrCreateAssociatedTokenAccount(
+                provider.connection,
+                (provider.wallet as anchor.Wallet).payer,
+                usdcMint,
+                authority.publicKey
+            );
+            userUsdcAccount = userUsdc.address;
+            await mintTo(
+                provider.connection,
+                (provider.wallet as anchor.Wallet).payer,
+                usdcMint,
+                userUsdcAccount,
+                authority.publicKey,
+                10_000_000_000 // 10,000 USDC
+            );
+        });
+
+        it("takes USDC and holds assigned xStock in its quote account", async () => {
+            // 2,000 USDC seed, 50% utilization
+            await program.methods
+                .initializeVault(putAssetId, 5000, new anchor.BN(2_000_000_000))
+                .accounts({
+                    underlyingMint: usdcMint,
+                    authorityTokenAccount: userUsdcAccount,
+                })
+                .rpc();
+
+            const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+            const [assetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("asset"), Buffer.from(assetId)],
+                oracleProgram.programId
+            );
+            const [assignedTokenAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault_quote"), putVaultPda.toBuffer()],
+                program.programId
+            );
+
+            await program.methods
+                .initializePutStrategy()
+                .accountsPartial({
+                    vault: putVaultPda,
+                    optionMint: underlyingMint,
+                    assetConfig: assetConfigPda,
+                    quoteTokenAccount: assignedTokenAccountPda,
+                    authority: authority.publicKey,
+                })
+                .rpc();
+
+            const vault = await program.account.vault.fetch(putVaultPda);
+            expect(vault.strategy).to.have.property("cashSecuredPut");
+            expect(vault.optionMint.toBase58()).to.equal(underlyingMint.toBase58());
+            expect(vault.quoteMint.toBase58()).to.equal(underlyingMint.toBase58());
+            expect(vault.quoteTokenAccount.toBase58()).to.equal(assignedTokenAccountPda.toBase58());
+            expect(vault.oracleAssetConfig.toBase58()).to.equal(assetConfigPda.toBase58());
+        });
+
+        it("only records exposure from RFQs the vault filled", async () => {
+            const rfq = await rfqProgram.account.rfq.fetch(foreignRfqPda);
+            expect(rfq.status).to.have.property("filled");
+
+            try {
+                await program.methods
+                    .recordNotionalExposure()
+                    .accountsPartial({
+                        vault: putVaultPda,
+                        rfq: foreignRfqPda,
+                        auctionPayer: authority.publicKey,
+                        makerOptionAccount: null,
+                        authority: authority.publicKey,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                // The vault never started an auction for this RFQ
+                expect(error.message).to.include("AccountNotInitialized");
+            }
+
+            const vault = await program.account.vault.fetch(putVaultPda);
+            expect(vault.epochNotionalExposed.toNumber()).to.equal(0);
+            expect(vault.epochCollateral.toNumber()).to.equal(0);
+
+            // No collateral was locked for the rejected fill
+            const [lockedCollateralPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("locked_collateral"), putVaultPda.toBuffer(), vault.epoch.toArrayLike(Buffer, "le", 8)],
+                program.programId
+            );
+            expect(await provider.connection.getAccountInfo(lockedCollateralPda)).to.be.null;
+        });
+    });
+
+    describe("deposit slippage at the roll", () => {
+        const slipAssetId = "MSFTx";
+        let slipVaultPda: anchor.web3.PublicKey;
+        let slipShareMintPda: anchor.web3.PublicKey;
+        let slipVaultTokenAccountPda: anchor.web3.PublicKey;
+
+        before(async () => {
+            [slipVaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault"), Buffer.from(slipAssetId)],
+                program.programId
+            );
+            [slipShareMintPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("shares"), slipVaultPda.toBuffer()],
+                program.programId
+            );
+            [slipVaultTokenAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault_tokens"), slipVaultPda.toBuffer()],
+                program.programId
+            );
+
+            await program.methods
+                .initializeVault(slipAssetId, 7500, new anchor.BN(SEED_AMOUNT))
+                .accounts({
+                    underlyingMint: underlyingMint,
+                    authorityTokenAccount: userTokenAccount,
+                })
+                .rpc();
+        });
+
+        it("refunds a deposit the roll prices below its minimum", async () => {
+            const slipShareAccount = (
+                await getOrCreateAssociatedTokenAccount(
+                    provider.connection,
+                    (provider.wallet as anchor.Wallet).payer,
+                    slipShareMintPda,
+                    authority.publicKey
+                )
+            ).address;
+            const [depositReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("deposit"), slipVaultPda.toBuffer(), authority.publicKey.toBuffer()],
+                program.programId
+            );
+            const [epochRecordPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("epoch_record"), slipVaultPda.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+                program.programId
+            );
+
+            // 1:1 with the seed when queued
+            await program.methods
+                .deposit(new anchor.BN(10_000_000), new anchor.BN(10_000_000))
+                .accountsPartial({
+                    vault: slipVaultPda,
+                    shareMint: slipShareMintPda,
+                    vaultTokenAccount: slipVaultTokenAccountPda,
+                    userTokenAccount: userTokenAccount,
+                    userShareAccount: slipShareAccount,
+                    depositReceipt: depositReceiptPda,
+                    user: authority.publicKey,
+                })
+                .rpc();
+
+            let receipt = await program.account.depositReceipt.fetch(depositReceiptPda);
+            expect(receipt.minSharesOut.toNumber()).to.equal(10_000_000);
+            expect((await program.account.vault.fetch(slipVaultPda)).guardedDeposits).to.equal(1);
+
+            // Premium lands before the roll and lifts the share price
+            await transfer(
+                provider.connection,
+                (provider.wallet as anchor.Wallet).payer,
+                userTokenAccount,
+                slipVaultTokenAccountPda,
+                authority.publicKey,
+                1_000_000
+            );
+
+            // The roll needs every receipt that carries a minimum
+            try {
+                await program.methods
+                    .advanceEpoch()
+                    .accountsPartial({
+                        vault: slipVaultPda,
+                        epochRecord: epochRecordPda,
+                        authority: authority.publicKey,
+                        vaultTokenAccount: slipVaultTokenAccountPda,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("InvalidDepositReceipts");
+            }
+
+            await program.methods
+                .advanceEpoch()
+                .accountsPartial({
+                    vault: slipVaultPda,
+                    epochRecord: epochRecordPda,
+                    authority: authority.publicKey,
+                    vaultTokenAccount: slipVaultTokenAccountPda,
+                })
+                .remainingAccounts([{ pubkey: depositReceiptPda, isSigner: false, isWritable: true }])
+                .rpc();
+
+            // The roll priced it at 9_990 shares, so the deposit is set aside rather than converted
+            receipt = await program.account.depositReceipt.fetch(depositReceiptPda);
+            expect(receipt.refunded).to.be.true;
+            const record = await program.account.epochRecord.fetch(epochRecordPda);
+            expect(record.depositAssets.toNumber()).to.equal(0);
+            expect(record.depositShares.toNumber()).to.equal(0);
+            let vault = await program.account.vault.fetch(slipVaultPda);
+            expect(vault.totalAssets.toNumber()).to.equal(1_001_000);
+            expect(vault.totalShares.toNumber()).to.equal(SEED_AMOUNT);
+            expect(vault.reservedAssets.toNumber()).to.equal(10_000_000);
+            expect(vault.guardedDeposits).to.equal(0);
+
+            const before = await getAccount(provider.connection, userTokenAccount);
+            await program.methods
+                .claimShares()
+                .accountsPartial({
+                    vault: slipVaultPda,
+                    depositReceipt: depositReceiptPda,
+                    epochRecord: epochRecordPda,
+                    shareMint: slipShareMintPda,
+                    userShareAccount: slipShareAccount,
+                    userTokenAccount: userTokenAccount,
+                    user: authority.publicKey,
+                })
+                .rpc();
+
+            const after = await getAccount(provider.connection, userTokenAccount);
+            expect(Number(after.amount - before.amount)).to.equal(10_000_000);
+            const shares = await provider.connection.getTokenAccountBalance(slipShareAccount);
+            expect(Number(shares.value.amount)).to.equal(0);
+            expect(await provider.connection.getAccountInfo(depositReceiptPda)).to.be.null;
+
+            // The refund no longer counts against the per-wallet cap
+            const [depositorPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("depositor"), slipVaultPda.toBuffer(), authority.publicKey.toBuffer()],
+                program.programId
+            );
+            const depositor = await program.account.depositor.fetch(depositorPda);
+            expect(depositor.principal.toNumber()).to.equal(0);
+
+            vault = await program.account.vault.fetch(slipVaultPda);
+            expect(vault.totalAssets.toNumber()).to.equal(1_001_000);
+            expect(vault.totalShares.toNumber()).to.equal(SEED_AMOUNT);
+            expect(vault.reservedAssets.toNumber()).to.equal(0);
+        });
+
+        it("values the quote balance at the oracle price when rolling", async () => {
+            const quoteMint = await createMint(
+                provider.connection,
+                (provider.wallet as anchor.Wallet).payer,
+                authority.publicKey,
+                null,
+                6
+            );
+            const [quoteTokenAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault_quote"), slipVaultPda.toBuffer()],
+                program.programId
+            );
+            await program.methods
+                .initializeQuoteAccount()
+                .accountsPartial({
+                    vault: slipVaultPda,
+                    quoteMint,
+                    quoteTokenAccount: quoteTokenAccountPda,
+                    authority: authority.publicKey,
+                })
+                .rpc();
+            await mintTo(
+                provider.connection,
+                (provider.wallet as anchor.Wallet).payer,
+                quoteMint,
+                quoteTokenAccountPda,
+                authority.publicKey,
+                1_000_000
+            );
+
+            // NAV, and with it the high-water mark, can't be measured without a price
+            try {
+                await program.methods
+                    .advanceEpoch()
+                    .accountsPartial({
+                        vault: slipVaultPda,
+                        epochRecord: anchor.web3.PublicKey.findProgramAddressSync(
+                            [Buffer.from("epoch_record"), slipVaultPda.toBuffer(), new anchor.BN(1).toArrayLike(Buffer, "le", 8)],
+                            program.programId
+                        )[0],
+                        authority: authority.publicKey,
+                        vaultTokenAccount: slipVaultTokenAccountPda,
+                        quoteTokenAccount: quoteTokenAccountPda,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("MissingOracleAccounts");
+            }
+        });
+    });
+
+    describe("authority and keeper roles", () => {
+        const rolesAssetId = "AAPLx";
+        let rolesVaultPda: anchor.web3.PublicKey;
+        const keeper = anchor.web3.Keypair.generate();
+        const newAuthority = anchor.web3.Keypair.generate();
+
+        before(async () => {
+            [rolesVaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault"), Buffer.from(rolesAssetId)],
+                program.programId
+            );
+
+            await program.methods
+                .initializeVault(rolesAssetId, 7500, new anchor.BN(SEED_AMOUNT))
+                .accounts({
+                    underlyingMint: underlyingMint,
+                    authorityTokenAccount: userTokenAccount,
+                })
+                .rpc();
+        });
+
+        it("lets the keeper run epoch operations but not change parameters", async () => {
+            await program.methods
+                .setKeeper(keeper.publicKey)
+                .accountsPartial({ vault: rolesVaultPda, authority: authority.publicKey })
+                .rpc();
+
+            const vault = await program.account.vault.fetch(rolesVaultPda);
+            expect(vault.keeper.toBase58()).to.equal(keeper.publicKey.toBase58());
+
+            // The keeper passes the operator check, the RFQ without an auction is then rejected
+            const airdrop = await provider.connection.requestAirdrop(
+                keeper.publicKey,
+                anchor.web3.LAMPORTS_PER_SOL
+            );
+            await provider.connection.confirmTransaction(airdrop);
+            try {
+                await program.methods
+                    .recordNotionalExposure()
+                    .accountsPartial({
+                        vault: rolesVaultPda,
+                        rfq: foreignRfqPda,
+                        auctionPayer: keeper.publicKey,
+                        makerOptionAccount: null,
+                        authority: keeper.publicKey,
+                    })
+                    .signers([keeper])
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("AccountNotInitialized");
+            }
+
+            const stranger = anchor.web3.Keypair.generate();
+            try {
+                await program.methods
+                    .recordNotionalExposure()
+                    .accountsPartial({
+                        vault: rolesVaultPda,
+                        rfq: foreignRfqPda,
+                        auctionPayer: stranger.publicKey,
+                        makerOptionAccount: null,
+                        authority: stranger.publicKey,
+                    })
+                    .signers([stranger])
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("Unauthorized");
+            }
+
+            try {
+                await program.methods
+                    .setInstantWithdrawFee(100)
+                    .accountsPartial({ vault: rolesVaultPda, authority: keeper.publicKey })
+                    .signers([keeper])
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("ConstraintHasOne");
+            }
+        });
+
+        it("transfers authority in two steps", async () => {
+            await program.methods
+                .proposeAuthority(newAuthority.publicKey)
+                .accountsPartial({ vault: rolesVaultPda, authority: authority.publicKey })
+                .rpc();
+
+            // Only the proposed authority can accept
+            try {
+                await program.methods
+                    .acceptAuthority()
+                    .accountsPartial({ vault: rolesVaultPda, pendingAuthority: keeper.publicKey })
+                    .signers([keeper])
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("Unauthorized");
+            }
+
+            await program.methods
+                .acceptAuthority()
+                .accountsPartial({ vault: rolesVaultPda, pendingAuthority: newAuthority.publicKey })
+                .signers([newAuthority])
+                .rpc();
+
+            const vault = await program.account.vault.fetch(rolesVaultPda);
+            expect(vault.authority.toBase58()).to.equal(newAuthority.publicKey.toBase58());
+            expect(vault.pendingAuthority.toBase58()).to.equal(anchor.web3.PublicKey.default.toBase58());
+
+            // The previous authority is locked out
+            try {
+                await program.methods
+                    .setKeeper(authority.publicKey)
+                    .accountsPartial({ vault: rolesVaultPda, authority: authority.publicKey })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("ConstraintHasOne");
+            }
+        });
+    });
+
+    describe("auctions", () => {
+        // Covered calls on 10 tokens at a 50% utilization cap, priced off a mock oracle
+        const auctionAssetId = "GOOGLx";
+        const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+        const maker = anchor.web3.Keypair.generate();
+        const SPOT = 100_000_000; // $100
+        let auctionVaultPda: anchor.web3.PublicKey;
+        let auctionVaultTokenAccount: anchor.web3.PublicKey;
+        let strategyPda: anchor.web3.PublicKey;
+        let auctionAssetConfigPda: anchor.web3.PublicKey;
+        let auctionPriceHistoryPda: anchor.web3.PublicKey;
+        let pythPriceAccount: anchor.web3.PublicKey;
+        let makerAccountPda: anchor.web3.PublicKey;
+        let makerTokenAccount: anchor.web3.PublicKey;
+        let expiryTs: number;
+        let validUntilTs: number;
+        // RFQs of the auctions started below, by strike
+        const auctionRfqs: Record<number, anchor.web3.PublicKey> = {};
+
+        const chainTime = async () =>
+            (await provider.connection.getBlockTime(await provider.connection.getSlot())) as number;
+
+        const deriveAuctionPda = (rfq: anchor.web3.PublicKey) =>
+            anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("auction"), auctionVaultPda.toBuffer(), rfq.toBuffer()],
+                program.programId
+            )[0];
+
+        const startAuction = async (strike: number, expiry: number, validUntil: number, premiumFloorBps = 50) => {
+            const config = await rfqProgram.account.config.fetch(
+                anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("config")], rfqProgram.programId)[0]
+            );
+            const [rfqPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("rfq"), config.rfqCount.toArrayLike(Buffer, "le", 8)],
+                rfqProgram.programId
+            );
+
+            await program.methods
+                .startAuction(new anchor.BN(strike), new anchor.BN(expiry), premiumFloorBps, new anchor.BN(validUntil))
+                .accountsPartial({
+                    vault: auctionVaultPda,
+                    strategy: strategyPda,
+                    authority: authority.publicKey,
+                    assetConfig: auctionAssetConfigPda,
+                    pythPriceAccount,
+                    premiumAccount: auctionVaultTokenAccount,
+                    rfq: rfqPda,
+                    auction: deriveAuctionPda(rfqPda),
+                })
+                .rpc();
+            return rfqPda;
+        };
+
+        const fillAuction = (rfq: anchor.web3.PublicKey) =>
+            rfqProgram.methods
+                .fillRfq(100)
+                .accountsPartial({
+                    rfq,
+                    makerAccount: makerAccountPda,
+                    creatorTokenAccount: auctionVaultTokenAccount,
+                    makerTokenAccount,
+                    maker: maker.publicKey,
+                })
+                .signers([maker])
+                .rpc();
+
+        const deriveLockedCollateralPda = (epoch: number) =>
+            anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("locked_collateral"), auctionVaultPda.toBuffer(), new anchor.BN(epoch).toArrayLike(Buffer, "le", 8)],
+                program.programId
+            )[0];
+
+        const deriveRecordedRfqPda = (rfq: anchor.web3.PublicKey) =>
+            anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("recorded_rfq"), auctionVaultPda.toBuffer(), rfq.toBuffer()],
+                program.programId
+            )[0];
+
+        const recordAuction = async (rfq: anchor.web3.PublicKey, recorder: anchor.web3.PublicKey, signers: anchor.web3.Keypair[] = []) => {
+            const vault = await program.account.vault.fetch(auctionVaultPda);
+            await program.methods
+                .recordNotionalExposure()
+                .accountsPartial({
+                    vault: auctionVaultPda,
+                    rfq,
+                    auction: deriveAuctionPda(rfq),
+                    auctionPayer: authority.publicKey,
+                    recordedRfq: deriveRecordedRfqPda(rfq),
+                    underlyingMint,
+                    vaultTokenAccount: auctionVaultTokenAccount,
+                    lockedCollateralAccount: deriveLockedCollateralPda(vault.epoch.toNumber()),
+                    makerOptionAccount: null,
+                    authority: recorder,
+                })
+                .signers(signers)
+                .rpc();
+        };
+
+        const settleAuction = async (rfq: anchor.web3.PublicKey) => {
+            const vault = await program.account.vault.fetch(auctionVaultPda);
+            await program.methods
+                .settleRfq()
+                .accountsPartial({
+                    vault: auctionVaultPda,
+                    rfq,
+                    recordedRfq: deriveRecordedRfqPda(rfq),
+                    lockedCollateralAccount: deriveLockedCollateralPda(vault.epoch.toNumber()),
+                    makerTokenAccount,
+                    makerOptionAccount: null,
+                    quoteTokenAccount: null,
+                    assetConfig: auctionAssetConfigPda,
+                })
+                .rpc();
+        };
+
+        // Records the observation an option expiring at `expiry` settles on, i.e. the
+        // first 15 minute slot starting at or after it, unless that slot already has one
+        const recordSettlementPrice = async (expiry: number, price: number) => {
+            const slotStart = Math.ceil(expiry / 900) * 900;
+            const history = await oracleProgram.account.priceHistory.fetch(auctionPriceHistoryPda);
+            const latest = history.observations[(history.head + history.observations.length - 1) % history.observations.length];
+            if (history.count > 0 && latest.publishTime.toNumber() >= slotStart) {
+                return;
+            }
+
+            await setMockPythPrice(provider, pythPriceAccount, price, slotStart);
+            await oracleProgram.methods
+                .recordObservation()
+                .accounts({ assetConfig: auctionAssetConfigPda, pythPriceAccount })
+                .rpc();
+        };
+
+        const sleepUntil = async (ts: number) => {
+            while ((await chainTime()) < ts) {
+                await new Promise((resolve) => setTimeout(resolve, 1000));
+            }
+        };
+
+        before(async () => {
+            [auctionVaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault"), Buffer.from(auctionAssetId)],
+                program.programId
+            );
+            [auctionVaultTokenAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault_tokens"), auctionVaultPda.toBuffer()],
+                program.programId
+            );
+            [strategyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("strategy"), auctionVaultPda.toBuffer()],
+                program.programId
+            );
+            [auctionAssetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("asset"), Buffer.from(auctionAssetId)],
+                oracleProgram.programId
+            );
+            [auctionPriceHistoryPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("history"), auctionAssetConfigPda.toBuffer()],
+                oracleProgram.programId
+            );
+            [makerAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("maker"), maker.publicKey.toBuffer()],
+                rfqProgram.programId
+            );
+
+            await program.methods
+                .initializeVault(auctionAssetId, 5000, new anchor.BN(10_000_000))
+                .accounts({
+                    underlyingMint: underlyingMint,
+                    authorityTokenAccount: userTokenAccount,
+                })
+                .rpc();
+
+            // RFQs take quotes for up to 30 seconds and expire 40 seconds to 2 minutes out,
+            // so the tests can wait for settlement; each auction sells at most 2 tokens
+            await program.methods
+                .initializeStrategy({
+                    minOtmBps: 300,
+                    maxOtmBps: 1_000,
+                    minTenorSecs: new anchor.BN(40),
+                    maxTenorSecs: new anchor.BN(120),
+                    minPremiumBps: 20,
+                    rfqDurationSecs: new anchor.BN(30),
+                    maxNotionalPerEpoch: new anchor.BN(2_000_000),
+                })
+                .accountsPartial({
+                    vault: auctionVaultPda,
+                    strategy: strategyPda,
+                    authority: authority.publicKey,
+                })
+                .rpc();
+
+            pythPriceAccount = await createMockPythAccount(provider);
+            await oracleProgram.methods.initializeAsset(auctionAssetId, pythPriceAccount).rpc();
+            await oracleProgram.methods
+                .initializePriceHistory()
+                .accounts({ assetConfig: auctionAssetConfigPda })
+                .rpc();
+            await setMockPythPrice(provider, pythPriceAccount, SPOT);
+
+            // The maker pays premium in the underlying and receives call payouts in it
+            const airdrop = await provider.connection.requestAirdrop(maker.publicKey, anchor.web3.LAMPORTS_PER_SOL);
+            await provider.connection.confirmTransaction(airdrop);
+            await rfqProgram.methods
+                .addMaker(maker.publicKey)
+                .accounts({ makerAccount: makerAccountPda })
+                .rpc();
+            makerTokenAccount = (
+                await getOrCreateAssociatedTokenAccount(
+                    provider.connection,
+                    (provider.wallet as anchor.Wallet).payer,
+                    underlyingMint,
+                    maker.publicKey
+                )
+            ).address;
+            await mintTo(
+                provider.connection,
+                (provider.wallet as anchor.Wallet).payer,
+                underlyingMint,
+                makerTokenAccount,
+                authority.publicKey,
+                10_000_000
+            );
+        });
+
+        it("starts auctions sized to the room left under the caps", async () => {
+            const now = await chainTime();
+            expiryTs = now + 60;
+            validUntilTs = now + 30;
+
+            // 4% and 8% out of the money take the strategy's 2 token limit each
+            for (const strike of [104_000_000, 108_000_000]) {
+                auctionRfqs[strike] = await startAuction(strike, expiryTs, validUntilTs);
+
+                const rfq = await rfqProgram.account.rfq.fetch(auctionRfqs[strike]);
+                expect(rfq.creator.toBase58()).to.equal(auctionVaultPda.toBase58());
+                expect(rfq.underlying.toBase58()).to.equal(underlyingMint.toBase58());
+                expect(rfq.optionType).to.have.property("call");
+                expect(rfq.strike.toNumber()).to.equal(strike);
+                expect(rfq.notionalTokens.toNumber()).to.equal(2_000_000);
+                expect(rfq.oraclePrice.toNumber()).to.equal(SPOT);
+                expect(rfq.premiumAccount.toBase58()).to.equal(auctionVaultTokenAccount.toBase58());
+
+                const auction = await program.account.auction.fetch(deriveAuctionPda(auctionRfqs[strike]));
+                expect(auction.collateral.toNumber()).to.equal(2_000_000);
+                expect(auction.payer.toBase58()).to.equal(authority.publicKey.toBase58());
+            }
+
+            // Only 1 token is left under the 5 token utilization cap
+            auctionRfqs[105_000_000] = await startAuction(105_000_000, expiryTs, validUntilTs);
+            const rfq = await rfqProgram.account.rfq.fetch(auctionRfqs[105_000_000]);
+            expect(rfq.notionalTokens.toNumber()).to.equal(1_000_000);
+
+            const vault = await program.account.vault.fetch(auctionVaultPda);
+            expect(vault.openAuctions).to.equal(3);
+            expect(vault.auctionCollateral.toNumber()).to.equal(5_000_000);
+
+            try {
+                await startAuction(106_000_000, expiryTs, validUntilTs);
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("ExceedsUtilizationCap");
+            }
+        });
+
+        it("rejects auctions outside the strategy bounds", async () => {
+            const now = await chainTime();
+            const cases: [number, number, number, number, string][] = [
+                // 2% out of the money is short of the 3% band, 15% past its 10%
+                [102_000_000, now + 60, now + 30, 50, "InvalidStrike"],
+                [115_000_000, now + 60, now + 30, 50, "InvalidStrike"],
+                // Calls are sold above spot
+                [95_000_000, now + 60, now + 30, 50, "InvalidStrike"],
+                [105_000_000, now + 180, now + 30, 50, "InvalidTenor"],
+                [105_000_000, now + 20, now + 10, 50, "InvalidTenor"],
+                [105_000_000, now + 60, now + 45, 50, "InvalidAuctionWindow"],
+                [105_000_000, now + 60, now + 30, 10, "PremiumFloorTooLow"],
+            ];
+
+            // The bounds are checked before the auction is sized, so the full cap doesn't mask them
+            for (const [strike, expiry, validUntil, premiumFloorBps, code] of cases) {
+                try {
+                    await startAuction(strike, expiry, validUntil, premiumFloorBps);
+                    expect.fail("Should have thrown an error");
+                } catch (error) {
+                    expect(error.message).to.include(code);
+                }
+            }
+
+            const vault = await program.account.vault.fetch(auctionVaultPda);
+            expect(vault.openAuctions).to.equal(3);
+        });
+
+        it("records the filled auctions", async () => {
+            for (const strike of [104_000_000, 108_000_000]) {
+                await fillAuction(auctionRfqs[strike]);
+                await recordAuction(auctionRfqs[strike], authority.publicKey);
+                expect(await provider.connection.getAccountInfo(deriveAuctionPda(auctionRfqs[strike]))).to.be.null;
+            }
+
+            const vault = await program.account.vault.fetch(auctionVaultPda);
+            expect(vault.epochNotionalExposed.toNumber()).to.equal(4_000_000);
+            expect(vault.epochCollateral.toNumber()).to.equal(4_000_000);
+            expect(vault.epochPremiumEarned.toNumber()).to.equal(40_000);
+            expect(vault.openAuctions).to.equal(1);
+            expect(vault.auctionCollateral.toNumber()).to.equal(1_000_000);
+        });
+
+        it("closes an auction once its RFQ can no longer be filled", async () => {
+            const rfq = auctionRfqs[105_000_000];
+            const closeAccounts = {
+                vault: auctionVaultPda,
+                rfq,
+                auction: deriveAuctionPda(rfq),
+                auctionPayer: authority.publicKey,
+                premiumAccount: null,
+                makerPremiumAccount: null,
+            };
+
+            try {
+                await program.methods.closeAuction().accountsPartial(closeAccounts).rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("AuctionStillOpen");
+            }
+
+            await sleepUntil(validUntilTs);
+            await program.methods.closeAuction().accountsPartial(closeAccounts).rpc();
+
+            expect(await provider.connection.getAccountInfo(deriveAuctionPda(rfq))).to.be.null;
+            const vault = await program.account.vault.fetch(auctionVaultPda);
+            expect(vault.openAuctions).to.equal(0);
+            expect(vault.auctionCollateral.toNumber()).to.equal(0);
+        });
+
+        it("settles calls that finished in and out of the money", async () => {
+            await sleepUntil(expiryTs);
+
+            // Nothing settles until the oracle has recorded the expiry slot
+            try {
+                await settleAuction(auctionRfqs[104_000_000]);
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("ObservationMissing");
+            }
+
+            await recordSettlementPrice(expiryTs, 106_000_000);
+            const makerBefore = (await getAccount(provider.connection, makerTokenAccount)).amount;
+
+            // 2 tokens struck at 104 settle at 106: 2 * 2 / 106 tokens to the maker
+            await settleAuction(auctionRfqs[104_000_000]);
+            let recorded = await program.account.recordedRfq.fetch(deriveRecordedRfqPda(auctionRfqs[104_000_000]));
+            expect(recorded.settled).to.be.true;
+            expect(recorded.settlementPrice.toNumber()).to.equal(106_000_000);
+            expect(recorded.payout.toNumber()).to.equal(37_735);
+
+            // Struck at 108, the second call expires worthless
+            await settleAuction(auctionRfqs[108_000_000]);
+            recorded = await program.account.recordedRfq.fetch(deriveRecordedRfqPda(auctionRfqs[108_000_000]));
+            expect(recorded.settled).to.be.true;
+            expect(recorded.payout.toNumber()).to.equal(0);
+
+            const makerAfter = (await getAccount(provider.connection, makerTokenAccount)).amount;
+            expect(Number(makerAfter - makerBefore)).to.equal(37_735);
+            const locked = await getAccount(provider.connection, deriveLockedCollateralPda(0));
+            expect(Number(locked.amount)).to.equal(4_000_000 - 37_735);
+
+            const vault = await program.account.vault.fetch(auctionVaultPda);
+            expect(vault.epochSettledNotional.toNumber()).to.equal(4_000_000);
+            expect(vault.epochPayout.toNumber()).to.equal(37_735);
+            expect(vault.epochCollateral.toNumber()).to.equal(4_000_000 - 37_735);
+            expect(vault.totalAssets.toNumber()).to.equal(10_000_000 - 37_735);
+
+            try {
+                await settleAuction(auctionRfqs[104_000_000]);
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("RfqAlreadySettled");
+            }
+        });
+
+        it("moves collateral into the epoch's locked account and back, refunding its rent to the recorder", async () => {
+            const tokenBalance = async (account: anchor.web3.PublicKey) =>
+                Number((await getAccount(provider.connection, account)).amount);
+            const settleEpoch = (lockedCollateralPayer: anchor.web3.PublicKey, epoch: number) =>
+                program.methods
+                    .settleEpoch()
+                    .accountsPartial({
+                        vault: auctionVaultPda,
+                        authority: authority.publicKey,
+                        lockedCollateralPayer,
+                        vaultTokenAccount: auctionVaultTokenAccount,
+                        lockedCollateralAccount: deriveLockedCollateralPda(epoch),
+                    })
+                    .rpc();
+
+            // Close out epoch 0: what the makers weren't paid goes back to the vault
+            let idle = await tokenBalance(auctionVaultTokenAccount);
+            await settleEpoch(authority.publicKey, 0);
+            expect(await tokenBalance(auctionVaultTokenAccount)).to.equal(idle + 4_000_000 - 37_735);
+            expect(await provider.connection.getAccountInfo(deriveLockedCollateralPda(0))).to.be.null;
+
+            await program.methods
+                .advanceEpoch()
+                .accountsPartial({
+                    vault: auctionVaultPda,
+                    epochRecord: anchor.web3.PublicKey.findProgramAddressSync(
+                        [Buffer.from("epoch_record"), auctionVaultPda.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+                        program.programId
+                    )[0],
+                    authority: authority.publicKey,
+                    vaultTokenAccount: auctionVaultTokenAccount,
+                })
+                .rpc();
+
+            // The keeper records epoch 1's only fill, so it pays for the locked account
+            const keeper = anchor.web3.Keypair.generate();
+            await provider.connection.confirmTransaction(
+                await provider.connection.requestAirdrop(keeper.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+            );
+            await program.methods
+                .setKeeper(keeper.publicKey)
+                .accountsPartial({ vault: auctionVaultPda, authority: authority.publicKey })
+                .rpc();
+
+            const now = await chainTime();
+            const expiry = now + 60;
+            // Struck above the 106 recorded for epoch 0, in case both expiries share a slot
+            await setMockPythPrice(provider, pythPriceAccount, SPOT);
+            const rfq = await startAuction(108_000_000, expiry, now + 30);
+            await fillAuction(rfq);
+
+            idle = await tokenBalance(auctionVaultTokenAccount);
+            await recordAuction(rfq, keeper.publicKey, [keeper]);
+
+            expect(await tokenBalance(auctionVaultTokenAccount)).to.equal(idle - 2_000_000);
+            expect(await tokenBalance(deriveLockedCollateralPda(1))).to.equal(2_000_000);
+            let vault = await program.account.vault.fetch(auctionVaultPda);
+            expect(vault.epochCollateral.toNumber()).to.equal(2_000_000);
+            expect(vault.lockedCollateralPayer.toBase58()).to.equal(keeper.publicKey.toBase58());
+
+            // Expires out of the money, so the whole of it is released
+            await sleepUntil(expiry);
+            await recordSettlementPrice(expiry, SPOT);
+            await settleAuction(rfq);
+
+            // The rent can only go back to the keeper
+            try {
+                await settleEpoch(authority.publicKey, 1);
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("Unauthorized");
+            }
+
+            const lockedRent = (await provider.connection.getAccountInfo(deriveLockedCollateralPda(1))).lamports;
+            const keeperLamports = await provider.connection.getBalance(keeper.publicKey);
+            idle = await tokenBalance(auctionVaultTokenAccount);
+            await settleEpoch(keeper.publicKey, 1);
+
+            expect(await tokenBalance(auctionVaultTokenAccount)).to.equal(idle + 2_000_000);
+            expect(await provider.connection.getAccountInfo(deriveLockedCollateralPda(1))).to.be.null;
+            expect(await provider.connection.getBalance(keeper.publicKey)).to.equal(keeperLamports + lockedRent);
+            vault = await program.account.vault.fetch(auctionVaultPda);
+            expect(vault.epochSettled).to.be.true;
+        });
+    });
+
+    it("versions accounts and refuses to re-migrate a current vault", async () => {
+        const vault = await program.account.vault.fetch(vaultPda);
+        expect(vault.version).to.equal(6);
+        expect(vault.shareTokenProgram.toBase58()).to.equal(TOKEN_PROGRAM_ID.toBase58());
+
+        const record = await program.account.epochRecord.fetch(deriveEpochRecordPda(1));
+        expect(record.version).to.equal(6);
+
+        try {
+            await program.methods
+                .migrateVault()
+                .accountsPartial({ vault: vaultPda, underlyingMint, authority: authority.publicKey })
+                .rpc();
+            expect.fail("Should have thrown an error");
+        } catch (error) {
+            expect(error.message).to.include("VaultAlreadyMigrated");
+        }
+    });
+
+    describe("conversion views on a vault holding quote", () => {
+        // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts:
+        // 1 token of underlying and 0.5 USDC of quote against 1 share, no fees
+        const quoteVaultPda = new anchor.web3.PublicKey("CFnbMoaqz5yQU6WBYg68HiisVj2rBfgkGtWLfWw2u2aT");
+        const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+        const [quoteAssetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("asset"), Buffer.from("QUOTEx")],
+            oracleProgram.programId
+        );
+        let pythPriceAccount: anchor.web3.PublicKey;
+
+        before(async () => {
+            pythPriceAccount = await createMockPythAccount(provider);
+            await oracleProgram.methods.initializeAsset("QUOTEx", pythPriceAccount).rpc();
+            // 100 USDC per token, so the quote balance is worth 0.005 tokens
+            await setMockPythPrice(provider, pythPriceAccount, 100_000_000);
+        });
+
+        it("values the quote balance at the oracle price", async () => {
+            const vault = await program.account.vault.fetch(quoteVaultPda);
+            expect(vault.totalQuoteAssets.toNumber()).to.equal(500_000);
+
+            const oracleAccounts = {
+                vault: quoteVaultPda,
+                assetConfig: quoteAssetConfigPda,
+                pythPriceAccount,
+                oracleProgram: oracleProgram.programId,
+            };
+
+            // NAV of 1.005 tokens per share
+            const shares = await program.methods
+                .convertToShares(new anchor.BN(100_000))
+                .accountsPartial(oracleAccounts)
+                .view();
+            expect(shares.toNumber()).to.equal(99_502);
+
+            const previewShares = await program.methods
+                .previewDeposit(new anchor.BN(100_000))
+                .accountsPartial(oracleAccounts)
+                .view();
+            expect(previewShares.toNumber()).to.equal(99_502);
+
+            const assets = await program.methods
+                .convertToAssets(new anchor.BN(100_000))
+                .accountsPartial(oracleAccounts)
+                .view();
+            expect(assets.toNumber()).to.equal(100_500);
+        });
+
+        it("needs the oracle accounts to price shares", async () => {
+            try {
+                await program.methods
+                    .convertToAssets(new anchor.BN(100_000))
+                    .accountsPartial({ vault: quoteVaultPda })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("MissingOracleAccounts");
+            }
+        });
+
+        it("previews the quote paid on redemption separately", async () => {
+            const redeem = await program.methods
+                .previewRedeem(new anchor.BN(100_000))
+                .accountsPartial({ vault: quoteVaultPda })
+                .view();
+            // 0.5% fee on each leg
+            expect(redeem.assets.toNumber()).to.equal(99_500);
+            expect(redeem.fee.toNumber()).to.equal(500);
+            expect(redeem.quoteAssets.toNumber()).to.equal(49_750);
+            expect(redeem.quoteFee.toNumber()).to.equal(250);
+        });
+    });
+
+    describe("redemptions after accrued fees", () => {
+        // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts: 1 token against
+        // 1 share, with a 10% performance fee on the gain above a 0.9 high-water mark
+        const feeVaultPda = new anchor.web3.PublicKey("2rvwqjDX2PzmsqxxY6TVWy1wnQhduaVnEkfKf5S93joB");
+
+        it("prices an instant redemption at NAV after the fees accrued since the roll", async () => {
+            const assets = await program.methods
+                .convertToAssets(new anchor.BN(100_000))
+                .accountsPartial({ vault: feeVaultPda })
+                .view();
+            // 0.01 tokens of fee, minted as 10,101 shares at the roll
+            expect(assets.toNumber()).to.equal(99_000);
+
+            const redeem = await program.methods
+                .previewRedeem(new anchor.BN(100_000))
+                .accountsPartial({ vault: feeVaultPda })
+                .view();
+            expect(redeem.assets.toNumber()).to.equal(assets.toNumber());
+            expect(redeem.fee.toNumber()).to.equal(0);
+        });
+    });
+
+    describe("instant withdrawals after expiry", () => {
+        // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts: 1 token of
+        // notional recorded against 2 tokens of assets, expired in 2023 and not settled
+        const expiredVaultPda = new anchor.web3.PublicKey("5fAYvfSMrth1H6tUfTipg8LuENFWQL2BwgPSzWBJktsb");
+        const expiredVaultUserShares = new anchor.web3.PublicKey("GYyXhJhMsfLmqHV6M5N6fjtfFRUinzAG1PNemsJNW29w");
+
+        it("allows nothing to be withdrawn until the epoch is settled", async () => {
+            const vault = await program.account.vault.fetch(expiredVaultPda);
+            expect(vault.epochNotionalExposed.toNumber()).to.equal(1_000_000);
+            expect(vault.epochSettled).to.be.false;
+
+            const maxWithdraw = await program.methods
+                .maxWithdraw()
+                .accountsPartial({ vault: expiredVaultPda, userShareAccount: expiredVaultUserShares })
+                .view();
+            expect(maxWithdraw.toNumber()).to.equal(0);
+        });
+    });
+
+    describe("attested settlement after an oracle outage", () => {
+        // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts: a call on 1 token
+        // struck at 110, recorded in epoch 1 and expired in 2023 without being settled
+        const legacyUser = anchor.web3.Keypair.fromSecretKey(
+            Uint8Array.from(require("./fixtures/legacy-user.json"))
+        );
+        const attestVaultPda = new anchor.web3.PublicKey("3tjPf7XJVBDbgDeAb8Gi2Dsqzzc7uopSKf9sQ2HEjQXS");
+        const attestRfqPda = new anchor.web3.PublicKey("4sftUsLYgDCGBer8KRMY7zrRDUHXtG98X2BMJGB2diTc");
+        const attestRecordedRfqPda = new anchor.web3.PublicKey("F5ehPxYpr61s1M7MBxPPKeMVM39AJkhT7yeZQBs9de3z");
+        const attestLockedCollateral = new anchor.web3.PublicKey("5uPRgTwUPYAPQp2a42v7ZFPoXqNknsH4vcDdo5FekERm");
+        const legacyUnderlyingMint = new anchor.web3.PublicKey("5B27X4xTVdufbSTDeb5t8Lt5u2zVL9ribgUJu9g1uaKM");
+        const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+        const [attestAssetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("asset"), Buffer.from("ATTESTx")],
+            oracleProgram.programId
+        );
+        let pythPriceAccount: anchor.web3.PublicKey;
+        let makerTokenAccount: anchor.web3.PublicKey;
+
+        before(async () => {
+            // The oracle is back, but never recorded an observation for the expiry slot
+            pythPriceAccount = await createMockPythAccount(provider);
+            await oracleProgram.methods.initializeAsset("ATTESTx", pythPriceAccount).rpc();
+            await oracleProgram.methods
+                .initializePriceHistory()
+                .accounts({ assetConfig: attestAssetConfigPda })
+                .rpc();
+            await setMockPythPrice(provider, pythPriceAccount, 120_000_000);
+
+            makerTokenAccount = (
+                await getOrCreateAssociatedTokenAccount(
+                    provider.connection,
+                    (provider.wallet as anchor.Wallet).payer,
+                    legacyUnderlyingMint,
+                    legacyUser.publicKey
+                )
+            ).address;
+            await provider.connection.confirmTransaction(
+                await provider.connection.requestAirdrop(legacyUser.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+            );
+        });
+
+        it("settles against the authority's price once the grace period is over", async () => {
+            const settle = () =>
+                program.methods
+                    .settleRfq()
+                    .accountsPartial({
+                        vault: attestVaultPda,
+                        rfq: attestRfqPda,
+                        recordedRfq: attestRecordedRfqPda,
+                        lockedCollateralAccount: attestLockedCollateral,
+                        makerTokenAccount,
+                        makerOptionAccount: null,
+                        quoteTokenAccount: null,
+                        assetConfig: attestAssetConfigPda,
+                    })
+                    .rpc();
+
+            try {
+                await settle();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("ObservationMissing");
+            }
+
+            // Only the vault authority can attest
+            try {
+                await program.methods
+                    .attestSettlementPrice(new anchor.BN(120_000_000))
+                    .accountsPartial({
+                        vault: attestVaultPda,
+                        rfq: attestRfqPda,
+                        recordedRfq: attestRecordedRfqPda,
+                        authority: authority.publicKey,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("ConstraintHasOne");
+            }
+
+            await program.methods
+                .attestSettlementPrice(new anchor.BN(120_000_000))
+                .accountsPartial({
+                    vault: attestVaultPda,
+                    rfq: attestRfqPda,
+                    recordedRfq: attestRecordedRfqPda,
+                    authority: legacyUser.publicKey,
+                })
+                .signers([legacyUser])
+                .rpc();
+
+            // Settles at 120 instead of reading the oracle: 1 * 10 / 120 tokens to the maker
+            await settle();
+
+            const recorded = await program.account.recordedRfq.fetch(attestRecordedRfqPda);
+            expect(recorded.settled).to.be.true;
+            expect(recorded.settlementPrice.toNumber()).to.equal(120_000_000);
+            expect(recorded.payout.toNumber()).to.equal(83_333);
+
+            const maker = await getAccount(provider.connection, makerTokenAccount);
+            expect(Number(maker.amount)).to.equal(83_333);
+
+            const vault = await program.account.vault.fetch(attestVaultPda);
+            expect(vault.epochSettledNotional.toNumber()).to.equal(1_000_000);
+            expect(vault.epochSettlementPrice.toNumber()).to.equal(120_000_000);
+            expect(vault.totalAssets.toNumber()).to.equal(2_000_000 - 83_333);
+        });
+    });
+
+    describe("legacy vault migration", () => {
+        // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts
+        const legacyUser = anchor.web3.Keypair.fromSecretKey(
+            Uint8Array.from(require("./fixtures/legacy-user.json"))
+        );
+        const legacyVaultPda = new anchor.web3.PublicKey("DtXwJNAWyXE6LqJwekCsURGMMsr38MbgxtKABFF4w9S4");
+        const legacyWithdrawalPda = new anchor.web3.PublicKey("FVd3v9rJ347HEoEHKWBWc6x5C6Vp6hkhZFGJMoQsX3Tr");
+        const legacyUnderlyingMint = new anchor.web3.PublicKey("5B27X4xTVdufbSTDeb5t8Lt5u2zVL9ribgUJu9g1uaKM");
+        const legacyUserShares = new anchor.web3.PublicKey("5WBorch535g1xxNAUHXaHZb2AvCizAUQoMGpLAH7SiyK");
+        const [legacyWithdrawalSharesPda] = anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("withdrawal_shares"), legacyVaultPda.toBuffer()],
+            program.programId
+        );
+
+        before(async () => {
+            await provider.connection.confirmTransaction(
+                await provider.connection.requestAirdrop(legacyUser.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+            );
+        });
+
+        it("upgrades a vault in the original layout", async () => {
+            const before = await provider.connection.getAccountInfo(legacyVaultPda);
+            expect(before.data.length).to.equal(263);
+
+            await program.methods
+                .migrateVault()
+                .accountsPartial({
+                    vault: legacyVaultPda,
+                    underlyingMint: legacyUnderlyingMint,
+                    authority: legacyUser.publicKey,
+                })
+                .signers([legacyUser])
+                .rpc();
+
+            const vault = await program.account.vault.fetch(legacyVaultPda);
+            expect(vault.version).to.equal(6);
+            expect(vault.assetId).to.equal("LEGACYx");
+            expect(vault.totalAssets.toNumber()).to.equal(5_000_000);
+            expect(vault.totalShares.toNumber()).to.equal(5_000_000);
+            expect(vault.epoch.toNumber()).to.equal(3);
+            expect(vault.utilizationCapBps).to.equal(8000);
+            expect(vault.underlyingDecimals).to.equal(6);
+            expect(vault.keeper.toBase58()).to.equal(legacyUser.publicKey.toBase58());
+            expect(vault.feeRecipient.toBase58()).to.equal(legacyUser.publicKey.toBase58());
+            expect(vault.optionMint.toBase58()).to.equal(legacyUnderlyingMint.toBase58());
+            expect(vault.highWaterMark.toNumber()).to.equal(1_000_000_000);
+            expect(vault.shareTokenProgram.toBase58()).to.equal(TOKEN_PROGRAM_ID.toBase58());
+            expect(vault.openAuctions).to.equal(0);
+            expect(vault.lockedCollateralPayer.toBase58()).to.equal(legacyUser.publicKey.toBase58());
+            // Off-chain settled exposure and unescrowed withdrawals are cleared
+            expect(vault.epochNotionalExposed.toNumber()).to.equal(0);
+            expect(vault.pendingWithdrawals.toNumber()).to.equal(0);
+        });
+
+        it("escrows the shares of an old withdrawal request", async () => {
+            const before = await provider.connection.getAccountInfo(legacyWithdrawalPda);
+            expect(before.data.length).to.equal(90);
+
+            await program.methods
+                .migrateWithdrawal()
+                .accountsPartial({
+                    vault: legacyVaultPda,
+                    withdrawalRequest: legacyWithdrawalPda,
+                    userShareAccount: legacyUserShares,
+                    user: legacyUser.publicKey,
+                })
+                .signers([legacyUser])
+                .rpc();
+
+            const withdrawal = await program.account.withdrawalRequest.fetch(legacyWithdrawalPda);
+            expect(withdrawal.version).to.equal(6);
+            expect(withdrawal.shares.toNumber()).to.equal(2_000_000);
+            expect(withdrawal.requestEpoch.toNumber()).to.equal(3);
+
+            const escrow = await getAccount(provider.connection, legacyWithdrawalSharesPda);
+            expect(Number(escrow.amount)).to.equal(2_000_000);
+            const userShares = await getAccount(provider.connection, legacyUserShares);
+            expect(Number(userShares.amount)).to.equal(3_000_000);
+
+            const vault = await program.account.vault.fetch(legacyVaultPda);
+            expect(vault.pendingWithdrawals.toNumber()).to.equal(2_000_000);
+
+            try {
+                await program.methods
+                    .migrateWithdrawal()
+                    .accountsPartial({
+                        vault: legacyVaultPda,
+                        withdrawalRequest: legacyWithdrawalPda,
+                        userShareAccount: legacyUserShares,
+                        user: legacyUser.publicKey,
+                    })
+                    .signers([legacyUser])
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("WithdrawalAlreadyMigrated");
+            }
+        });
+    });
+
+    describe("Token-2022 share mint", () => {
+        const t22AssetId = "NVDAx-T22";
+        let t22VaultPda: anchor.web3.PublicKey;
+        let t22ShareMintPda: anchor.web3.PublicKey;
+
+        before(() => {
+            [t22VaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("vault"), Buffer.from(t22AssetId)],
+                program.programId
+            );
+            [t22ShareMintPda] = anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("shares"), t22VaultPda.toBuffer()],
+                program.programId
+            );
+        });
+
+        it("keeps the share metadata on the mint", async () => {
+            await program.methods
+                .initializeVaultToken2022(
+                    t22AssetId,
+                    5000,
+                    new anchor.BN(SEED_AMOUNT),
+                    "Vault NVDAx",
+                    "vNVDAx",
+                    "https://example.com/vnvdax.json"
+                )
+                .accounts({
+                    underlyingMint: underlyingMint,
+                    authorityTokenAccount: userTokenAccount,
+                })
+                .rpc();
+
+            const vault = await program.account.vault.fetch(t22VaultPda);
+            expect(vault.shareTokenProgram.toBase58()).to.equal(TOKEN_2022_PROGRAM_ID.toBase58());
+            expect(vault.totalShares.toNumber()).to.equal(SEED_AMOUNT);
+
+            const metadata = await getTokenMetadata(provider.connection, t22ShareMintPda);
+            expect(metadata.name).to.equal("Vault NVDAx");
+            expect(metadata.symbol).to.equal("vNVDAx");
+            expect(metadata.updateAuthority.toBase58()).to.equal(t22VaultPda.toBase58());
+        });
+
+        it("updates the share metadata in place", async () => {
+            // A longer URI grows the mint, which the authority funds
+            const uri = "https://raw.githubusercontent.com/feeniks01/xstock-options/main/app/public/metadata/vnvdax.json";
+            await program.methods
+                .updateShareMetadata("Vault NVDAx Covered Call", "vNVDAx", uri)
+                .accountsPartial({
+                    vault: t22VaultPda,
+                    metadata: null,
+                    tokenMetadataProgram: null,
+                })
+                .rpc();
+
+            const metadata = await getTokenMetadata(provider.connection, t22ShareMintPda);
+            expect(metadata.name).to.equal("Vault NVDAx Covered Call");
+            expect(metadata.uri).to.equal(uri);
+        });
+
+        it("rejects metadata updates from anyone but the authority", async () => {
+            const stranger = anchor.web3.Keypair.generate();
+            await provider.connection.confirmTransaction(
+                await provider.connection.requestAirdrop(stranger.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+            );
+
+            try {
+                await program.methods
+                    .updateShareMetadata("Rugged", "RUG", "https://example.com/rug.json")
+                    .accountsPartial({
+                        vault: t22VaultPda,
+                        authority: stranger.publicKey,
+                        metadata: null,
+                        tokenMetadataProgram: null,
+                    })
+                    .signers([stranger])
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("ConstraintHasOne");
+            }
+        });
+
+        it("requires the Metaplex accounts for SPL Token share mints", async () => {
+            try {
+                await program.methods
+                    .updateShareMetadata("Vault NVDAx", "vNVDAx", "https://example.com/vnvdax.json")
+                    .accountsPartial({
+                        vault: vaultPda,
+                        metadata: null,
+                        tokenMetadataProgram: null,
+                    })
+                    .rpc();
+                expect.fail("Should have thrown an error");
+            } catch (error) {
+                expect(error.message).to.include("MissingMetadataAccounts");
+            }
+        });
     });
 });
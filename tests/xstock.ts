@@ -1,6 +1,8 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { XstockOptions } from "../target/types/xstock_options";
+import { Oracle } from "../target/types/oracle";
+import { Vault } from "../target/types/vault";
 import {
   createMint,
   getOrCreateAssociatedTokenAccount,
@@ -17,6 +19,7 @@ import {
 } from "@metaplex-foundation/umi";
 import { createMetadataAccountV3 } from "@metaplex-foundation/mpl-token-metadata";
 import { fromWeb3JsPublicKey } from "@metaplex-foundation/umi-web3js-adapters";
+import { createMockPythAccount, setMockPythPrice } from "./mock-pyth";
 
 const homeDir = os.homedir();
 const keypairPath = `${homeDir}/.config/solana/id.json`;
@@ -139,9 +142,9 @@ describe("xstock_options", () => {
       quoteMint,
       buyerQuoteAccount,
       userKeypair,
-      10000 * 1_000_000 // 10,000 USDC
+      100_000 * 1_000_000 // 100,000 USDC
     );
-    console.log("Minted 10,000 Test USDC");
+    console.log("Minted 100,000 Test USDC");
 
     await mintTo(
       provider.connection,
@@ -227,7 +230,7 @@ describe("xstock_options", () => {
       );
 
       await program.methods
-        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT))
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
         .accounts({
           seller: seller.publicKey,
           xstockMint,
@@ -261,7 +264,7 @@ describe("xstock_options", () => {
       if (Number(currentBalance.amount) < XSTOCK_AMOUNT) {
         try {
           await program.methods
-            .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT))
+            .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
             .accounts({
               seller: seller.publicKey,
               xstockMint,
@@ -365,7 +368,7 @@ describe("xstock_options", () => {
       );
 
       await program.methods
-        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiredTs, new anchor.BN(XSTOCK_AMOUNT))
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiredTs, new anchor.BN(XSTOCK_AMOUNT), 0)
         .accounts({
           seller: seller.publicKey,
           xstockMint,
@@ -486,7 +489,7 @@ describe("xstock_options", () => {
       );
 
       await program.methods
-        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT))
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
         .accounts({
           seller: seller.publicKey,
           xstockMint,
@@ -547,7 +550,7 @@ describe("xstock_options", () => {
       );
 
       await program.methods
-        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT))
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
         .accounts({
           seller: seller.publicKey,
           xstockMint,
@@ -608,7 +611,7 @@ describe("xstock_options", () => {
       );
 
       await program.methods
-        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, shortExpiryTs, new anchor.BN(XSTOCK_AMOUNT))
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, shortExpiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
         .accounts({
           seller: seller.publicKey,
           xstockMint,
@@ -658,7 +661,7 @@ describe("xstock_options", () => {
       );
 
       await program.methods
-        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT))
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
         .accounts({
           seller: seller.publicKey,
           xstockMint,
@@ -719,7 +722,7 @@ describe("xstock_options", () => {
       );
 
       await program.methods
-        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT))
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
         .accounts({
           seller: seller.publicKey,
           xstockMint,
@@ -777,4 +780,817 @@ describe("xstock_options", () => {
     });
 
   });
+
+  describe("writer royalty", () => {
+    const ROYALTY_BPS = 500; // 5% of secondary sales
+
+    it("Stores the writer royalty on creation", async () => {
+      const uid = new anchor.BN(10);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      const [royaltyCallPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("covered_call"),
+          seller.publicKey.toBuffer(),
+          xstockMint.toBuffer(),
+          uid.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), ROYALTY_BPS)
+        .accounts({
+          seller: seller.publicKey,
+          xstockMint,
+          quoteMint,
+          sellerXstockAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      const coveredCall = await program.account.coveredCall.fetch(royaltyCallPda);
+      expect(coveredCall.royaltyBps).to.equal(ROYALTY_BPS);
+    });
+
+    it("Rejects a royalty above 10%", async () => {
+      const uid = new anchor.BN(11);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      try {
+        await program.methods
+          .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 1_001)
+          .accounts({
+            seller: seller.publicKey,
+            xstockMint,
+            quoteMint,
+            sellerXstockAccount,
+          })
+          .signers([seller])
+          .rpc();
+        expect.fail("Should have failed - royalty too high");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("InvalidRoyalty");
+      }
+    });
+  });
+
+  describe("barrier options", () => {
+    const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+    const barrierAssetId = "NVDAxBARRIER";
+    const BARRIER_LEVEL = new anchor.BN(120_000_000); // 120 USDC (6 decimals)
+    const KNOCK_OUT_LEVEL = new anchor.BN(180_000_000); // 180 USDC (6 decimals)
+
+    let assetConfigPda: anchor.web3.PublicKey;
+    let pythPriceAccount: anchor.web3.PublicKey;
+    let downAndInCallPda: anchor.web3.PublicKey;
+
+    const triggerBarrier = (coveredCall: anchor.web3.PublicKey) =>
+      program.methods
+        .triggerBarrier()
+        .accounts({
+          coveredCall,
+          assetConfig: assetConfigPda,
+          pythPriceAccount,
+        })
+        .rpc();
+
+    before(async () => {
+      [assetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(barrierAssetId)],
+        oracleProgram.programId
+      );
+
+      pythPriceAccount = await createMockPythAccount(provider);
+      await oracleProgram.methods
+        .initializeAsset(barrierAssetId, pythPriceAccount)
+        .rpc();
+      await oracleProgram.methods
+        .registerAssetMint()
+        .accounts({ assetConfig: assetConfigPda, mint: xstockMint })
+        .rpc();
+    });
+
+    it("Creates a down-and-in barrier call", async () => {
+      const uid = new anchor.BN(20);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      [downAndInCallPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("covered_call"),
+          seller.publicKey.toBuffer(),
+          xstockMint.toBuffer(),
+          uid.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .createBarrierCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), {
+          barrierType: { downAndIn: {} },
+          level: BARRIER_LEVEL,
+        })
+        .accounts({
+          seller: seller.publicKey,
+          xstockMint,
+          quoteMint,
+          assetConfig: assetConfigPda,
+          sellerXstockAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      const coveredCall = await program.account.coveredCall.fetch(downAndInCallPda);
+      expect(coveredCall.barrierType).to.deep.equal({ downAndIn: {} });
+      expect(coveredCall.barrier.toNumber()).to.equal(BARRIER_LEVEL.toNumber());
+      expect(coveredCall.oracleAsset.toString()).to.equal(assetConfigPda.toString());
+      expect(coveredCall.barrierTriggered).to.be.false;
+    });
+
+    it("Cannot trigger a barrier the price has not crossed", async () => {
+      await setMockPythPrice(provider, pythPriceAccount, 130_000_000);
+
+      try {
+        await triggerBarrier(downAndInCallPda);
+        expect.fail("Should have failed - price is above the down-and-in barrier");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("BarrierNotCrossed");
+      }
+    });
+
+    it("Knocks in a down-and-in call once the price falls to the barrier", async () => {
+      await setMockPythPrice(provider, pythPriceAccount, 115_000_000);
+      await triggerBarrier(downAndInCallPda);
+
+      const coveredCall = await program.account.coveredCall.fetch(downAndInCallPda);
+      expect(coveredCall.barrierTriggered).to.be.true;
+    });
+
+    it("Knocks out an up-and-out call once the price reaches the barrier", async () => {
+      const uid = new anchor.BN(23);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+      const [upAndOutCallPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("covered_call"),
+          seller.publicKey.toBuffer(),
+          xstockMint.toBuffer(),
+          uid.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .createBarrierCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), {
+          barrierType: { upAndOut: {} },
+          level: KNOCK_OUT_LEVEL,
+        })
+        .accounts({
+          seller: seller.publicKey,
+          xstockMint,
+          quoteMint,
+          assetConfig: assetConfigPda,
+          sellerXstockAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      await setMockPythPrice(provider, pythPriceAccount, 185_000_000);
+      await triggerBarrier(upAndOutCallPda);
+
+      const coveredCall = await program.account.coveredCall.fetch(upAndOutCallPda);
+      expect(coveredCall.barrierTriggered).to.be.true;
+
+      try {
+        await program.methods
+          .buyOption()
+          .accounts({
+            buyer: buyer.publicKey,
+            coveredCall: upAndOutCallPda,
+            buyerQuoteAccount,
+            paymentAccount: sellerQuoteAccount,
+          })
+          .signers([buyer])
+          .rpc();
+        expect.fail("Should have failed - option is knocked out");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("OptionKnockedOut");
+      }
+    });
+
+    it("Cannot create a barrier call without a barrier", async () => {
+      const uid = new anchor.BN(21);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      try {
+        await program.methods
+          .createBarrierCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), {
+            barrierType: { none: {} },
+            level: BARRIER_LEVEL,
+          })
+          .accounts({
+            seller: seller.publicKey,
+            xstockMint,
+            quoteMint,
+            assetConfig: assetConfigPda,
+            sellerXstockAccount,
+          })
+          .signers([seller])
+          .rpc();
+        expect.fail("Should have failed - barrier type is none");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("InvalidBarrier");
+      }
+    });
+
+    it("Rejects an oracle asset registered for another mint", async () => {
+      const uid = new anchor.BN(22);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+      const otherAssetId = "USDCxBARRIER";
+      const [otherAssetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(otherAssetId)],
+        oracleProgram.programId
+      );
+      await oracleProgram.methods
+        .initializeAsset(otherAssetId, anchor.web3.Keypair.generate().publicKey)
+        .rpc();
+      await oracleProgram.methods
+        .registerAssetMint()
+        .accounts({ assetConfig: otherAssetConfigPda, mint: quoteMint })
+        .rpc();
+
+      try {
+        await program.methods
+          .createBarrierCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), {
+            barrierType: { downAndIn: {} },
+            level: BARRIER_LEVEL,
+          })
+          .accounts({
+            seller: seller.publicKey,
+            xstockMint,
+            quoteMint,
+            assetConfig: otherAssetConfigPda,
+            sellerXstockAccount,
+          })
+          .signers([seller])
+          .rpc();
+        expect.fail("Should have failed - oracle asset prices the quote mint");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("InvalidOracleAsset");
+      }
+    });
+  });
+
+  describe("binary options", () => {
+    const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+    const binaryAssetId = "NVDAxBINARY";
+    const PAYOUT = new anchor.BN(100_000_000); // 100 USDC (6 decimals)
+
+    let assetConfigPda: anchor.web3.PublicKey;
+    let binaryOptionPda: anchor.web3.PublicKey;
+    let binaryVaultPda: anchor.web3.PublicKey;
+
+    before(async () => {
+      [assetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(binaryAssetId)],
+        oracleProgram.programId
+      );
+
+      await oracleProgram.methods
+        .initializeAsset(binaryAssetId, anchor.web3.Keypair.generate().publicKey)
+        .rpc();
+      await oracleProgram.methods
+        .registerAssetMint()
+        .accounts({ assetConfig: assetConfigPda, mint: xstockMint })
+        .rpc();
+
+      await mintTo(
+        provider.connection,
+        userKeypair,
+        quoteMint,
+        sellerQuoteAccount,
+        userKeypair,
+        PAYOUT.toNumber()
+      );
+    });
+
+    it("Creates a digital call and escrows the payout", async () => {
+      const uid = new anchor.BN(30);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      [binaryOptionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("covered_call"),
+          seller.publicKey.toBuffer(),
+          xstockMint.toBuffer(),
+          uid.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      [binaryVaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), binaryOptionPda.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .createBinaryOption(uid, STRIKE_PRICE, PREMIUM, expiryTs, PAYOUT, { digitalCall: {} })
+        .accounts({
+          seller: seller.publicKey,
+          xstockMint,
+          quoteMint,
+          assetConfig: assetConfigPda,
+          sellerQuoteAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      const option = await program.account.coveredCall.fetch(binaryOptionPda);
+      expect(option.payoff).to.deep.equal({ digitalCall: {} });
+      expect(option.amount.toNumber()).to.equal(PAYOUT.toNumber());
+
+      const escrow = await getAccount(provider.connection, binaryVaultPda);
+      expect(escrow.mint.toString()).to.equal(quoteMint.toString());
+      expect(Number(escrow.amount)).to.equal(PAYOUT.toNumber());
+    });
+
+    it("Cannot settle an unsold binary option", async () => {
+      try {
+        await program.methods
+          .settleBinary()
+          .accountsPartial({
+            coveredCall: binaryOptionPda,
+            vaultAccount: binaryVaultPda,
+            assetConfig: assetConfigPda,
+            buyerQuoteAccount,
+            sellerQuoteAccount,
+          })
+          .rpc();
+        expect.fail("Should have failed - option has not been sold");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("OptionNotSold");
+      }
+    });
+
+    it("Seller can reclaim the payout of an unsold binary option", async () => {
+      await program.methods
+        .reclaim()
+        .accounts({
+          seller: seller.publicKey,
+          coveredCall: binaryOptionPda,
+          sellerXstockAccount: sellerQuoteAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      const option = await program.account.coveredCall.fetch(binaryOptionPda);
+      expect(option.cancelled).to.be.true;
+    });
+
+    // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts: a digital call
+    // and a digital put struck at 150, each sold with a 100 USDC payout and expired in
+    // 2023, and the 160 observation recorded for their expiry slot
+    const binaryBuyerQuoteAccount = new anchor.web3.PublicKey("AeFoCrBPkc4LSLE9iGMgrbpF9XMNuvsz6pb8cenByFt2");
+    const binarySellerQuoteAccount = new anchor.web3.PublicKey("F28UuRjUAGAap91kQM6PvMmST22a4BH4Vs3i7A3yvPcy");
+
+    it("Settles a sold digital call in the money on the expiry observation", async () => {
+      const binaryCallPda = new anchor.web3.PublicKey("74XDGYN8kcf62BTwbnai5ARF5LZ1QUApYUj6DARJbKt7");
+      const buyerBefore = await getAccount(provider.connection, binaryBuyerQuoteAccount);
+
+      await program.methods
+        .settleBinary()
+        .accounts({
+          coveredCall: binaryCallPda,
+          assetConfig: assetConfigPda,
+          buyerQuoteAccount: binaryBuyerQuoteAccount,
+          sellerQuoteAccount: binarySellerQuoteAccount,
+        })
+        .rpc();
+
+      const buyerAfter = await getAccount(provider.connection, binaryBuyerQuoteAccount);
+      expect(Number(buyerAfter.amount - buyerBefore.amount)).to.equal(PAYOUT.toNumber());
+
+      const option = await program.account.coveredCall.fetch(binaryCallPda);
+      expect(option.exercised).to.be.true;
+      expect(option.buyerExercised).to.be.true;
+    });
+
+    it("Settles a sold digital put out of the money back to the seller", async () => {
+      const binaryPutPda = new anchor.web3.PublicKey("3CRdmMa62UpFHDJYqGoZDpx65y8ZxLgGt9Y6axgivdey");
+      const buyerBefore = await getAccount(provider.connection, binaryBuyerQuoteAccount);
+      const sellerBefore = await getAccount(provider.connection, binarySellerQuoteAccount);
+
+      await program.methods
+        .settleBinary()
+        .accounts({
+          coveredCall: binaryPutPda,
+          assetConfig: assetConfigPda,
+          buyerQuoteAccount: binaryBuyerQuoteAccount,
+          sellerQuoteAccount: binarySellerQuoteAccount,
+        })
+        .rpc();
+
+      const buyerAfter = await getAccount(provider.connection, binaryBuyerQuoteAccount);
+      expect(Number(buyerAfter.amount - buyerBefore.amount)).to.equal(0);
+      const sellerAfter = await getAccount(provider.connection, binarySellerQuoteAccount);
+      expect(Number(sellerAfter.amount - sellerBefore.amount)).to.equal(PAYOUT.toNumber());
+
+      const option = await program.account.coveredCall.fetch(binaryPutPda);
+      expect(option.exercised).to.be.true;
+      expect(option.buyerExercised).to.be.false;
+    });
+  });
+
+  describe("asian options", () => {
+    const oracleProgram = anchor.workspace.Oracle as Program<Oracle>;
+    const asianAssetId = "NVDAxASIAN";
+    const AVERAGING_WINDOW = new anchor.BN(3600); // 1 hour
+
+    let assetConfigPda: anchor.web3.PublicKey;
+    let asianCallPda: anchor.web3.PublicKey;
+
+    before(async () => {
+      [assetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(asianAssetId)],
+        oracleProgram.programId
+      );
+
+      await oracleProgram.methods
+        .initializeAsset(asianAssetId, anchor.web3.Keypair.generate().publicKey)
+        .rpc();
+      await oracleProgram.methods
+        .registerAssetMint()
+        .accounts({ assetConfig: assetConfigPda, mint: xstockMint })
+        .rpc();
+      await oracleProgram.methods
+        .initializePriceHistory()
+        .accounts({ assetConfig: assetConfigPda })
+        .rpc();
+
+      [asianCallPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("covered_call"),
+          seller.publicKey.toBuffer(),
+          xstockMint.toBuffer(),
+          new anchor.BN(40).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+    });
+
+    it("Creates an Asian call with an averaging window", async () => {
+      const uid = new anchor.BN(40);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      await program.methods
+        .createAsianCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), AVERAGING_WINDOW)
+        .accounts({
+          seller: seller.publicKey,
+          xstockMint,
+          quoteMint,
+          assetConfig: assetConfigPda,
+          sellerXstockAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      const coveredCall = await program.account.coveredCall.fetch(asianCallPda);
+      expect(coveredCall.averagingWindow.toNumber()).to.equal(AVERAGING_WINDOW.toNumber());
+      expect(coveredCall.oracleAsset.toString()).to.equal(assetConfigPda.toString());
+      expect(coveredCall.averagedUntil.toNumber()).to.equal(0);
+      expect(coveredCall.averageObservations).to.equal(0);
+    });
+
+    it("Refuses to checkpoint before the averaging window starts", async () => {
+      try {
+        await program.methods
+          .checkpointAverage()
+          .accounts({
+            coveredCall: asianCallPda,
+            assetConfig: assetConfigPda,
+          })
+          .rpc();
+        expect.fail("Should have failed - averaging window has not started");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("NothingToCheckpoint");
+      }
+    });
+
+    it("Rejects an averaging window shorter than one observation slot", async () => {
+      const uid = new anchor.BN(42);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      try {
+        await program.methods
+          .createAsianCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), new anchor.BN(60))
+          .accounts({
+            seller: seller.publicKey,
+            xstockMint,
+            quoteMint,
+            assetConfig: assetConfigPda,
+            sellerXstockAccount,
+          })
+          .signers([seller])
+          .rpc();
+        expect.fail("Should have failed - averaging window too short");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("InvalidAveragingWindow");
+      }
+    });
+
+    it("Rejects an averaging window longer than the price history", async () => {
+      const uid = new anchor.BN(41);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      try {
+        await program.methods
+          .createAsianCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), new anchor.BN(86400 * 7))
+          .accounts({
+            seller: seller.publicKey,
+            xstockMint,
+            quoteMint,
+            assetConfig: assetConfigPda,
+            sellerXstockAccount,
+          })
+          .signers([seller])
+          .rpc();
+        expect.fail("Should have failed - averaging window too long");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("InvalidAveragingWindow");
+      }
+    });
+
+    it("Settles on the average of the observations in its window", async () => {
+      // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts: a call on 100
+      // xStock struck at 100, sold and expired in 2023, averaging the hour before expiry
+      // over observations at 100, 110, 120 and 130
+      const legacyUser = anchor.web3.Keypair.fromSecretKey(
+        Uint8Array.from(require("./fixtures/legacy-user.json"))
+      );
+      const averageCallPda = new anchor.web3.PublicKey("9T3LMWyugvQ1ZdqdJx11irsx5EnHedLyngM63rfNuxf9");
+      const averageBuyerXstockAccount = new anchor.web3.PublicKey("APDiR6d26J9jvmZzibzadBkZwmZ9Sqh45a1zswqFTdWf");
+      const legacyXstockMint = new anchor.web3.PublicKey("5B27X4xTVdufbSTDeb5t8Lt5u2zVL9ribgUJu9g1uaKM");
+      const averageAssetId = "NVDAxAVERAGE";
+      const [averageAssetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(averageAssetId)],
+        oracleProgram.programId
+      );
+
+      const pythPriceAccount = await createMockPythAccount(provider);
+      await oracleProgram.methods
+        .initializeAsset(averageAssetId, pythPriceAccount)
+        .rpc();
+
+      // An observation recorded after expiry doesn't move the average. It is published
+      // at the start of the next slot so it is both fresh and slot-aligned.
+      const slot = await provider.connection.getSlot();
+      const now = await provider.connection.getBlockTime(slot);
+      await setMockPythPrice(provider, pythPriceAccount, 200_000_000, Math.ceil(now / 900) * 900);
+      await oracleProgram.methods
+        .recordObservation()
+        .accounts({
+          assetConfig: averageAssetConfigPda,
+          pythPriceAccount,
+        })
+        .rpc();
+
+      await program.methods
+        .checkpointAverage()
+        .accounts({
+          coveredCall: averageCallPda,
+          assetConfig: averageAssetConfigPda,
+        })
+        .rpc();
+
+      let coveredCall = await program.account.coveredCall.fetch(averageCallPda);
+      expect(coveredCall.averagedUntil.toNumber()).to.equal(coveredCall.expiryTs.toNumber());
+      expect(coveredCall.averageObservations).to.equal(4);
+      // 115 held over the 3600 second window
+      expect(coveredCall.averageSum.toString()).to.equal("414000000000");
+
+      const sellerXstockAccount = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          userKeypair,
+          legacyXstockMint,
+          legacyUser.publicKey
+        )
+      ).address;
+      const sellerBefore = await getAccount(provider.connection, sellerXstockAccount);
+
+      await program.methods
+        .settleAverage()
+        .accounts({
+          coveredCall: averageCallPda,
+          assetConfig: averageAssetConfigPda,
+          buyerXstockAccount: averageBuyerXstockAccount,
+          sellerXstockAccount,
+        })
+        .rpc();
+
+      // 100 * (115 - 100) / 115 xStock to the buyer, the rest back to the seller
+      const buyerAccount = await getAccount(provider.connection, averageBuyerXstockAccount);
+      expect(Number(buyerAccount.amount)).to.equal(13_043_478);
+      const sellerAfter = await getAccount(provider.connection, sellerXstockAccount);
+      expect(Number(sellerAfter.amount - sellerBefore.amount)).to.equal(86_956_522);
+
+      coveredCall = await program.account.coveredCall.fetch(averageCallPda);
+      expect(coveredCall.exercised).to.be.true;
+      expect(coveredCall.buyerExercised).to.be.true;
+    });
+
+    it("Refuses to settle an average with a slot of its window unrecorded", async () => {
+      // Loaded from tests/fixtures: the same call, but its history has observations
+      // at 100, 110 and 130 with the third slot missing. The window is still covered
+      // in time, since 110 holds until the next observation.
+      const legacyUser = anchor.web3.Keypair.fromSecretKey(
+        Uint8Array.from(require("./fixtures/legacy-user.json"))
+      );
+      const gappedCallPda = new anchor.web3.PublicKey("Cg32vephR5RS5otE3bKxYUQ25V2xdHQBboTg8PMTZF8P");
+      const averageBuyerXstockAccount = new anchor.web3.PublicKey("APDiR6d26J9jvmZzibzadBkZwmZ9Sqh45a1zswqFTdWf");
+      const legacyXstockMint = new anchor.web3.PublicKey("5B27X4xTVdufbSTDeb5t8Lt5u2zVL9ribgUJu9g1uaKM");
+      const gappedAssetId = "NVDAxGAPPED";
+      const [gappedAssetConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("asset"), Buffer.from(gappedAssetId)],
+        oracleProgram.programId
+      );
+
+      const pythPriceAccount = await createMockPythAccount(provider);
+      await oracleProgram.methods
+        .initializeAsset(gappedAssetId, pythPriceAccount)
+        .rpc();
+
+      const sellerXstockAccount = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          userKeypair,
+          legacyXstockMint,
+          legacyUser.publicKey
+        )
+      ).address;
+
+      try {
+        await program.methods
+          .settleAverage()
+          .accounts({
+            coveredCall: gappedCallPda,
+            assetConfig: gappedAssetConfigPda,
+            buyerXstockAccount: averageBuyerXstockAccount,
+            sellerXstockAccount,
+          })
+          .rpc();
+        expect.fail("Should have failed - a slot of the window has no observation");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("TooFewObservations");
+      }
+
+      const coveredCall = await program.account.coveredCall.fetch(gappedCallPda);
+      expect(coveredCall.exercised).to.be.false;
+      expect(coveredCall.averageObservations).to.equal(0);
+    });
+  });
+
+  describe("migrate_to_vault", () => {
+    const vaultProgram = anchor.workspace.Vault as Program<Vault>;
+    const earnAssetId = "NVDAxMIGRATE";
+    const SEED_AMOUNT = 1_000; // MIN_SEED_DEPOSIT
+
+    let earnVaultPda: anchor.web3.PublicKey;
+    let shareMintPda: anchor.web3.PublicKey;
+    let vaultTokenAccountPda: anchor.web3.PublicKey;
+    let sellerShareAccount: anchor.web3.PublicKey;
+
+    before(async () => {
+      [earnVaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), Buffer.from(earnAssetId)],
+        vaultProgram.programId
+      );
+      [shareMintPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("shares"), earnVaultPda.toBuffer()],
+        vaultProgram.programId
+      );
+      [vaultTokenAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("vault_tokens"), earnVaultPda.toBuffer()],
+        vaultProgram.programId
+      );
+
+      await vaultProgram.methods
+        .initializeVault(earnAssetId, 7500, new anchor.BN(SEED_AMOUNT))
+        .accounts({
+          underlyingMint: xstockMint,
+          authority: seller.publicKey,
+          authorityTokenAccount: sellerXstockAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      const sellerShares = await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        seller,
+        shareMintPda,
+        seller.publicKey
+      );
+      sellerShareAccount = sellerShares.address;
+    });
+
+    it("Seller can move unsold collateral into the vault", async () => {
+      const uid = new anchor.BN(50);
+      const expiryTs = new anchor.BN(Math.floor(Date.now() / 1000) + 86400 * 7);
+
+      const [migrateCallPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("covered_call"),
+          seller.publicKey.toBuffer(),
+          xstockMint.toBuffer(),
+          uid.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .createCoveredCall(uid, STRIKE_PRICE, PREMIUM, expiryTs, new anchor.BN(XSTOCK_AMOUNT), 0)
+        .accounts({
+          seller: seller.publicKey,
+          xstockMint,
+          quoteMint,
+          sellerXstockAccount,
+        })
+        .signers([seller])
+        .rpc();
+
+      // The vault holds only its seed at 1 share per token, so the deposit is worth as many shares
+      await program.methods
+        .migrateToVault(new anchor.BN(XSTOCK_AMOUNT))
+        .accountsPartial({
+          seller: seller.publicKey,
+          coveredCall: migrateCallPda,
+          sellerXstockAccount,
+          earnVault: earnVaultPda,
+          shareMint: shareMintPda,
+          vaultTokenAccount: vaultTokenAccountPda,
+          sellerShareAccount,
+          shareTokenProgram: TOKEN_PROGRAM_ID,
+          depositReceipt: anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("deposit"), earnVaultPda.toBuffer(), seller.publicKey.toBuffer()],
+            vaultProgram.programId
+          )[0],
+          depositor: anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("depositor"), earnVaultPda.toBuffer(), seller.publicKey.toBuffer()],
+            vaultProgram.programId
+          )[0],
+        })
+        .signers([seller])
+        .rpc();
+
+      const coveredCall = await program.account.coveredCall.fetch(migrateCallPda);
+      expect(coveredCall.cancelled).to.be.true;
+
+      // The deposit is queued for the seller until the vault's next epoch roll, which
+      // checks the minimum again
+      const earnVault = await vaultProgram.account.vault.fetch(earnVaultPda);
+      expect(earnVault.totalAssets.toNumber()).to.equal(SEED_AMOUNT);
+      expect(earnVault.pendingDeposits.toNumber()).to.equal(XSTOCK_AMOUNT);
+      expect(earnVault.guardedDeposits).to.equal(1);
+
+      const [depositReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), earnVaultPda.toBuffer(), seller.publicKey.toBuffer()],
+        vaultProgram.programId
+      );
+      const receipt = await vaultProgram.account.depositReceipt.fetch(depositReceiptPda);
+      expect(receipt.amount.toNumber()).to.equal(XSTOCK_AMOUNT);
+      expect(receipt.minSharesOut.toNumber()).to.equal(XSTOCK_AMOUNT);
+    });
+  });
+
+  describe("migrate_covered_call", () => {
+    // Loaded from tests/fixtures, see scripts/build-legacy-fixtures.ts
+    const legacyCoveredCallPda = new anchor.web3.PublicKey("5Bnt17jSTzSmk6uJfau2W7QEG8eB2H9ZWmMsmvAzrc48");
+
+    it("Grows an old covered call to the current layout", async () => {
+      await program.methods
+        .migrateCoveredCall()
+        .accounts({ coveredCall: legacyCoveredCallPda })
+        .rpc();
+
+      const coveredCall = await program.account.coveredCall.fetch(legacyCoveredCallPda);
+      expect(coveredCall.strike.toNumber()).to.equal(150_000_000);
+      expect(coveredCall.askPrice.toNumber()).to.equal(5_000_000);
+      expect(coveredCall.isListed).to.be.true;
+      expect(coveredCall.barrierType).to.deep.equal({ none: {} });
+      expect(coveredCall.payoff).to.deep.equal({ physical: {} });
+      expect(coveredCall.averagingWindow.toNumber()).to.equal(0);
+      expect(coveredCall.royaltyBps).to.equal(0);
+    });
+
+    it("Cannot migrate a covered call twice", async () => {
+      try {
+        await program.methods
+          .migrateCoveredCall()
+          .accounts({ coveredCall: legacyCoveredCallPda })
+          .rpc();
+        expect.fail("Should have failed - already migrated");
+      } catch (error: any) {
+        expect(error.error?.errorCode?.code || error.code).to.equal("CoveredCallAlreadyMigrated");
+      }
+    });
+  });
 });